@@ -19,11 +19,12 @@ async fn main() -> io::Result<()> {
     );
 
     // Send some data to the remote host.
-    client.send("Hello from client!".as_bytes()).await?;
+    client.send("Hello from client!").await.0?;
 
-    // Now read back anything the server sent and then exit.
-    let mut buf = vec![0u8; 1024];
-    let read = client.recv(buf.as_mut_slice()).await?;
+    // Now read back anything the server sent and then exit. Buffers are handed to the event and
+    // returned once it completes.
+    let (read, buf) = client.recv(vec![0u8; 1024]).await;
+    let read = read?;
 
     let str = String::from_utf8_lossy(&buf[..read]);
     println!("Server response: {}", str);
@@ -10,7 +10,7 @@ async fn main() -> io::Result<()> {
     // is set to listen on [::]:9091 and have a connection backlog of 1024.
     let mut listener = TcpListener::with_outstanding("[::]", 9091, 1024)?;
 
-    let mut buf = vec![0u8; 1024];
+    let mut buf = Vec::with_capacity(1024);
 
     println!("Listening on: {}", listener.local_addr());
 
@@ -33,7 +33,12 @@ async fn main() -> io::Result<()> {
 
         println!("Got connection from: {}", conn.peer_addr());
 
-        let read = match conn.recv(buf.as_mut_slice()).await {
+        // The buffer is handed to the receive and returned once it completes, with its length set
+        // to the amount of data read.
+        buf.clear();
+        let (read, returned) = conn.recv(buf).await;
+        buf = returned;
+        let read = match read {
             Ok(ret) => ret,
             Err(e) => {
                 println!("Failed to receive from client: {}", e);
@@ -45,9 +50,9 @@ async fn main() -> io::Result<()> {
 
         println!("Client request: {}", s);
 
-        conn.send(&buf[..read])
-            .await
-            .expect("Failed to respond to client.");
+        let (sent, returned) = conn.send(buf).await;
+        buf = returned;
+        sent.expect("Failed to respond to client.");
     }
     Ok(())
 }
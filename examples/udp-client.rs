@@ -9,12 +9,12 @@ async fn main() -> io::Result<()> {
     println!("Listening for UDP messages on: {:?}", socket.local_addr());
 
     let remote = "[::1]:9092".parse().unwrap();
-    let mut data = String::from("Hello world!").into_bytes();
+    let data = String::from("Hello world!").into_bytes();
 
     println!("Sending UDP message to remote: {:?}", remote);
 
     // We can operate in unconnected mode via send/recv_to and send/recv_msg.
-    match socket.send_to(data.as_mut_slice(), Some(&remote)).await {
+    match socket.send_to(data, Some(&remote)).await.0 {
         Ok(sent) => println!("Sent {} bytes to {:?}.", sent, remote),
         Err(e) => println!("Failed to send data to remote: {}", e),
     };
@@ -22,8 +22,8 @@ async fn main() -> io::Result<()> {
     // As usual we can connect to a remote address and use send/recv directly.
     socket.connect(&remote).await?;
 
-    let mut buf = vec![0u8; 1024];
-    match socket.recv(buf.as_mut_slice()).await {
+    let (recv, buf) = socket.recv(vec![0u8; 1024]).await;
+    match recv {
         Ok(recv) => println!(
             "Received {} bytes from {:?} message: {}",
             recv,
@@ -11,7 +11,9 @@ async fn main() {
     println!("Listening for UDP messages on: {:?}", socket.local_addr());
 
     loop {
-        let (recv, addr, raw) = match socket.recv_msg(bufs.as_mut_slice()).await {
+        let (result, returned) = socket.recv_msg(bufs).await;
+        bufs = returned;
+        let (recv, addr, raw) = match result {
             Ok((recv, addr)) => {
                 let mut raw = Vec::with_capacity(recv);
                 let mut current = recv;
@@ -41,11 +43,11 @@ async fn main() {
             }
         };
 
-        let mut send_bufs = vec![
+        let send_bufs = vec![
             Vec::from_iter(raw[..recv / 2].iter().copied()),
             Vec::from_iter(raw[recv / 2..].iter().copied()),
         ];
-        match socket.send_msg(send_bufs.as_mut_slice(), Some(&addr)).await {
+        match socket.send_msg(send_bufs, Some(&addr)).await.0 {
             Ok(sent) => println!("Sent {} bytes to {:?}.", sent, addr),
             Err(e) => println!("Failed to send data to remote: {}", e),
         };
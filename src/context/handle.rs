@@ -1,8 +1,16 @@
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 use thread_local::ThreadLocal;
 
-use crate::io_uring::UringDriver;
+use crate::io_uring::{UringBuilder, UringDriver};
+
+thread_local! {
+    /// The configuration to use when creating the [UringDriver] for the current thread.
+    static CONFIG: RefCell<Option<UringBuilder>> = const { RefCell::new(None) };
+}
 
 /// Represents a thread local handle to retrieve a [Uring] object from. This is used to
 /// transparently inject the [Uring] into the various [crate::net] implementations. It is generally
@@ -20,14 +28,40 @@ impl Handle {
         }
     }
 
+    /// Set the configuration used to create the [UringDriver] for the current thread, this must
+    /// be called before the first call to [Handle::uring] on the thread to have any effect.
+    pub fn configure(&self, builder: UringBuilder) {
+        CONFIG.with(|config| *config.borrow_mut() = Some(builder));
+    }
+
     pub fn uring(&self) -> MutexGuard<'_, UringDriver> {
-        self.uring_driver
-            .get_or(|| {
-                UringDriver::new(4096)
-                    .map(Mutex::new)
-                    .expect("Failed to configure the UringDriver.")
-            })
+        let mut driver = self
+            .uring_driver
+            .get_or(|| Mutex::new(build_driver()))
             .lock()
-            .expect("Failed to lock thread local UringDriver: poisoned")
+            .expect("Failed to lock thread local UringDriver: poisoned");
+
+        // The slot of a thread that has exited is handed to the next thread that is started, but
+        // a ring may only ever be used by the thread that created it, so replace it.
+        if !driver.is_owner() {
+            *driver = build_driver();
+        }
+        driver
+    }
+
+    /// Returns the [UringDriver] of the current thread like [Handle::uring], unless it hasn't been
+    /// created yet or is already locked further up the stack, such as while it is resolving
+    /// completions.
+    pub(crate) fn try_uring(&self) -> Option<MutexGuard<'_, UringDriver>> {
+        let driver = self.uring_driver.get()?.try_lock().ok()?;
+        driver.is_owner().then_some(driver)
     }
 }
+
+fn build_driver() -> UringDriver {
+    CONFIG
+        .with(|config| config.borrow().clone())
+        .unwrap_or_default()
+        .build()
+        .expect("Failed to configure the UringDriver.")
+}
@@ -12,4 +12,5 @@ mod handle;
 mod statics;
 
 pub use handle::Handle;
+pub(crate) use statics::try_uring;
 pub use statics::{handle, uring};
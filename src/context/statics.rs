@@ -22,3 +22,9 @@ pub fn handle() -> &'static Handle {
 pub fn uring<'a>() -> MutexGuard<'a, UringDriver> {
     handle().uring()
 }
+
+/// Return the thread local [UringDriver] like [uring], unless it is already locked by the current
+/// thread, in which case `None` is returned rather than deadlocking.
+pub(crate) fn try_uring<'a>() -> Option<MutexGuard<'a, UringDriver>> {
+    handle().try_uring()
+}
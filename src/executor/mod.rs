@@ -17,5 +17,5 @@ mod statics;
 mod unpark_mutex;
 
 pub use block_on::block_on;
-pub use pool::{ThreadPool, ThreadPoolBuilder};
-pub use statics::spawn;
+pub use pool::{Priority, ThreadPool, ThreadPoolBuilder};
+pub use statics::{priority, spawn, spawn_on, spawn_on_each, spawn_with_priority};
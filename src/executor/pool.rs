@@ -1,9 +1,10 @@
 use std::{
     boxed::Box,
-    cmp, fmt, io,
+    cell::Cell,
+    cmp, fmt, io, ptr,
     sync::atomic::{AtomicUsize, Ordering},
     sync::mpsc::{self, TryRecvError},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
     thread,
 };
 
@@ -13,7 +14,16 @@ use futures::{
     task::{waker_ref, ArcWake, Context, Poll, Spawn, SpawnError},
 };
 
-use crate::context;
+use nix::{
+    sched::{sched_setaffinity, CpuSet},
+    unistd::Pid,
+};
+
+use crate::{
+    context,
+    io_uring::UringBuilder,
+    time::clock::{self, Clock},
+};
 
 use super::{statics::set_pool, unpark_mutex::UnparkMutex};
 
@@ -29,8 +39,11 @@ pub struct ThreadPool {
 /// Thread pool configuration object.
 pub struct ThreadPoolBuilder {
     pool_size: usize,
+    per_core: bool,
+    classes: Vec<SchedulingClass>,
     stack_size: usize,
     name_prefix: Option<String>,
+    uring: Option<UringBuilder>,
     after_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
     before_stop: Option<Arc<dyn Fn(usize) + Send + Sync>>,
 }
@@ -39,17 +52,71 @@ pub struct ThreadPoolBuilder {
 trait AssertSendSync: Send + Sync {}
 impl AssertSendSync for ThreadPool {}
 
-struct PoolState {
+/// A handle to one of the scheduling classes configured on a [ThreadPool], used to spawn tasks
+/// via [ThreadPool::spawn_with_priority]. Handles are retrieved by name with
+/// [ThreadPool::priority], with the exception of [Priority::DEFAULT] which always exists. A handle
+/// is tied to the pool it was retrieved from, spawning with a handle from another pool places the
+/// task in the default class instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Priority {
+    pool: usize,
+    class: usize,
+}
+
+impl Priority {
+    /// The default scheduling class, this is what [ThreadPool::spawn_ok] and [crate::spawn] use.
+    pub const DEFAULT: Priority = Priority { pool: 0, class: 0 };
+}
+
+/// The id handed to the next [ThreadPool], ids start at `1` as `0` is reserved for
+/// [Priority::DEFAULT] which is valid for every pool.
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The name of the [Priority::DEFAULT] scheduling class.
+const DEFAULT_CLASS: &str = "default";
+
+/// A named scheduling class, tasks in a class are polled in batches of up to `weight` tasks per
+/// round before the worker moves on to the next class.
+#[derive(Clone, Debug)]
+struct SchedulingClass {
+    name: String,
+    weight: usize,
+}
+
+struct RunQueue {
     tx: Mutex<mpsc::Sender<Message>>,
     rx: Mutex<mpsc::Receiver<Message>>,
+}
+
+/// The set of run queues, one per scheduling class, that one or more workers pull tasks from. In
+/// the default mode there is a single scheduler shared by all workers, in thread-per-core mode
+/// each worker owns its own scheduler and tasks never migrate between workers.
+struct Scheduler {
+    queues: Vec<RunQueue>,
+}
+
+struct PoolState {
+    id: usize,
+    classes: Vec<SchedulingClass>,
+    schedulers: Vec<Scheduler>,
+    poll_order: Vec<usize>,
+    next: AtomicUsize,
     cnt: AtomicUsize,
     size: usize,
+    per_core: bool,
+    clock: Arc<Clock>,
+}
+
+thread_local! {
+    /// The pool and worker index of the current thread, if it is a worker thread.
+    static WORKER: Cell<Option<(*const PoolState, usize)>> = const { Cell::new(None) };
 }
 
 impl fmt::Debug for ThreadPool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ThreadPool")
             .field("size", &self.state.size)
+            .field("per_core", &self.is_per_core())
             .finish()
     }
 }
@@ -58,7 +125,10 @@ impl fmt::Debug for ThreadPoolBuilder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ThreadPoolBuilder")
             .field("pool_size", &self.pool_size)
+            .field("per_core", &self.per_core)
+            .field("classes", &self.classes)
             .field("name_prefix", &self.name_prefix)
+            .field("uring", &self.uring)
             .finish()
     }
 }
@@ -92,15 +162,42 @@ impl ThreadPool {
     /// > **Note**: This method is similar to `Spawn::spawn_obj`, except that
     /// >           it is guaranteed to always succeed.
     pub fn spawn_obj_ok(&self, future: FutureObj<'static, ()>) {
+        self.spawn_obj_with_priority(Priority::DEFAULT, future)
+    }
+
+    /// Spawns a future in the given scheduling class.
+    ///
+    /// > **Note**: This method is similar to [ThreadPool::spawn_obj_ok], except that the task is
+    /// >           scheduled according to the weight of `priority`.
+    pub fn spawn_obj_with_priority(&self, priority: Priority, future: FutureObj<'static, ()>) {
+        self.spawn_obj_inner(self.state.local_worker(), priority, future)
+    }
+
+    /// Spawns a future on the given worker in the given scheduling class, see
+    /// [ThreadPool::spawn_on] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` is not a valid worker index for this pool.
+    pub fn spawn_obj_on(&self, core: usize, priority: Priority, future: FutureObj<'static, ()>) {
+        assert!(core < self.state.size, "invalid core index: {}", core);
+        let worker = if self.is_per_core() { core } else { 0 };
+        self.spawn_obj_inner(worker, priority, future)
+    }
+
+    fn spawn_obj_inner(&self, worker: usize, priority: Priority, future: FutureObj<'static, ()>) {
+        let class = self.state.class(priority);
         let task = Task {
             future,
+            worker,
+            class,
             wake_handle: Arc::new(WakeHandle {
                 exec: self.clone(),
                 mutex: UnparkMutex::new(),
             }),
             exec: self.clone(),
         };
-        self.state.send(Message::Run(task));
+        self.state.send(worker, class, Message::Run(task));
     }
 
     /// Spawns a task that polls the given future with output `()` to
@@ -126,6 +223,114 @@ impl ThreadPool {
     {
         self.spawn_obj_ok(FutureObj::new(Box::new(future)))
     }
+
+    /// Spawns a task that polls the given future with output `()` to completion in the given
+    /// scheduling class. Workers poll classes with a higher weight first, so latency sensitive
+    /// work placed in a heavier class will be run ahead of bulk work in lighter classes. Lighter
+    /// classes are still guaranteed to be polled once per round and so are never starved.
+    ///
+    /// ```
+    /// # {
+    /// use libuio::executor::ThreadPoolBuilder;
+    ///
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .scheduling_class("control", 8)
+    ///     .create()
+    ///     .unwrap();
+    ///
+    /// let control = pool.priority("control").unwrap();
+    /// pool.spawn_with_priority(control, async { /* ... */ });
+    /// # }
+    /// # std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    /// ```
+    pub fn spawn_with_priority<Fut>(&self, priority: Priority, future: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_obj_with_priority(priority, FutureObj::new(Box::new(future)))
+    }
+
+    /// Spawns a task that polls the given future with output `()` to completion on the specified
+    /// worker. In thread-per-core mode the task is pinned to that worker for its entire lifetime,
+    /// otherwise this is equivalent to [ThreadPool::spawn_ok] as all workers share a single queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` is not a valid worker index for this pool.
+    pub fn spawn_on<Fut>(&self, core: usize, future: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_on_with_priority(core, Priority::DEFAULT, future)
+    }
+
+    /// Spawns a task that polls the given future with output `()` to completion on the specified
+    /// worker in the given scheduling class, combining [ThreadPool::spawn_on] and
+    /// [ThreadPool::spawn_with_priority].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` is not a valid worker index for this pool.
+    pub fn spawn_on_with_priority<Fut>(&self, core: usize, priority: Priority, future: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_obj_on(core, priority, FutureObj::new(Box::new(future)))
+    }
+
+    /// Spawns one task per worker, passing the worker index to `f` to create the future for that
+    /// worker. This is primarily meant for thread-per-core mode, where it can be used to open a
+    /// [crate::net::TcpListener] per core which thanks to `SO_REUSEPORT` will each receive a share
+    /// of the incoming connections without any cross-thread coordination.
+    ///
+    /// ```
+    /// # {
+    /// use libuio::executor::ThreadPoolBuilder;
+    ///
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .thread_per_core(true)
+    ///     .create()
+    ///     .unwrap();
+    ///
+    /// pool.spawn_on_each(|core| async move {
+    ///     // Open a listener and serve connections on `core`.
+    /// });
+    /// # }
+    /// # std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    /// ```
+    pub fn spawn_on_each<F, Fut>(&self, f: F)
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        for core in 0..self.state.size {
+            self.spawn_on(core, f(core));
+        }
+    }
+
+    /// Returns the number of worker threads in this pool.
+    pub fn size(&self) -> usize {
+        self.state.size
+    }
+
+    /// Returns whether or not this pool was created in thread-per-core mode, see
+    /// [ThreadPoolBuilder::thread_per_core].
+    pub fn is_per_core(&self) -> bool {
+        self.state.per_core
+    }
+
+    /// Lookup the [Priority] handle for the scheduling class with the given name, returning
+    /// `None` if no such class was configured via [ThreadPoolBuilder::scheduling_class].
+    pub fn priority(&self, name: &str) -> Option<Priority> {
+        self.state
+            .classes
+            .iter()
+            .position(|class| class.name == name)
+            .map(|class| Priority {
+                pool: self.state.id,
+                class,
+            })
+    }
 }
 
 impl Spawn for ThreadPool {
@@ -135,24 +340,77 @@ impl Spawn for ThreadPool {
     }
 }
 
+impl RunQueue {
+    fn new() -> RunQueue {
+        let (tx, rx) = mpsc::channel();
+        RunQueue {
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
 impl PoolState {
-    fn send(&self, msg: Message) {
-        self.tx.lock().unwrap().send(msg).unwrap();
+    fn send(&self, worker: usize, class: usize, msg: Message) {
+        let tx = self.schedulers[worker].queues[class]
+            .tx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // The receiving ends are owned by the pool state rather than the workers, so a message
+        // sent after a worker has exited stays queued and is dropped along with the pool.
+        let _ = tx.send(msg);
+    }
+
+    /// Returns the scheduling class to place tasks spawned with the given [Priority] in, falling
+    /// back to the default class for handles that belong to another pool.
+    fn class(&self, priority: Priority) -> usize {
+        match priority.pool == self.id && priority.class < self.classes.len() {
+            true => priority.class,
+            false => Priority::DEFAULT.class,
+        }
+    }
+
+    /// Pick the scheduler a newly spawned task should be placed on. Spawns from one of our own
+    /// workers stay on that worker, anything else is distributed round robin across workers.
+    fn local_worker(&self) -> usize {
+        if self.schedulers.len() == 1 {
+            return 0;
+        }
+        match WORKER.with(Cell::get) {
+            Some((pool, idx)) if ptr::eq(pool, self) => idx,
+            _ => self.next.fetch_add(1, Ordering::Relaxed) % self.schedulers.len(),
+        }
     }
 
-    fn handle_tasks(&self) -> bool {
+    fn handle_tasks(&self, worker: usize) -> bool {
+        let scheduler = &self.schedulers[worker];
         loop {
-            // Now grab any ready tasks and execute them.
-            let msg = match self.rx.lock().unwrap().try_recv() {
-                Ok(msg) => msg,                                 // We got a task to execute or close.
-                Err(TryRecvError::Empty) => return false,       // Nothing ready just continue on.
-                Err(TryRecvError::Disconnected) => return true, // Something horrible happened, shutdown.
-            };
-
-            // Handle our message and then loop again.
-            match msg {
-                Message::Run(task) => task.run(),
-                Message::Close => return true,
+            // Run a single weighted round across all classes, heaviest first, polling up to
+            // `weight` tasks from each class before moving on to the next one.
+            let mut idle = true;
+            for &class in self.poll_order.iter() {
+                for _ in 0..self.classes[class].weight {
+                    // Now grab any ready tasks and execute them.
+                    let msg = match scheduler.queues[class].rx.lock().unwrap().try_recv() {
+                        // We got a task to execute or close.
+                        Ok(msg) => msg,
+                        // Nothing ready in this class, move on to the next one.
+                        Err(TryRecvError::Empty) => break,
+                        // Something horrible happened, shutdown.
+                        Err(TryRecvError::Disconnected) => return true,
+                    };
+
+                    // Handle our message and then loop again.
+                    match msg {
+                        Message::Run(task) => task.run(),
+                        Message::Close => return true,
+                    }
+                    idle = false;
+                }
+            }
+
+            if idle {
+                return false;
             }
         }
     }
@@ -160,10 +418,30 @@ impl PoolState {
     fn work(
         &self,
         idx: usize,
+        uring: Option<UringBuilder>,
         after_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
         before_stop: Option<Arc<dyn Fn(usize) + Send + Sync>>,
     ) {
         let _scope = enter().unwrap();
+        WORKER.with(|worker| worker.set(Some((self as *const _, idx))));
+        clock::set_clock(self.clock.clone());
+        if let Some(uring) = uring {
+            context::handle().configure(uring);
+        }
+
+        // In thread-per-core mode each worker owns its own scheduler, and is pinned to a core on
+        // a best effort basis, restricted cpusets for instance will simply leave the worker
+        // unpinned.
+        let worker = if self.per_core {
+            let mut cpus = CpuSet::new();
+            if cpus.set(idx % num_cpus::get()).is_ok() {
+                let _ = sched_setaffinity(Pid::from_raw(0), &cpus);
+            }
+            idx
+        } else {
+            0
+        };
+
         if let Some(after_start) = after_start {
             after_start(idx);
         }
@@ -173,7 +451,7 @@ impl PoolState {
 
             // Now handle any outstanding tasks, breaking out of the loop if we are in graceful
             // shutdown mode or we had a fatal error.
-            if self.handle_tasks() {
+            if self.handle_tasks(worker) {
                 break;
             }
         }
@@ -195,8 +473,10 @@ impl Clone for ThreadPool {
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         if self.state.cnt.fetch_sub(1, Ordering::Relaxed) == 1 {
-            for _ in 0..self.state.size {
-                self.state.send(Message::Close);
+            for worker in 0..self.state.size {
+                let worker = worker % self.state.schedulers.len();
+                self.state
+                    .send(worker, Priority::DEFAULT.class, Message::Close);
             }
         }
     }
@@ -209,8 +489,14 @@ impl ThreadPoolBuilder {
     pub fn new() -> Self {
         Self {
             pool_size: cmp::max(1, num_cpus::get()),
+            per_core: false,
+            classes: vec![SchedulingClass {
+                name: DEFAULT_CLASS.to_string(),
+                weight: 1,
+            }],
             stack_size: 0,
             name_prefix: None,
+            uring: None,
             after_start: None,
             before_stop: None,
         }
@@ -230,6 +516,37 @@ impl ThreadPoolBuilder {
         self
     }
 
+    /// Enable thread-per-core mode, where each worker thread is pinned to a core and owns its own
+    /// run queue and [crate::io_uring::UringDriver]. Tasks never migrate between workers, tasks
+    /// spawned from a worker stay on that worker, and [ThreadPool::spawn_on] can be used to
+    /// explicitly target a given worker.
+    ///
+    /// By default, this is disabled and all workers share a single run queue.
+    pub fn thread_per_core(&mut self, enabled: bool) -> &mut Self {
+        self.per_core = enabled;
+        self
+    }
+
+    /// Add a named scheduling class with the given weight, or update the weight of an existing
+    /// class with the same name. The weight is the number of tasks from this class a worker will
+    /// poll per scheduling round, and classes are polled in order of descending weight.
+    ///
+    /// By default, there is a single class named `default` with a weight of `1`, which is used by
+    /// [ThreadPool::spawn_ok] and friends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight == 0`.
+    pub fn scheduling_class<S: Into<String>>(&mut self, name: S, weight: usize) -> &mut Self {
+        assert!(weight > 0);
+        let name = name.into();
+        match self.classes.iter_mut().find(|class| class.name == name) {
+            Some(class) => class.weight = weight,
+            None => self.classes.push(SchedulingClass { name, weight }),
+        }
+        self
+    }
+
     /// Set stack size of threads in the pool, in bytes.
     ///
     /// By default, worker threads use Rust's standard stack size.
@@ -249,6 +566,15 @@ impl ThreadPoolBuilder {
         self
     }
 
+    /// Set the configuration of the [crate::io_uring::UringDriver] each worker thread creates,
+    /// for instance to enable SQPOLL mode via [UringBuilder::sqpoll].
+    ///
+    /// By default, workers use the default [UringBuilder] configuration.
+    pub fn uring(&mut self, uring: UringBuilder) -> &mut Self {
+        self.uring = Some(uring);
+        self
+    }
+
     /// Execute the closure `f` immediately after each worker thread is started,
     /// but before running any tasks on it.
     ///
@@ -284,18 +610,31 @@ impl ThreadPoolBuilder {
 
     /// Create a [`ThreadPool`](ThreadPool) with the given configuration.
     pub fn create(&mut self) -> Result<ThreadPool, io::Error> {
-        let (tx, rx) = mpsc::channel();
+        let mut poll_order: Vec<usize> = (0..self.classes.len()).collect();
+        poll_order.sort_by_key(|&class| cmp::Reverse(self.classes[class].weight));
+
+        let schedulers = if self.per_core { self.pool_size } else { 1 };
         let pool = ThreadPool {
             state: Arc::new(PoolState {
-                tx: Mutex::new(tx),
-                rx: Mutex::new(rx),
+                id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+                classes: self.classes.clone(),
+                schedulers: (0..schedulers)
+                    .map(|_| Scheduler {
+                        queues: self.classes.iter().map(|_| RunQueue::new()).collect(),
+                    })
+                    .collect(),
+                poll_order,
+                next: AtomicUsize::new(0),
                 cnt: AtomicUsize::new(1),
                 size: self.pool_size,
+                per_core: self.per_core,
+                clock: Arc::default(),
             }),
         };
 
         for counter in 0..self.pool_size {
             let state = pool.state.clone();
+            let uring = self.uring.clone();
             let after_start = self.after_start.clone();
             let before_stop = self.before_stop.clone();
             let mut thread_builder = thread::Builder::new();
@@ -306,7 +645,7 @@ impl ThreadPoolBuilder {
                 thread_builder = thread_builder.stack_size(self.stack_size);
             }
             thread_builder.spawn(move || {
-                state.work(counter, after_start, before_stop);
+                state.work(counter, uring, after_start, before_stop);
             })?;
         }
         set_pool(pool.clone());
@@ -323,6 +662,8 @@ impl Default for ThreadPoolBuilder {
 /// A task responsible for polling a future to completion.
 struct Task {
     future: FutureObj<'static, ()>,
+    worker: usize,
+    class: usize,
     exec: ThreadPool,
     wake_handle: Arc<WakeHandle>,
 }
@@ -338,6 +679,8 @@ impl Task {
     fn run(self) {
         let Self {
             mut future,
+            worker,
+            class,
             wake_handle,
             mut exec,
         } = self;
@@ -357,6 +700,8 @@ impl Task {
                 }
                 let task = Self {
                     future,
+                    worker,
+                    class,
                     wake_handle: wake_handle.clone(),
                     exec,
                 };
@@ -382,7 +727,10 @@ impl fmt::Debug for Task {
 impl ArcWake for WakeHandle {
     fn wake_by_ref(arc_self: &Arc<Self>) {
         if let Ok(task) = arc_self.mutex.notify() {
-            arc_self.exec.state.send(Message::Run(task))
+            arc_self
+                .exec
+                .state
+                .send(task.worker, task.class, Message::Run(task))
         }
     }
 }
@@ -390,6 +738,7 @@ impl ArcWake for WakeHandle {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time;
 
     #[test]
     fn test_drop_after_start() {
@@ -408,4 +757,129 @@ mod tests {
         }
         std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
     }
+
+    #[test]
+    fn test_priority_polled_first() {
+        {
+            let pool = ThreadPoolBuilder::new()
+                .pool_size(1)
+                .scheduling_class("control", 4)
+                .create()
+                .unwrap();
+            let control = pool.priority("control").unwrap();
+            assert!(pool.priority("missing").is_none());
+
+            // Block the only worker so that everything below is queued up before it is polled.
+            let (block_tx, block_rx) = mpsc::channel::<()>();
+            pool.spawn_ok(async move {
+                block_rx.recv().unwrap();
+            });
+
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..4 {
+                let tx = tx.clone();
+                pool.spawn_ok(async move { tx.send("bulk").unwrap() });
+            }
+            for _ in 0..4 {
+                let tx = tx.clone();
+                pool.spawn_with_priority(control, async move { tx.send("control").unwrap() });
+            }
+            drop(tx);
+            block_tx.send(()).unwrap();
+
+            let order: Vec<_> = rx.into_iter().collect();
+            assert_eq!(order.len(), 8);
+            assert!(order[..4].iter().all(|class| *class == "control"));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    }
+
+    #[test]
+    fn test_foreign_priority() {
+        {
+            let pool = ThreadPoolBuilder::new().pool_size(1).create().unwrap();
+            let other = ThreadPoolBuilder::new()
+                .pool_size(1)
+                .scheduling_class("first", 2)
+                .scheduling_class("second", 2)
+                .create()
+                .unwrap();
+            let second = other.priority("second").unwrap();
+
+            // A handle from a pool with more classes falls back to the default class.
+            let (tx, rx) = mpsc::channel();
+            pool.spawn_with_priority(second, async move { tx.send(()).unwrap() });
+            rx.recv().unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    }
+
+    #[test]
+    fn test_thread_per_core_spawn_on() {
+        {
+            let pool = ThreadPoolBuilder::new()
+                .pool_size(2)
+                .thread_per_core(true)
+                .name_prefix("tpc-")
+                .create()
+                .unwrap();
+            assert!(pool.is_per_core());
+            let single = ThreadPoolBuilder::new()
+                .pool_size(1)
+                .thread_per_core(true)
+                .create()
+                .unwrap();
+            assert!(single.is_per_core());
+
+            let (tx, rx) = mpsc::channel();
+            let inner = pool.clone();
+            pool.spawn_on(1, async move {
+                // Tasks spawned from a worker must stay on that worker.
+                let name = thread::current().name().map(String::from);
+                let nested_tx = tx.clone();
+                inner.spawn_ok(async move {
+                    let name = thread::current().name().map(String::from);
+                    nested_tx.send(name).unwrap();
+                });
+                tx.send(name).unwrap();
+            });
+
+            let names: Vec<_> = rx.into_iter().collect();
+            assert_eq!(names.len(), 2);
+            assert!(names.iter().all(|name| name.as_deref() == Some("tpc-1")));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    }
+
+    #[test]
+    fn test_shared_clock() {
+        {
+            let pool = ThreadPoolBuilder::new()
+                .pool_size(2)
+                .thread_per_core(true)
+                .create()
+                .unwrap();
+
+            let (tx, rx) = mpsc::channel();
+            let inner = pool.clone();
+            pool.spawn_on(0, async move {
+                time::pause();
+
+                // A timer created on another worker is driven by the clock paused on this one.
+                let (created_tx, created_rx) = mpsc::channel();
+                inner.spawn_on(1, async move {
+                    let sleep = time::sleep(std::time::Duration::from_secs(3600));
+                    created_tx.send(()).unwrap();
+                    sleep.await;
+                    tx.send(time::is_paused()).unwrap();
+                });
+                created_rx.recv().unwrap();
+                time::advance(std::time::Duration::from_secs(3600)).await;
+            });
+
+            assert!(rx.recv().unwrap());
+            assert!(!time::is_paused());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500)); // wait for background threads closed: https://github.com/rust-lang/miri/issues/1371
+    }
 }
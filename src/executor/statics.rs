@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use futures::Future;
 use lazy_static::lazy_static;
 
-use super::ThreadPool;
+use super::{Priority, ThreadPool};
 
 lazy_static! {
     static ref POOL: Arc<Mutex<Option<ThreadPool>>> = Arc::new(Mutex::new(None));
@@ -50,3 +50,71 @@ where
         None => panic!("runtime not configured"),
     };
 }
+
+/// Spawn a task on the runtime in the given scheduling class, see [ThreadPool::spawn_with_priority]
+/// for details on how scheduling classes are polled relative to one another.
+///
+/// # Panics
+///
+/// This method will panic in the event that the internal locking logic is poisoned, or the runtime
+/// hasn't been configured.
+pub fn spawn_with_priority<F>(priority: Priority, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let pool = POOL.lock().expect("failed to lock thread pool: poisoned");
+    match pool.as_ref() {
+        Some(pool) => pool.spawn_with_priority(priority, future),
+        None => panic!("runtime not configured"),
+    };
+}
+
+/// Lookup the [Priority] handle for the named scheduling class on the configured runtime, see
+/// [ThreadPool::priority] for details.
+///
+/// # Panics
+///
+/// This method will panic in the event that the internal locking logic is poisoned, or the runtime
+/// hasn't been configured.
+pub fn priority(name: &str) -> Option<Priority> {
+    let pool = POOL.lock().expect("failed to lock thread pool: poisoned");
+    match pool.as_ref() {
+        Some(pool) => pool.priority(name),
+        None => panic!("runtime not configured"),
+    }
+}
+
+/// Spawn a task on the given worker of the runtime, see [ThreadPool::spawn_on] for details.
+///
+/// # Panics
+///
+/// This method will panic in the event that the internal locking logic is poisoned, the runtime
+/// hasn't been configured, or `core` is not a valid worker index.
+pub fn spawn_on<F>(core: usize, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let pool = POOL.lock().expect("failed to lock thread pool: poisoned");
+    match pool.as_ref() {
+        Some(pool) => pool.spawn_on(core, future),
+        None => panic!("runtime not configured"),
+    };
+}
+
+/// Spawn one task per worker of the runtime, see [ThreadPool::spawn_on_each] for details.
+///
+/// # Panics
+///
+/// This method will panic in the event that the internal locking logic is poisoned, or the runtime
+/// hasn't been configured.
+pub fn spawn_on_each<F, Fut>(f: F)
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let pool = POOL.lock().expect("failed to lock thread pool: poisoned");
+    match pool.as_ref() {
+        Some(pool) => pool.spawn_on_each(f),
+        None => panic!("runtime not configured"),
+    };
+}
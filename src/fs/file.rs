@@ -0,0 +1,92 @@
+use std::{
+    fs, io,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    path::Path,
+};
+
+use crate::{
+    io_uring::{AsTarget, Descriptor, FixedBuf, Target},
+    net::{ReadFixed, WriteFixed},
+};
+
+/// A [File] represents an open file on the filesystem that can be read and written
+/// asynchronously at given offsets. Note that opening the file is a blocking operation.
+pub struct File {
+    fd: Descriptor,
+}
+
+impl File {
+    /// Open the file at the given path in read-only mode, see [std::fs::File::open].
+    pub fn open(path: impl AsRef<Path>) -> io::Result<File> {
+        fs::File::open(path).map(File::from)
+    }
+
+    /// Open the file at the given path in write-only mode, creating it if it doesn't exist and
+    /// truncating it if it does, see [std::fs::File::create].
+    pub fn create(path: impl AsRef<Path>) -> io::Result<File> {
+        fs::File::create(path).map(File::from)
+    }
+
+    /// Read data from the file at the given offset into the given [FixedBuf]. This will return a
+    /// single use [ReadFixed] future that returns the amount of data read, which is also set as the
+    /// length of the buffer, along with the buffer.
+    pub fn read_fixed_at<'a>(&'a mut self, buf: FixedBuf, offset: u64) -> ReadFixed<'a, File> {
+        ReadFixed::new(self, buf, offset)
+    }
+
+    /// Write the data in the given [FixedBuf] to the file at the given offset. This will return a
+    /// single use [WriteFixed] future that returns the amount of data written from the buffer along
+    /// with the buffer.
+    pub fn write_fixed_at<'a>(&'a mut self, buf: FixedBuf, offset: u64) -> WriteFixed<'a, File> {
+        WriteFixed::new(self, buf, offset)
+    }
+}
+
+impl From<fs::File> for File {
+    fn from(file: fs::File) -> Self {
+        File {
+            fd: Descriptor::new(OwnedFd::from(file)),
+        }
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsTarget for File {
+    fn as_target(&self) -> Target {
+        self.fd.target()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{executor::block_on, io_uring::FixedBufPool};
+
+    use super::*;
+
+    #[test]
+    fn test_file_fixed() {
+        let path = env::temp_dir().join(format!("libuio-file-{}", std::process::id()));
+        let pool = FixedBufPool::new(1, 4096).unwrap();
+        let mut buf = pool.checkout().unwrap();
+
+        block_on(async {
+            let mut file = File::create(&path).unwrap();
+            buf.extend_from_slice(b"hello world");
+            let (written, buf) = file.write_fixed_at(buf, 4).await;
+            assert_eq!(written.unwrap(), 11);
+
+            let mut file = File::open(&path).unwrap();
+            let (read, buf) = file.read_fixed_at(buf, 10).await;
+            assert_eq!(read.unwrap(), 5);
+            assert_eq!(&buf[..], b"world");
+        });
+        fs::remove_file(&path).unwrap();
+    }
+}
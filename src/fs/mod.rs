@@ -0,0 +1,7 @@
+//! The [self] package handles all logic relating to async file I/O. This currently exposes
+//! [File], which supports reading and writing through the registered buffers of a
+//! [crate::io_uring::FixedBufPool].
+
+mod file;
+
+pub use file::File;
@@ -0,0 +1,374 @@
+use std::{
+    future::poll_fn,
+    io,
+    os::fd::{AsRawFd, RawFd},
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use io_uring::{cqueue, opcode, squeue, types::Fd};
+use nix::libc;
+
+use crate::context;
+
+use super::{Completion, CompletionStatus, Registration};
+
+/// The events that make a file descriptor readable, errors and hang ups are reported regardless.
+const READABLE: u32 = (libc::POLLIN | libc::POLLPRI | libc::POLLRDHUP) as u32;
+
+/// The events that make a file descriptor writable.
+const WRITABLE: u32 = libc::POLLOUT as u32;
+
+/// The events that make a file descriptor both readable and writable, so that any task waiting on
+/// it gets to see the error.
+const CLOSED: u32 = (libc::POLLERR | libc::POLLHUP) as u32;
+
+/// The readiness of an [AsyncFd] as reported by the kernel, shared with its [PollCompletion]s.
+#[derive(Default)]
+struct Readiness {
+    ready: u32,
+    tick: u64,
+    /// The events polled for by the polls currently in flight.
+    armed: u32,
+    /// Whether to use a multi-shot poll, picked the first time the file descriptor is polled.
+    multishot: Option<bool>,
+    readers: Option<Waker>,
+    writers: Option<Waker>,
+}
+
+impl Readiness {
+    fn set(&mut self, events: u32) {
+        let mut events = events;
+        if events & CLOSED != 0 {
+            events |= READABLE | WRITABLE;
+        }
+        self.ready |= events;
+        self.tick = self.tick.wrapping_add(1);
+
+        if events & READABLE != 0 {
+            if let Some(waker) = self.readers.take() {
+                waker.wake();
+            }
+        }
+        if events & WRITABLE != 0 {
+            if let Some(waker) = self.writers.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Mark the poll for the given events as done, waking any task still waiting on them so that
+    /// it arms a new poll.
+    fn disarm(&mut self, events: u32) {
+        self.armed &= !events;
+        if events & READABLE != 0 {
+            if let Some(waker) = self.readers.take() {
+                waker.wake();
+            }
+        }
+        if events & WRITABLE != 0 {
+            if let Some(waker) = self.writers.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+struct PollCompletion {
+    fd: RawFd,
+    events: u32,
+    multishot: bool,
+    readiness: Arc<Mutex<Readiness>>,
+}
+
+impl Completion for PollCompletion {
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        let more = self.multishot && cqueue::more(value.flags());
+        let mut readiness = lock(&self.readiness);
+        match value.result() {
+            result if result < 0 => readiness.set(CLOSED),
+            result => readiness.set(result as u32),
+        }
+        if !more {
+            // The poll is done, it is armed again once a task waits on the file descriptor.
+            readiness.disarm(self.events);
+        }
+
+        match more {
+            true => CompletionStatus::Armed,
+            false => CompletionStatus::Finalized,
+        }
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        opcode::PollAdd::new(Fd(self.fd), self.events)
+            .multi(self.multishot)
+            .build()
+    }
+}
+
+/// An [AsyncFd] provides readiness notifications for an arbitrary file descriptor, such as those
+/// handed out by third party libraries that do their own I/O, via `IORING_OP_POLL_ADD`. The file
+/// descriptor is polled via a single multi-shot poll from the first time a task waits on it until
+/// the [AsyncFd] is dropped, and on kernels without [super::Capabilities::multishot_poll] via a
+/// single-shot poll per direction issued whenever a task starts waiting on it.
+///
+/// Readiness is tracked on behalf of the file descriptor, and is only cleared when asked to via
+/// [ReadyGuard::clear_ready], typically once an I/O operation fails with
+/// [io::ErrorKind::WouldBlock]. As such the file descriptor should be in nonblocking mode. Only
+/// one task at a time is woken up per direction, so concurrent waits for the same direction
+/// should be avoided.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{io::Read, os::unix::net::UnixStream};
+///
+/// use libuio::{executor::block_on, io_uring::AsyncFd};
+///
+/// let (stream, _peer) = UnixStream::pair().expect("failed to create socket pair");
+/// stream.set_nonblocking(true).expect("failed to set nonblocking");
+/// let fd = AsyncFd::new(stream);
+///
+/// block_on(async {
+///     let mut buf = [0u8; 1024];
+///     loop {
+///         let mut guard = fd.readable().await;
+///         match guard.try_io(|mut stream| stream.read(&mut buf)) {
+///             Ok(read) => break println!("read {read} bytes"),
+///             Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => continue,
+///             Err(err) => panic!("failed to read: {err}"),
+///         }
+///     }
+/// });
+/// ```
+pub struct AsyncFd<T: AsRawFd> {
+    // The polls are canceled before the file descriptor is closed. A multi-shot poll covers both
+    // directions and lives in the first slot, while single-shot polls get a slot per direction.
+    registrations: Mutex<[Option<Registration<PollCompletion>>; 2]>,
+    readiness: Arc<Mutex<Readiness>>,
+    inner: T,
+}
+
+impl<T> AsyncFd<T>
+where
+    T: AsRawFd,
+{
+    /// Create a new [AsyncFd] wrapping the given file descriptor, note that the file descriptor
+    /// isn't polled until a task waits on it.
+    pub fn new(inner: T) -> AsyncFd<T> {
+        AsyncFd {
+            registrations: Mutex::new([None, None]),
+            readiness: Arc::new(Mutex::new(Readiness::default())),
+            inner,
+        }
+    }
+
+    /// Returns a shared reference to the wrapped file descriptor.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped file descriptor.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Stop polling the file descriptor, and return it.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Wait for the file descriptor to become readable, returning a [ReadyGuard] that clears the
+    /// readiness if asked to.
+    pub async fn readable(&self) -> ReadyGuard<'_, T> {
+        poll_fn(|cx| self.poll_readable(cx)).await
+    }
+
+    /// Wait for the file descriptor to become writable, returning a [ReadyGuard] that clears the
+    /// readiness if asked to.
+    pub async fn writable(&self) -> ReadyGuard<'_, T> {
+        poll_fn(|cx| self.poll_writable(cx)).await
+    }
+
+    /// Poll for the file descriptor to become readable, see [AsyncFd::readable].
+    pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<ReadyGuard<'_, T>> {
+        self.poll_ready(cx, READABLE)
+    }
+
+    /// Poll for the file descriptor to become writable, see [AsyncFd::writable].
+    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<ReadyGuard<'_, T>> {
+        self.poll_ready(cx, WRITABLE)
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>, interest: u32) -> Poll<ReadyGuard<'_, T>> {
+        let mut readiness = lock(&self.readiness);
+        if readiness.ready & interest != 0 {
+            return Poll::Ready(ReadyGuard {
+                fd: self,
+                interest,
+                tick: readiness.tick,
+            });
+        }
+
+        let (waker, slot) = match interest {
+            READABLE => (&mut readiness.readers, 0),
+            _ => (&mut readiness.writers, 1),
+        };
+        *waker = Some(cx.waker().clone());
+        if readiness.armed & interest != 0 {
+            return Poll::Pending;
+        }
+
+        // A single-shot poll only covers the direction being waited on, otherwise a file
+        // descriptor that is always writable would complete every poll for a reader right away.
+        let multishot = match readiness.multishot {
+            Some(multishot) => multishot,
+            None => {
+                // The readiness lock has to be released before touching the ring, which resolves
+                // the poll while holding on to the ring.
+                drop(readiness);
+                let multishot = context::uring().capabilities().multishot_poll();
+                readiness = lock(&self.readiness);
+                *readiness.multishot.get_or_insert(multishot)
+            }
+        };
+        let (events, slot) = match multishot {
+            true => (READABLE | WRITABLE, 0),
+            false => (interest, slot),
+        };
+        if readiness.armed & events != 0 {
+            return Poll::Pending;
+        }
+        readiness.armed |= events;
+        drop(readiness);
+
+        let op = PollCompletion {
+            fd: self.inner.as_raw_fd(),
+            events,
+            multishot,
+            readiness: self.readiness.clone(),
+        };
+        let mut registrations = lock(&self.registrations);
+        let registration = registrations[slot].insert(Registration::new(op));
+        if registration.poll_register(cx).is_pending() {
+            // Try again once there is room on the ring.
+            lock(&self.readiness).armed &= !events;
+        }
+        Poll::Pending
+    }
+}
+
+/// A [ReadyGuard] is handed out by [AsyncFd::readable] and [AsyncFd::writable] once the file
+/// descriptor is ready. Dropping the guard leaves the readiness as is, so that the next wait
+/// returns right away.
+pub struct ReadyGuard<'a, T: AsRawFd> {
+    fd: &'a AsyncFd<T>,
+    interest: u32,
+    tick: u64,
+}
+
+impl<'a, T> ReadyGuard<'a, T>
+where
+    T: AsRawFd,
+{
+    /// Returns a shared reference to the wrapped file descriptor.
+    pub fn get_ref(&self) -> &'a T {
+        &self.fd.inner
+    }
+
+    /// Clear the readiness this guard was handed out for, such that the next wait only returns
+    /// once the kernel reports the file descriptor as ready again. This should only be called once
+    /// an I/O operation failed with [io::ErrorKind::WouldBlock], and has no effect if the kernel
+    /// reported new readiness since the guard was handed out.
+    pub fn clear_ready(&mut self) {
+        let mut readiness = lock(&self.fd.readiness);
+        if readiness.tick == self.tick {
+            readiness.ready &= !self.interest;
+        }
+    }
+
+    /// Execute the given I/O operation on the wrapped file descriptor, clearing the readiness if
+    /// it fails with [io::ErrorKind::WouldBlock].
+    pub fn try_io<R>(&mut self, f: impl FnOnce(&'a T) -> io::Result<R>) -> io::Result<R> {
+        let result = f(&self.fd.inner);
+        if matches!(result, Err(ref err) if err.kind() == io::ErrorKind::WouldBlock) {
+            self.clear_ready();
+        }
+        result
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .expect("failed to lock async fd state, this is due to a poisoned mutex")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use futures::poll;
+
+    use super::*;
+    use crate::executor::block_on;
+
+    #[test]
+    fn test_async_fd() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let fd = AsyncFd::new(stream);
+
+        block_on(async {
+            // A fresh socket has room to write, but nothing to read.
+            fd.writable().await;
+            let mut readable = Box::pin(fd.readable());
+            assert!(poll!(readable.as_mut()).is_pending());
+
+            peer.write_all(b"ping").unwrap();
+            let mut guard = readable.await;
+            let mut buf = [0u8; 8];
+            let read = guard.try_io(|mut stream| stream.read(&mut buf)).unwrap();
+            assert_eq!(&buf[..read], b"ping");
+
+            // Readiness sticks around until an operation would block.
+            let mut guard = fd.readable().await;
+            let err = guard
+                .try_io(|mut stream| stream.read(&mut buf))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+            let mut readable = Box::pin(fd.readable());
+            assert!(poll!(readable.as_mut()).is_pending());
+
+            drop(peer);
+            let mut guard = readable.await;
+            assert_eq!(guard.try_io(|mut stream| stream.read(&mut buf)).unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn test_async_fd_single_shot() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let fd = AsyncFd::new(stream);
+        lock(&fd.readiness).multishot = Some(false);
+
+        block_on(async {
+            // A reader waiting alongside a writer must still be woken once the writer is done.
+            let mut readable = Box::pin(fd.readable());
+            assert!(poll!(readable.as_mut()).is_pending());
+            let mut guard = fd.writable().await;
+            guard.clear_ready();
+
+            peer.write_all(b"ping").unwrap();
+            let mut guard = readable.await;
+            let mut buf = [0u8; 8];
+            let read = guard.try_io(|mut stream| stream.read(&mut buf)).unwrap();
+            assert_eq!(&buf[..read], b"ping");
+        });
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    alloc::{self, Layout},
+    fmt, io, mem,
+    ops::{Deref, DerefMut},
+    slice,
+    sync::{
+        atomic::{AtomicU16, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::Waker,
+    thread::{self, ThreadId},
+};
+
+use io_uring::types::BufRingEntry;
+
+use crate::context;
+
+/// The alignment the kernel requires for the memory backing a buffer ring.
+const PAGE_SIZE: usize = 4096;
+
+/// The largest number of buffers a buffer ring can hold.
+const MAX_ENTRIES: u16 = 1 << 15;
+
+struct Inner {
+    bgid: u16,
+    mask: u16,
+    buf_size: usize,
+    ring: *mut BufRingEntry,
+    ring_layout: Layout,
+    bufs: *mut u8,
+    bufs_layout: Layout,
+    tail: Mutex<u16>,
+    owner: ThreadId,
+    /// Bumped every time a buffer is handed back to the kernel.
+    generation: AtomicU64,
+    /// The tasks waiting for a buffer to be handed back to the kernel.
+    waiters: Mutex<Vec<Waker>>,
+}
+
+// SAFETY: The raw pointers are owned allocations, the buffers are only ever handed out to a
+// single [BorrowedBuffer] at a time, and the producer side of the ring is guarded by a mutex.
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl Inner {
+    /// Hand the buffer with the given id back to the kernel.
+    fn recycle(&self, bid: u16) {
+        let mut tail = self
+            .tail
+            .lock()
+            .expect("failed to lock buffer ring: poisoned");
+
+        // SAFETY: The entry index is masked to the size of the ring, and the buffer id was handed
+        // out by the kernel so is in bounds of the buffers. Note that the tail lives in the
+        // reserved field of the first entry, so the entry is updated field by field.
+        unsafe {
+            let entry = &mut *self.ring.add((*tail & self.mask) as usize);
+            entry.set_addr(self.bufs.add(bid as usize * self.buf_size) as u64);
+            entry.set_len(self.buf_size as u32);
+            entry.set_bid(bid);
+        }
+        *tail = tail.wrapping_add(1);
+        self.publish(*tail);
+        drop(tail);
+
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        let waiters = mem::take(&mut *self.lock_waiters());
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+
+    fn lock_waiters(&self) -> MutexGuard<'_, Vec<Waker>> {
+        self.waiters
+            .lock()
+            .expect("failed to lock buffer ring waiters: poisoned")
+    }
+
+    fn publish(&self, tail: u16) {
+        // SAFETY: The tail is a valid u16 within the ring, that the kernel reads atomically.
+        unsafe {
+            let ptr = BufRingEntry::tail(self.ring) as *const AtomicU16;
+            (*ptr).store(tail, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // SAFETY: Both allocations were made with these layouts in [BufRing::alloc], and the
+        // driver holds on to a [BufRing] for as long as it is registered with the kernel.
+        unsafe {
+            alloc::dealloc(self.ring as *mut u8, self.ring_layout);
+            alloc::dealloc(self.bufs, self.bufs_layout);
+        }
+    }
+}
+
+/// A [BufRing] is a ring of provided buffers registered with the ring of a [super::UringDriver]
+/// under a buffer group id. Receives issued against a [BufRing] let the kernel pick a buffer from
+/// the ring once data actually arrives, rather than pinning a caller supplied buffer for the
+/// lifetime of the receive. The data is handed back as a [BorrowedBuffer] which returns the
+/// buffer to the ring once dropped.
+///
+/// A [BufRing] belongs to the ring of the thread that created it, and can only be used by
+/// receives submitted from that thread. This pairs naturally with
+/// [crate::executor::ThreadPoolBuilder::thread_per_core].
+///
+/// # Examples
+///
+/// ```no_run
+/// use libuio::{io_uring::BufRing, net::TcpStream};
+///
+/// async fn echo(mut conn: TcpStream) -> std::io::Result<()> {
+///     let bufs = BufRing::new(64, 4096)?;
+///     loop {
+///         let buf = conn.recv_buf(&bufs).await?;
+///         if buf.is_empty() {
+///             return Ok(());
+///         }
+///         conn.send(buf).await.0?;
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct BufRing {
+    inner: Arc<Inner>,
+}
+
+impl BufRing {
+    /// Create a new [BufRing] of `entries` buffers of `buf_size` bytes each, registered with the
+    /// [super::UringDriver] of the current thread. See [super::UringDriver::register_buf_ring].
+    ///
+    /// # Errors
+    ///
+    /// This method will error if `entries` isn't a power of two no larger than `32768`, if the
+    /// buffer memory couldn't be allocated, or if the kernel doesn't support buffer rings.
+    pub fn new(entries: u16, buf_size: usize) -> io::Result<BufRing> {
+        context::uring().register_buf_ring(entries, buf_size)
+    }
+
+    /// Allocate a new [BufRing] with the given buffer group id and all of its buffers handed to
+    /// the ring, this needs to be registered with the kernel before use.
+    pub(crate) fn alloc(bgid: u16, entries: u16, buf_size: usize) -> io::Result<BufRing> {
+        if !entries.is_power_of_two() || entries > MAX_ENTRIES || buf_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer ring entries must be a power of two up to 32768 with a non-zero size",
+            ));
+        }
+
+        let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+        let ring_layout =
+            Layout::from_size_align(entries as usize * mem::size_of::<BufRingEntry>(), PAGE_SIZE)
+                .map_err(invalid)?;
+        let bufs_layout =
+            Layout::from_size_align(entries as usize * buf_size, 1).map_err(invalid)?;
+
+        // SAFETY: Both layouts have a non-zero size, and are only freed if allocated.
+        let (ring, bufs) = unsafe {
+            let ring = alloc::alloc_zeroed(ring_layout) as *mut BufRingEntry;
+            if ring.is_null() {
+                return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+            }
+            let bufs = alloc::alloc(bufs_layout);
+            if bufs.is_null() {
+                alloc::dealloc(ring as *mut u8, ring_layout);
+                return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+            }
+            (ring, bufs)
+        };
+
+        let inner = Inner {
+            bgid,
+            mask: entries - 1,
+            buf_size,
+            ring,
+            ring_layout,
+            bufs,
+            bufs_layout,
+            tail: Mutex::new(0),
+            owner: thread::current().id(),
+            generation: AtomicU64::new(0),
+            waiters: Mutex::new(Vec::new()),
+        };
+        for bid in 0..entries {
+            inner.recycle(bid);
+        }
+
+        Ok(BufRing {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Returns the buffer group id of this [BufRing].
+    pub fn bgid(&self) -> u16 {
+        self.inner.bgid
+    }
+
+    /// Returns the size of each buffer in this [BufRing].
+    pub fn buf_size(&self) -> usize {
+        self.inner.buf_size
+    }
+
+    /// Returns the address of the ring memory, for registration with the kernel.
+    pub(crate) fn ring_addr(&self) -> u64 {
+        self.inner.ring as u64
+    }
+
+    /// Returns the number of entries in the ring.
+    pub(crate) fn entries(&self) -> u16 {
+        self.inner.mask + 1
+    }
+
+    /// Ensure this [BufRing] can be used by receives submitted from the current thread.
+    pub(crate) fn check_owner(&self) -> io::Result<()> {
+        if thread::current().id() != self.inner.owner {
+            return Err(io::Error::other(
+                "buffer ring used from a thread that doesn't own it",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of times a buffer has been handed back to the kernel, used along with
+    /// [BufRing::poll_recycled] to wait for the ring to refill after running dry.
+    pub(crate) fn generation(&self) -> u64 {
+        self.inner.generation.load(Ordering::Acquire)
+    }
+
+    /// Check whether a buffer has been handed back to the kernel since the given
+    /// [BufRing::generation], storing the [Waker] to notify once one is if not.
+    pub(crate) fn poll_recycled(&self, generation: u64, waker: &Waker) -> bool {
+        let mut waiters = self.inner.lock_waiters();
+        if self.generation() != generation {
+            return true;
+        }
+        if !waiters.iter().any(|waiter| waiter.will_wake(waker)) {
+            waiters.push(waker.clone());
+        }
+        false
+    }
+
+    /// Take ownership of the buffer with the given id, that the kernel filled with `len` bytes.
+    /// The kernel doesn't always pick a buffer for empty receives, in which case this returns an
+    /// empty [BorrowedBuffer] that isn't backed by any buffer.
+    pub(crate) fn borrow(&self, bid: Option<u16>, len: usize) -> BorrowedBuffer {
+        BorrowedBuffer {
+            ring: self.clone(),
+            len: bid.map(|_| len.min(self.inner.buf_size)).unwrap_or(0),
+            bid,
+        }
+    }
+}
+
+impl fmt::Debug for BufRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufRing")
+            .field("bgid", &self.inner.bgid)
+            .field("entries", &self.entries())
+            .field("buf_size", &self.inner.buf_size)
+            .finish()
+    }
+}
+
+/// A [BorrowedBuffer] is a buffer from a [BufRing] that the kernel filled with received data, it
+/// dereferences to the received bytes and hands the buffer back to the [BufRing] once dropped.
+pub struct BorrowedBuffer {
+    ring: BufRing,
+    bid: Option<u16>,
+    len: usize,
+}
+
+impl BorrowedBuffer {
+    fn as_ptr(&self) -> *mut u8 {
+        let inner = &self.ring.inner;
+        match self.bid {
+            // SAFETY: The buffer id was handed out by the kernel so is in bounds of the buffers.
+            Some(bid) => unsafe { inner.bufs.add(bid as usize * inner.buf_size) },
+            None => inner.bufs,
+        }
+    }
+}
+
+impl Deref for BorrowedBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The kernel handed this buffer to us, and it isn't handed out again until we
+        // return it on drop.
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for BorrowedBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: See [BorrowedBuffer::deref].
+        unsafe { slice::from_raw_parts_mut(self.as_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for BorrowedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BorrowedBuffer")
+            .field("bid", &self.bid)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Drop for BorrowedBuffer {
+    fn drop(&mut self) {
+        if let Some(bid) = self.bid {
+            self.ring.inner.recycle(bid);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, BorrowedFd, OwnedFd, RawFd},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use io_uring::IoUring;
+use nix::libc;
+
+use super::{epoll::Epoll, Capabilities, UringDriver};
+
+/// The default number of submission queue entries for a [UringDriver].
+const DEFAULT_ENTRIES: u32 = 4096;
+
+/// The default maximum number of submissions held in the backlog of a [UringDriver].
+const DEFAULT_BACKLOG: usize = 4096;
+
+/// The default number of slots in the fixed file table of a [UringDriver].
+const DEFAULT_FIXED_FILES: u32 = 4096;
+
+/// The `IORING_REGISTER_FILE_ALLOC_RANGE` register opcode, available since 6.0, which isn't
+/// exposed by the version of [io_uring] in use.
+const IORING_REGISTER_FILE_ALLOC_RANGE: libc::c_uint = 25;
+
+#[repr(C)]
+struct FileIndexRange {
+    off: u32,
+    len: u32,
+    resv: u64,
+}
+
+/// The backend executing the events of a [UringDriver], see [UringBuilder::backend].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Use io_uring, falling back to [Backend::Epoll] if the kernel doesn't support io_uring or
+    /// it has been disabled, for instance via the `kernel.io_uring_disabled` sysctl or a seccomp
+    /// filter.
+    #[default]
+    Auto,
+    /// Always use io_uring, failing to build the [UringDriver] if it is unavailable.
+    IoUring,
+    /// Always use `epoll`, emulating events with nonblocking syscalls issued once their file
+    /// descriptor is ready. This supports the events used by [crate::net] at reduced performance,
+    /// while events that only exist in io_uring, such as [super::BufRing] reads, fail with
+    /// `EOPNOTSUPP`, and [super::FixedBuf]s are used as regular buffers. Any other operation
+    /// submitted via [super::Op], such as the filesystem operations `MkDirAt`, `OpenAt` or
+    /// `Statx`, fails with `EINVAL`.
+    Epoll,
+}
+
+/// Configuration for a [UringDriver], this is used to tune the underlying io_uring of the
+/// per-worker drivers via [crate::executor::ThreadPoolBuilder::uring], or to create a
+/// [UringDriver] directly.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use libuio::{executor::ThreadPoolBuilder, io_uring::UringBuilder};
+///
+/// let mut uring = UringBuilder::new();
+/// uring.sqpoll(Duration::from_millis(50));
+///
+/// let pool = ThreadPoolBuilder::new()
+///     .uring(uring)
+///     .create()
+///     .expect("failed to create thread pool");
+/// ```
+#[derive(Clone, Debug)]
+pub struct UringBuilder {
+    entries: u32,
+    backlog: usize,
+    sqpoll_idle: Option<Duration>,
+    sqpoll_cpu: Option<u32>,
+    single_issuer: bool,
+    defer_taskrun: bool,
+    fixed_files: u32,
+    fixed_buffers: Option<(u16, usize)>,
+    shared_wq: Option<Arc<Mutex<Option<OwnedFd>>>>,
+    iowq_max_workers: Option<[u32; 2]>,
+    backend: Backend,
+}
+
+impl UringBuilder {
+    /// Create a default [UringDriver] configuration.
+    ///
+    /// See the other methods on this type for details on the defaults.
+    pub fn new() -> UringBuilder {
+        UringBuilder {
+            entries: DEFAULT_ENTRIES,
+            backlog: DEFAULT_BACKLOG,
+            sqpoll_idle: None,
+            sqpoll_cpu: None,
+            single_issuer: true,
+            defer_taskrun: true,
+            fixed_files: DEFAULT_FIXED_FILES,
+            fixed_buffers: None,
+            shared_wq: None,
+            iowq_max_workers: None,
+            backend: Backend::Auto,
+        }
+    }
+
+    /// Set the maximum number of events in flight in the submission queue.
+    ///
+    /// By default, this is `4096`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entries == 0`.
+    pub fn entries(&mut self, entries: u32) -> &mut Self {
+        assert!(entries > 0);
+        self.entries = entries;
+        self
+    }
+
+    /// Set the maximum number of submissions held in the backlog of the ring, where events are
+    /// queued up while the submission queue is full until the kernel has caught up. Once the
+    /// backlog is full, futures hold off registering new events, and are woken once there is room
+    /// again, see [UringDriver::poll_ready]. This keeps a burst of events from growing memory
+    /// without bound, and from delaying the events already in the backlog.
+    ///
+    /// The limit counts submission queue entries, so an event with a timeout counts twice, once
+    /// for the event and once for its linked timeout, and a [super::Chain] counts once per step.
+    /// Since a chain is never split up, the backlog may exceed the limit by up to one chain.
+    ///
+    /// Registering an event directly via [UringDriver::register] and friends fails with `EAGAIN`
+    /// while the backlog is full. Submissions the ring needs to make progress, such as
+    /// cancelations, re-armed multishot events and timers, may use a small reserve beyond the
+    /// limit, and are held back until the backlog drains once that is used up as well.
+    ///
+    /// By default, this is `4096`.
+    pub fn backlog(&mut self, limit: usize) -> &mut Self {
+        self.backlog = limit;
+        self
+    }
+
+    /// Enable `IORING_SETUP_SQPOLL`, where a kernel thread polls the submission queue so that
+    /// submitting events doesn't require a syscall. The poller goes to sleep once it has been
+    /// idle for the given [Duration], after which the [UringDriver] has to wake it up again on the
+    /// next submission.
+    ///
+    /// Note that the poller thread burns a CPU for as long as it is awake, so this trades CPU time
+    /// for lower submission latency and is best suited to services with a high and steady rate of
+    /// I/O.
+    ///
+    /// By default, this is disabled.
+    pub fn sqpoll(&mut self, idle: Duration) -> &mut Self {
+        self.sqpoll_idle = Some(idle);
+        self
+    }
+
+    /// Pin the submission queue poller thread to the given CPU, this has no effect unless
+    /// [UringBuilder::sqpoll] is enabled.
+    ///
+    /// By default, the poller thread is free to run on any CPU.
+    pub fn sqpoll_cpu(&mut self, cpu: u32) -> &mut Self {
+        self.sqpoll_cpu = Some(cpu);
+        self
+    }
+
+    /// Enable `IORING_SETUP_SINGLE_ISSUER`, which tells the kernel that only the thread that
+    /// created the ring will ever submit to it, allowing it to skip some synchronization. This is
+    /// always the case for the [UringDriver]s owned by the workers of the runtime.
+    ///
+    /// By default, this is enabled, and is ignored on kernels that don't support it.
+    pub fn single_issuer(&mut self, enabled: bool) -> &mut Self {
+        self.single_issuer = enabled;
+        self
+    }
+
+    /// Enable `IORING_SETUP_DEFER_TASKRUN`, which defers completion work until the owning thread
+    /// asks for completions on each iteration of the event loop, rather than interrupting it
+    /// whenever an event completes. This allows the kernel to batch up completion work, and
+    /// requires [UringBuilder::single_issuer].
+    ///
+    /// By default, this is enabled, and is ignored on kernels that don't support it as well as in
+    /// SQPOLL mode where the poller thread handles completion work instead.
+    pub fn defer_taskrun(&mut self, enabled: bool) -> &mut Self {
+        self.defer_taskrun = enabled;
+        self
+    }
+
+    /// Set the number of slots in the fixed file table of the ring, or `0` to disable it. Sockets
+    /// are registered in the table of the ring of the thread that created them while there is
+    /// room, and events on them are issued against their slot when submitted to that ring. Half of
+    /// the slots are reserved for connections accepted directly into the table, see
+    /// [crate::net::Incoming::direct].
+    ///
+    /// By default, this is `4096`, and is ignored on kernels that don't support sparse file
+    /// tables.
+    pub fn fixed_files(&mut self, slots: u32) -> &mut Self {
+        self.fixed_files = slots;
+        self
+    }
+
+    /// Register a [super::FixedBufPool] of `count` buffers of `buf_size` bytes each with the ring,
+    /// which is then available to the thread that owns the ring via
+    /// [super::FixedBufPool::current]. This gives each worker of the runtime its own pool.
+    ///
+    /// By default, no buffers are registered.
+    pub fn fixed_buffers(&mut self, count: u16, buf_size: usize) -> &mut Self {
+        self.fixed_buffers = Some((count, buf_size));
+        self
+    }
+
+    /// Enable `IORING_SETUP_ATTACH_WQ`, where every ring created from this builder, or any of its
+    /// clones, attaches to the async backend of a small anchor ring set up alongside the first
+    /// such ring, instead of setting up its own. The anchor lives for as long as the builder and
+    /// its clones, so that no worker's ring is kept alive past its [UringDriver]. This allows for
+    /// the rings of all workers of a [crate::executor::ThreadPool] to share a single backend. Note
+    /// that since 5.12 the kernel creates the io-wq workers executing blocking events per thread
+    /// regardless, so on newer kernels this is mostly useful in combination with
+    /// [UringBuilder::sqpoll], where the rings then share a single poller thread.
+    ///
+    /// By default, this is disabled, and is ignored on kernels that don't support attaching.
+    pub fn share_wq(&mut self, enabled: bool) -> &mut Self {
+        self.shared_wq = enabled.then(|| Arc::new(Mutex::new(None)));
+        self
+    }
+
+    /// Limit the number of kernel io-wq workers executing blocking events on behalf of the ring,
+    /// via `IORING_REGISTER_IOWQ_MAX_WORKERS`. The `bounded` limit applies to events that are
+    /// expected to complete in bounded time, such as I/O on regular files, while the `unbounded`
+    /// limit applies to events that may never complete, such as I/O on sockets. A limit of `0`
+    /// leaves the kernel default in place, which is bounded by the number of CPUs and by
+    /// `RLIMIT_NPROC` respectively. The limits are per NUMA node, and apply to the io-wq of the
+    /// thread that owns the ring.
+    ///
+    /// By default, the kernel defaults are used, and this is ignored on kernels that don't
+    /// support it.
+    pub fn iowq_max_workers(&mut self, bounded: u32, unbounded: u32) -> &mut Self {
+        self.iowq_max_workers = Some([bounded, unbounded]);
+        self
+    }
+
+    /// Set the [Backend] executing events, the io_uring specific options of this builder are
+    /// ignored with the [Backend::Epoll] backend.
+    ///
+    /// By default, this is [Backend::Auto].
+    pub fn backend(&mut self, backend: Backend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Create a new [UringDriver] with the given configuration, the [UringDriver] is owned by
+    /// the calling thread.
+    ///
+    /// # Errors
+    ///
+    /// This method will error if the kernel doesn't support the io_uring features we need, or is
+    /// otherwise unable to create the necessary kernel and userspace abstractions to use the ring,
+    /// or if the [UringBuilder::fixed_buffers] couldn't be registered.
+    pub fn build(&self) -> io::Result<UringDriver> {
+        let result = match self.backend {
+            Backend::Epoll => return self.build_epoll(),
+            Backend::IoUring | Backend::Auto => self.build_uring(),
+        };
+        match result {
+            Err(err) if self.backend == Backend::Auto && is_unavailable(&err) => self.build_epoll(),
+            result => result,
+        }
+    }
+
+    fn build_epoll(&self) -> io::Result<UringDriver> {
+        let mut driver = UringDriver::from_epoll(Epoll::new()?);
+        if let Some((count, buf_size)) = self.fixed_buffers {
+            driver.register_buffers(count, buf_size)?;
+        }
+        Ok(driver)
+    }
+
+    fn build_uring(&self) -> io::Result<UringDriver> {
+        let single_issuer = self.single_issuer;
+        let mut defer_taskrun = single_issuer && self.defer_taskrun && self.sqpoll_idle.is_none();
+
+        // Hold on to the shared backend while setting up, so that only one anchor ring ends up
+        // being the one the others attach to.
+        let mut shared_wq = self.shared_wq.as_ref().map(|shared| {
+            shared
+                .lock()
+                .expect("failed to lock shared wq, this is due to a poisoned mutex")
+        });
+        if let Some(ref mut shared) = shared_wq {
+            if shared.is_none() {
+                // Without an anchor the ring just sets up its own backend.
+                **shared = self.anchor().ok();
+            }
+        }
+        let mut attach_wq = shared_wq
+            .as_ref()
+            .and_then(|shared| shared.as_ref().map(AsRawFd::as_raw_fd));
+
+        // Older kernels reject setup flags they don't know about with -EINVAL, so fallback to
+        // dropping the optional flags one at a time until the kernel accepts the ring.
+        let mut result = self.setup(single_issuer, defer_taskrun, attach_wq);
+        if attach_wq.is_some() && is_invalid(&result) {
+            attach_wq = None;
+            result = self.setup(single_issuer, defer_taskrun, None);
+        }
+        if defer_taskrun && is_invalid(&result) {
+            defer_taskrun = false;
+            result = self.setup(single_issuer, false, attach_wq);
+        }
+        if single_issuer && is_invalid(&result) {
+            result = self.setup(false, false, attach_wq);
+        }
+
+        let uring = result?;
+        drop(shared_wq);
+        if let Some(mut max_workers) = self.iowq_max_workers {
+            // Kernels without support for limiting the workers reject the opcode with -EINVAL.
+            match uring
+                .submitter()
+                .register_iowq_max_workers(&mut max_workers)
+            {
+                Err(err) if !is_unsupported(&err) => return Err(err),
+                _ => (),
+            }
+        }
+
+        let caps = Capabilities::probe(&uring, defer_taskrun);
+        let fixed_files = match register_fixed_files(&uring, self.fixed_files) {
+            Ok(()) => self.fixed_files,
+            Err(_) => 0,
+        };
+        let mut driver = UringDriver::from_uring(uring, fixed_files, caps);
+        driver.set_backlog_limit(self.backlog);
+        if let Some((count, buf_size)) = self.fixed_buffers {
+            driver.register_buffers(count, buf_size)?;
+        }
+        Ok(driver)
+    }
+
+    /// Create the anchor ring the rings created from this builder attach to, returning its file
+    /// descriptor. The anchor never has any events submitted to it, so only the options that
+    /// concern the shared backend are applied, and its queues are unmapped right away.
+    fn anchor(&self) -> io::Result<OwnedFd> {
+        let mut builder = IoUring::builder();
+        if let Some(idle) = self.sqpoll_idle {
+            builder.setup_sqpoll(idle.as_millis().min(u32::MAX as u128) as u32);
+            if let Some(cpu) = self.sqpoll_cpu {
+                builder.setup_sqpoll_cpu(cpu);
+            }
+        }
+        let uring: IoUring = builder.build(1)?;
+        // The backend is kept alive for as long as any ring is attached to it, or the ring file
+        // descriptor remains open.
+        // SAFETY: The ring file descriptor is valid for as long as we hold on to the ring.
+        let ring = unsafe { BorrowedFd::borrow_raw(uring.as_raw_fd()) };
+        ring.try_clone_to_owned()
+    }
+
+    fn setup(
+        &self,
+        single_issuer: bool,
+        defer_taskrun: bool,
+        attach_wq: Option<RawFd>,
+    ) -> io::Result<IoUring> {
+        let mut builder = IoUring::builder();
+        if let Some(fd) = attach_wq {
+            builder.setup_attach_wq(fd);
+        }
+        if single_issuer {
+            builder.setup_single_issuer();
+        }
+        if defer_taskrun {
+            builder.setup_defer_taskrun();
+        }
+        if let Some(idle) = self.sqpoll_idle {
+            builder.setup_sqpoll(idle.as_millis().min(u32::MAX as u128) as u32);
+            if let Some(cpu) = self.sqpoll_cpu {
+                builder.setup_sqpoll_cpu(cpu);
+            }
+        }
+        builder.build(self.entries)
+    }
+}
+
+/// Register a sparse fixed file table with the given number of slots, handing the upper half of
+/// the table to the kernel for direct accepts.
+fn register_fixed_files(uring: &IoUring, slots: u32) -> io::Result<()> {
+    if slots == 0 {
+        return Ok(());
+    }
+    uring.submitter().register_files_sparse(slots)?;
+
+    let range = FileIndexRange {
+        off: slots / 2,
+        len: slots - slots / 2,
+        resv: 0,
+    };
+    // SAFETY: The range is a valid `io_uring_file_index_range` that outlives the call.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_io_uring_register,
+            uring.as_raw_fd(),
+            IORING_REGISTER_FILE_ALLOC_RANGE,
+            &range as *const FileIndexRange,
+            0,
+        )
+    };
+    if ret < 0 {
+        // Without an allocation range the kernel could hand out slots we manage ourselves, so
+        // don't use the table at all.
+        let err = io::Error::last_os_error();
+        let _ = uring.submitter().unregister_files();
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Returns whether or not the error from setting up a ring means io_uring isn't available at all.
+fn is_unavailable(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOSYS) | Some(libc::EPERM) | Some(libc::EACCES)
+    )
+}
+
+/// Returns whether or not the error from a register opcode means the kernel doesn't support it.
+fn is_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EINVAL) | Some(libc::EOPNOTSUPP)
+    )
+}
+
+fn is_invalid<T>(result: &io::Result<T>) -> bool {
+    matches!(result, Err(err) if err.raw_os_error() == Some(libc::EINVAL))
+}
+
+impl Default for UringBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{
+        context,
+        executor::ThreadPoolBuilder,
+        io_uring::Backend,
+        net::{TcpListener, TcpStream},
+        time::{self, wheel::WheelTimeout},
+    };
+
+    #[test]
+    fn test_sqpoll_pool() {
+        let mut uring = UringBuilder::new();
+        uring.entries(64).sqpoll(Duration::from_millis(10));
+        let pool = ThreadPoolBuilder::new()
+            .pool_size(1)
+            .uring(uring)
+            .create()
+            .unwrap();
+
+        // Sleep past the idle timeout in between, so the poller has to be woken back up.
+        let (tx, rx) = mpsc::channel();
+        pool.spawn_ok(async move {
+            for _ in 0..3 {
+                time::sleep(Duration::from_millis(30)).await;
+                tx.send(()).unwrap();
+            }
+        });
+        assert_eq!(rx.iter().take(3).count(), 3);
+    }
+
+    #[test]
+    fn test_shared_wq() {
+        let mut uring = UringBuilder::new();
+        uring.entries(8).share_wq(true).iowq_max_workers(2, 4);
+
+        // Rings created from clones of the builder attach to the same anchor ring.
+        let first = uring.build().unwrap();
+        let shared = uring.shared_wq.clone().unwrap();
+        assert!(shared.lock().unwrap().is_some());
+        let second = uring.clone().build().unwrap();
+
+        for driver in [&first, &second] {
+            let mut max_workers = [0; 2];
+            let submitter = driver.uring().unwrap().submitter();
+            submitter
+                .register_iowq_max_workers(&mut max_workers)
+                .unwrap();
+            assert_eq!(max_workers, [2, 4]);
+        }
+
+        // The attached ring is fully functional.
+        let mut driver = second;
+        driver
+            .register(WheelTimeout::new(Duration::from_millis(1)))
+            .unwrap();
+        driver.run().unwrap();
+    }
+
+    #[test]
+    fn test_epoll_pool() {
+        let mut uring = UringBuilder::new();
+        uring.backend(Backend::Epoll);
+        let pool = ThreadPoolBuilder::new()
+            .pool_size(1)
+            .uring(uring)
+            .create()
+            .unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        pool.spawn_ok(async move {
+            assert_eq!(context::uring().backend(), Backend::Epoll);
+            let mut listener = TcpListener::new("127.0.0.1", 0).unwrap();
+            let addr = listener.local_addr();
+            let mut incoming = listener.incoming();
+
+            let mut client = TcpStream::new(true).unwrap();
+            let (connected, conn) = futures::join!(client.connect(&addr), incoming.next());
+            connected.unwrap();
+            let mut conn = conn.unwrap().unwrap();
+
+            let (sent, _) = client.send(b"hello").await;
+            assert_eq!(sent.unwrap(), 5);
+            let (received, buf) = conn.recv(vec![0; 16]).await;
+            let received = received.unwrap();
+
+            time::sleep(Duration::from_millis(10)).await;
+            tx.send(buf[..received].to_vec()).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), b"hello");
+    }
+}
@@ -2,17 +2,22 @@ use io_uring::{opcode, types::CancelBuilder};
 
 use super::{Completion, CompletionStatus};
 
-/// A cancel event operation, that will target a given state index. This is a best effort operation
-/// which will attempt to cancel any and all operations associated with the given index. Generally
+/// A cancel event operation, that will target a given user_data. This is a best effort operation
+/// which will attempt to cancel any and all operations associated with the given user_data. Generally
 /// this is used during a drop of a given Future or Completion event inegrated with the uring loop.
+///
+/// Kernels without [super::Capabilities::cancel_all] only cancel the first operation matching the
+/// user_data.
 pub struct Cancel {
-    index: usize,
+    user_data: u64,
+    all: bool,
 }
 
 impl Cancel {
-    /// Create a new [Cancel] event targeting the given state index.
-    pub fn new(index: usize) -> Cancel {
-        Cancel { index }
+    /// Create a new [Cancel] event targeting the given user_data, cancelling all matching
+    /// operations if `all` is set.
+    pub fn new(user_data: u64, all: bool) -> Cancel {
+        Cancel { user_data, all }
     }
 }
 
@@ -22,7 +27,10 @@ impl Completion for Cancel {
     }
 
     fn as_entry(&mut self) -> io_uring::squeue::Entry {
-        let cancel = CancelBuilder::user_data(self.index as u64).all();
+        if !self.all {
+            return opcode::AsyncCancel::new(self.user_data).build();
+        }
+        let cancel = CancelBuilder::user_data(self.user_data).all();
         opcode::AsyncCancel2::new(cancel).build()
     }
 }
@@ -0,0 +1,106 @@
+use io_uring::{opcode, IoUring, Probe};
+
+/// The [Capabilities] of the kernel and the io_uring of a [super::UringDriver], probed when the
+/// ring is set up. Events that rely on newer features check these and fall back to older
+/// equivalents where the kernel doesn't support them:
+///
+/// - Without [Capabilities::multishot_accept], [crate::net::Incoming] issues a single-shot accept
+///   for every connection.
+/// - Without [Capabilities::multishot_poll], [super::AsyncFd] issues a single-shot poll whenever
+///   a task waits on its file descriptor.
+/// - Without [Capabilities::cancel_all], deregistering an event cancels it with a plain
+///   `IORING_OP_ASYNC_CANCEL` by user_data.
+/// - Without [Capabilities::ext_arg], waiting on the ring is bounded by a timeout event on the
+///   ring itself, rather than by the timeout passed along when entering the kernel.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libuio::{context, io_uring::Capabilities};
+///
+/// let caps: Capabilities = context::uring().capabilities();
+/// if !caps.multishot_accept() {
+///     println!("falling back to single-shot accepts");
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    opcodes: [u64; 4],
+    ext_arg: bool,
+    single_issuer: bool,
+    defer_taskrun: bool,
+    sqpoll: bool,
+}
+
+impl Capabilities {
+    /// Probe the opcodes supported by the kernel through the given ring, along with the features
+    /// and setup flags the ring ended up with. If the kernel doesn't support probing at all no
+    /// opcodes are reported as supported.
+    pub(crate) fn probe(uring: &IoUring, defer_taskrun: bool) -> Capabilities {
+        let mut caps = Capabilities {
+            opcodes: [0; 4],
+            ext_arg: uring.params().is_feature_ext_arg(),
+            single_issuer: uring.params().is_setup_single_issuer(),
+            defer_taskrun,
+            sqpoll: uring.params().is_setup_sqpoll(),
+        };
+
+        let mut probe = Probe::new();
+        if uring.submitter().register_probe(&mut probe).is_ok() {
+            for code in 0..=u8::MAX {
+                if probe.is_supported(code) {
+                    caps.opcodes[code as usize / 64] |= 1 << (code % 64);
+                }
+            }
+        }
+        caps
+    }
+
+    /// Returns whether or not the given opcode, such as [opcode::SendZc::CODE], is supported by
+    /// the kernel.
+    pub fn is_supported(&self, code: u8) -> bool {
+        self.opcodes[code as usize / 64] & (1 << (code % 64)) != 0
+    }
+
+    /// Returns whether or not the kernel supports multi-shot accepts, available since 5.19.
+    pub fn multishot_accept(&self) -> bool {
+        // Multi-shot accepts are a flag on the accept opcode which can't be probed for, so key
+        // off of the socket opcode which landed in the same release.
+        self.is_supported(opcode::Socket::CODE)
+    }
+
+    /// Returns whether or not the kernel supports multi-shot polls, available since 5.13.
+    pub fn multishot_poll(&self) -> bool {
+        // Multi-shot polls are a flag on the poll opcode which can't be probed for, so key off of
+        // the mkdirat opcode which landed shortly after in 5.15.
+        self.is_supported(opcode::MkDirAt::CODE)
+    }
+
+    /// Returns whether or not the kernel supports cancelling all events matching a user_data with
+    /// a single cancel event, available since 5.19.
+    pub fn cancel_all(&self) -> bool {
+        // Same as for multi-shot accepts, the cancel flags landed alongside the socket opcode.
+        self.is_supported(opcode::Socket::CODE)
+    }
+
+    /// Returns whether or not the kernel supports passing a timeout when waiting on the ring via
+    /// `IORING_ENTER_EXT_ARG`, available since 5.11.
+    pub fn ext_arg(&self) -> bool {
+        self.ext_arg
+    }
+
+    /// Returns whether or not the ring was set up with `IORING_SETUP_SINGLE_ISSUER`.
+    pub fn single_issuer(&self) -> bool {
+        self.single_issuer
+    }
+
+    /// Returns whether or not the ring was set up with `IORING_SETUP_DEFER_TASKRUN`.
+    pub fn defer_taskrun(&self) -> bool {
+        self.defer_taskrun
+    }
+
+    /// Returns whether or not the ring was set up with `IORING_SETUP_SQPOLL`.
+    pub fn sqpoll(&self) -> bool {
+        self.sqpoll
+    }
+}
@@ -0,0 +1,516 @@
+use std::{
+    cell::Cell,
+    io, mem,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+
+use futures::Future;
+use io_uring::{cqueue, squeue};
+
+use crate::{context, sync::OneShot};
+
+use super::{
+    op::{self, OpState},
+    Completion, CompletionStatus, Operation, Remote,
+};
+
+/// How an event in a chain is linked to the event before it, see
+/// [super::UringDriver::register_chain] and [Chain].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Link {
+    /// Only execute the event once the event before it succeeded, via `IOSQE_IO_LINK`. If the
+    /// event before it fails, this event and any events soft linked after it are resolved with a
+    /// result of `-ECANCELED`. Note that the kernel treats a short read, write, send or receive as
+    /// a failure here.
+    #[default]
+    Soft,
+    /// Execute the event once the event before it completed, regardless of its result, via
+    /// `IOSQE_IO_HARDLINK`.
+    Hard,
+}
+
+/// A step of a [Chain] the kernel is done with, which hands back the output of the step once
+/// called. This is deferred until the chain is polled, see [Operation::complete].
+type Finish<O> = Box<dyn FnOnce() -> O + Send>;
+
+/// The outputs of the steps of a [Chain], shared between the chain and its steps so that every
+/// step, along with any resources it hands back, lives until the last step is done.
+struct Outputs<O> {
+    slots: Mutex<Vec<Option<Finish<O>>>>,
+    done: OneShot<()>,
+}
+
+impl<O> Outputs<O> {
+    fn lock(&self) -> MutexGuard<'_, Vec<Option<Finish<O>>>> {
+        self.slots
+            .lock()
+            .expect("failed to lock chain outputs: poisoned")
+    }
+
+    fn store(&self, index: usize, output: Finish<O>) {
+        let mut slots = self.lock();
+        slots[index] = Some(output);
+        if slots.iter().all(Option::is_some) {
+            self.done.complete(());
+        }
+    }
+}
+
+/// A step of a [Chain], storing the wrapped [Operation] in its slot once done.
+struct Step<T>
+where
+    T: Operation,
+{
+    op: Cell<Option<OpState<T>>>,
+    index: usize,
+    outputs: Arc<Outputs<T::Output>>,
+}
+
+impl<T> Completion for Step<T>
+where
+    T: Operation,
+{
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        op::resolve(&self.op, value, |state| {
+            self.outputs
+                .store(self.index, Box::new(move || state.finish()))
+        })
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        op::as_entry(&mut self.op)
+    }
+}
+
+enum State {
+    Idle(Vec<(Box<dyn Completion>, Link)>),
+    Registered(Remote, Vec<u64>),
+    // The chain couldn't be registered, the error is handed back every time the chain is polled.
+    Failed(io::Error),
+    Done,
+}
+
+/// This represents a chain of events submitted to the ring as a single unit and executed by the
+/// kernel in order, such as reading a file and then sending it, without a round trip through the
+/// event loop in between. The chain resolves once every step has completed, with the output of
+/// each step in the order they were pushed. When a step fails any steps soft linked after it are
+/// short-circuited, see [Link] for details.
+///
+/// Each step is an [Operation], and all steps of a chain share the same [Operation::Output]. The
+/// output of a step is held on to until every step of the chain is done, even if the chain is
+/// dropped early, so a step may point into memory that an earlier step hands back as part of its
+/// output. The chain is submitted the first time it is polled, and any steps that haven't
+/// completed when it is dropped are canceled. Should the chain fail to be submitted, for example
+/// because it is longer than the submission queue, none of its steps are run and the chain
+/// resolves to that error.
+///
+/// # Examples
+///
+/// Sending a file, reusing the buffer it was read into:
+///
+/// ```no_run
+/// use std::{
+///     fs::File,
+///     io,
+///     os::fd::{AsRawFd, RawFd},
+/// };
+///
+/// use io_uring::{opcode, squeue, types::Fd};
+///
+/// use libuio::{
+///     io_uring::{Chain, Operation},
+///     net::TcpStream,
+/// };
+///
+/// /// The result of a step, along with the buffer it owned if any.
+/// type Output = (io::Result<u32>, Option<Vec<u8>>);
+///
+/// /// Read from a file into an owned buffer.
+/// struct ReadFile {
+///     fd: RawFd,
+///     buf: Vec<u8>,
+/// }
+///
+/// // SAFETY: The buffer lives on the heap, owned by the operation.
+/// unsafe impl Operation for ReadFile {
+///     type Output = Output;
+///
+///     fn as_entry(&mut self) -> squeue::Entry {
+///         opcode::Read::new(Fd(self.fd), self.buf.as_mut_ptr(), self.buf.len() as u32).build()
+///     }
+///
+///     fn complete(self, result: io::Result<u32>, _: u32) -> Output {
+///         (result, Some(self.buf))
+///     }
+/// }
+///
+/// /// Send the buffer owned by an earlier step of the same chain.
+/// struct SendBuf {
+///     fd: RawFd,
+///     ptr: *const u8,
+///     len: usize,
+/// }
+///
+/// // SAFETY: The pointer is only ever handed to the kernel, and never dereferenced.
+/// unsafe impl Send for SendBuf {}
+///
+/// // SAFETY: The buffer belongs to the read step, which the chain keeps alive until we are done.
+/// unsafe impl Operation for SendBuf {
+///     type Output = Output;
+///
+///     fn as_entry(&mut self) -> squeue::Entry {
+///         opcode::Send::new(Fd(self.fd), self.ptr, self.len as u32).build()
+///     }
+///
+///     fn complete(self, result: io::Result<u32>, _: u32) -> Output {
+///         (result, None)
+///     }
+/// }
+///
+/// async fn send_file(conn: &TcpStream, file: &File) -> io::Result<Vec<u8>> {
+///     let buf = vec![0u8; file.metadata()?.len() as usize];
+///     let (ptr, len) = (buf.as_ptr(), buf.len());
+///
+///     let mut chain = Chain::new();
+///     chain
+///         .push(ReadFile { fd: file.as_raw_fd(), buf })
+///         .push(SendBuf { fd: conn.as_raw_fd(), ptr, len });
+///
+///     let mut outputs = chain.await?.into_iter();
+///     let (read, buf) = outputs.next().unwrap();
+///     let (sent, _) = outputs.next().unwrap();
+///     read?;
+///     sent?;
+///
+///     // Hand the buffer back for the next file.
+///     Ok(buf.unwrap())
+/// }
+/// ```
+pub struct Chain<O> {
+    state: State,
+    outputs: Arc<Outputs<O>>,
+}
+
+impl<O> Chain<O>
+where
+    O: Send + 'static,
+{
+    /// Create a new empty [Chain], which resolves right away unless steps are pushed onto it.
+    pub fn new() -> Chain<O> {
+        Chain {
+            state: State::Idle(Vec::new()),
+            outputs: Arc::new(Outputs {
+                slots: Mutex::new(Vec::new()),
+                done: OneShot::new(),
+            }),
+        }
+    }
+
+    /// Push a step onto the chain, soft linked to the step before it, see [Link::Soft].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chain has already been polled.
+    pub fn push<T>(&mut self, op: T) -> &mut Self
+    where
+        T: Operation<Output = O>,
+    {
+        self.push_link(op, Link::Soft)
+    }
+
+    /// Push a step onto the chain, hard linked to the step before it, see [Link::Hard].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chain has already been polled.
+    pub fn push_hard<T>(&mut self, op: T) -> &mut Self
+    where
+        T: Operation<Output = O>,
+    {
+        self.push_link(op, Link::Hard)
+    }
+
+    fn push_link<T>(&mut self, op: T, link: Link) -> &mut Self
+    where
+        T: Operation<Output = O>,
+    {
+        let steps = match self.state {
+            State::Idle(ref mut steps) => steps,
+            _ => panic!("steps can't be pushed onto a chain once it has been polled"),
+        };
+
+        let mut slots = self.outputs.lock();
+        let step = Step {
+            op: Cell::new(Some(OpState::new(op))),
+            index: slots.len(),
+            outputs: self.outputs.clone(),
+        };
+        slots.push(None);
+        steps.push((Box::new(step), link));
+        drop(slots);
+        self
+    }
+
+    /// Returns the number of steps in the chain.
+    pub fn len(&self) -> usize {
+        self.outputs.lock().len()
+    }
+
+    /// Returns whether or not the chain has no steps.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Register the chain with the thread local [super::UringDriver] if it hasn't been registered
+    /// already, holding off while its backlog is full, see [super::UringDriver::poll_ready].
+    fn poll_register(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.state {
+            State::Idle(_) => {}
+            State::Failed(ref err) => return Poll::Ready(Err(copy_error(err))),
+            State::Registered(..) | State::Done => return Poll::Ready(Ok(())),
+        }
+        let mut uring = context::uring();
+        if uring.poll_ready(cx).is_pending() {
+            return Poll::Pending;
+        }
+        if let State::Idle(steps) = mem::replace(&mut self.state, State::Done) {
+            self.state = match uring.register_chain(steps) {
+                Ok(ids) => State::Registered(uring.remote(), ids),
+                Err(err) => State::Failed(err),
+            };
+        }
+        drop(uring);
+        self.poll_register(cx)
+    }
+}
+
+impl<O> Future for Chain<O>
+where
+    O: Send + 'static,
+{
+    type Output = io::Result<Vec<O>>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if matches!(self.state, State::Idle(_)) && self.is_empty() {
+            self.state = State::Done;
+            return Poll::Ready(Ok(Vec::new()));
+        }
+
+        match self.poll_register(cx) {
+            Poll::Ready(Ok(())) => self.outputs.done.set_waker(cx.waker().clone()),
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        }
+        if self.outputs.done.take().is_none() {
+            return Poll::Pending;
+        }
+
+        self.state = State::Done;
+        // Complete the steps outside of the lock, so that they are free to use the outputs.
+        let steps = mem::take(&mut *self.outputs.lock());
+        let outputs = steps.into_iter().flatten().map(|finish| finish()).collect();
+        Poll::Ready(Ok(outputs))
+    }
+}
+
+/// Make a copy of the given error, registering a chain only ever fails with an OS error.
+fn copy_error(err: &io::Error) -> io::Error {
+    match err.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(err.kind(), err.to_string()),
+    }
+}
+
+impl<O> Default for Chain<O>
+where
+    O: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O> Drop for Chain<O> {
+    fn drop(&mut self) {
+        // Cancel any steps still in flight, the kernel cancels the steps linked after a canceled
+        // step along with it.
+        if let State::Registered(ref ring, ref ids) = self.state {
+            let pending: Vec<_> = {
+                let slots = self.outputs.lock();
+                ids.iter()
+                    .zip(slots.iter())
+                    .filter(|(_, slot)| slot.is_none())
+                    .map(|(&id, _)| id)
+                    .collect()
+            };
+            for id in pending {
+                ring.deregister(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+    use futures::FutureExt;
+    use io_uring::{opcode, types::Fd};
+    use nix::libc;
+
+    use super::*;
+    use crate::{executor::block_on, io_uring::UringBuilder};
+
+    type Output = (io::Result<u32>, Option<Vec<u8>>);
+
+    struct Nop;
+
+    // SAFETY: A no-op doesn't reference any memory.
+    unsafe impl Operation for Nop {
+        type Output = Output;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Nop::new().build()
+        }
+
+        fn complete(self, result: io::Result<u32>, _: u32) -> Output {
+            (result, None)
+        }
+    }
+
+    /// A read into an owned buffer, which is handed back along with the result.
+    struct Read(RawFd, Vec<u8>);
+
+    // SAFETY: The buffer lives on the heap, owned by the operation.
+    unsafe impl Operation for Read {
+        type Output = Output;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Read::new(Fd(self.0), self.1.as_mut_ptr(), self.1.len() as u32)
+                .offset(u64::MAX)
+                .build()
+        }
+
+        fn complete(self, result: io::Result<u32>, _: u32) -> Output {
+            (result, Some(self.1))
+        }
+    }
+
+    /// A write from memory owned by an earlier step of the chain.
+    struct Write(RawFd, *const u8, usize);
+
+    // SAFETY: The pointer is only ever handed to the kernel, and never dereferenced.
+    unsafe impl Send for Write {}
+
+    // SAFETY: The memory belongs to an earlier step, which the chain keeps alive.
+    unsafe impl Operation for Write {
+        type Output = Output;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Write::new(Fd(self.0), self.1, self.2 as u32)
+                .offset(u64::MAX)
+                .build()
+        }
+
+        fn complete(self, result: io::Result<u32>, _: u32) -> Output {
+            (result, None)
+        }
+    }
+
+    fn errno(output: &Output) -> Option<i32> {
+        output.0.as_ref().err().and_then(io::Error::raw_os_error)
+    }
+
+    fn pipe() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn test_chain() {
+        block_on(async {
+            let mut chain = Chain::new();
+            chain
+                .push(Nop)
+                .push(Read(-1, vec![0; 8]))
+                .push(Nop)
+                .push(Nop);
+            let outputs = chain.await.unwrap();
+            assert_eq!(outputs.len(), 4);
+            assert_eq!(outputs[0].0.as_ref().unwrap(), &0);
+            assert_eq!(errno(&outputs[1]), Some(libc::EBADF));
+            assert_eq!(outputs[1].1.as_ref().unwrap().len(), 8);
+            assert_eq!(errno(&outputs[2]), Some(libc::ECANCELED));
+            assert_eq!(errno(&outputs[3]), Some(libc::ECANCELED));
+
+            let mut chain = Chain::new();
+            chain.push(Read(-1, vec![0; 8])).push_hard(Nop).push(Nop);
+            let outputs = chain.await.unwrap();
+            assert_eq!(errno(&outputs[0]), Some(libc::EBADF));
+            assert!(outputs[1..].iter().all(|output| output.0.is_ok()));
+
+            assert!(Chain::<Output>::new().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_chain_reuse() {
+        let (src_rx, src_tx) = pipe();
+        let (dst_rx, dst_tx) = pipe();
+        assert_eq!(
+            unsafe { libc::write(src_tx.as_raw_fd(), b"hello".as_ptr() as _, 5) },
+            5
+        );
+
+        // The write goes out of the buffer the read step owns, and hands back.
+        let buf = vec![0u8; 5];
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+        let mut chain = Chain::new();
+        chain
+            .push(Read(src_rx.as_raw_fd(), buf))
+            .push(Write(dst_tx.as_raw_fd(), ptr, len));
+        let mut outputs = block_on(chain).unwrap().into_iter();
+
+        let (read, buf) = outputs.next().unwrap();
+        assert_eq!(read.unwrap(), 5);
+        assert_eq!(buf.unwrap(), b"hello");
+        assert_eq!(outputs.next().unwrap().0.unwrap(), 5);
+
+        let mut out = [0u8; 5];
+        assert_eq!(
+            unsafe { libc::read(dst_rx.as_raw_fd(), out.as_mut_ptr() as _, 5) },
+            5
+        );
+        assert_eq!(&out, b"hello");
+    }
+
+    #[test]
+    fn test_chain_too_long() {
+        let mut uring = UringBuilder::new();
+        uring.entries(4);
+        context::handle().configure(uring);
+
+        let mut chain = Chain::new();
+        for _ in 0..8 {
+            chain.push(Nop);
+        }
+
+        // The error is handed back on every poll, rather than only the first one.
+        for _ in 0..2 {
+            let err = (&mut chain).now_or_never().unwrap().unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "once it has been polled")]
+    fn test_chain_push_after_poll() {
+        let (rx, _tx) = pipe();
+        let mut chain = Chain::new();
+        chain.push(Read(rx.as_raw_fd(), vec![0; 8]));
+        assert!((&mut chain).now_or_never().is_none());
+        chain.push(Nop);
+    }
+}
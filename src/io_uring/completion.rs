@@ -126,3 +126,16 @@ pub trait Completion: Send {
     /// of these at this layer so be forewarned.
     fn as_entry(&mut self) -> squeue::Entry;
 }
+
+impl<C> Completion for Box<C>
+where
+    C: Completion + ?Sized,
+{
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        (**self).resolve(value)
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        (**self).as_entry()
+    }
+}
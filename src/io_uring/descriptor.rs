@@ -0,0 +1,156 @@
+use std::{
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    thread::{self, ThreadId},
+};
+
+use io_uring::{squeue::Flags, types};
+use nix::sys::socket::{shutdown, Shutdown};
+
+use crate::context;
+
+use super::Remote;
+
+/// A [Descriptor] is the file descriptor owned by a socket, along with its slot in the fixed file
+/// table of a ring if it has one. Sockets are registered in the table of the ring of the thread
+/// that created them on a best effort basis, and events on them submitted to that ring are issued
+/// against the slot rather than the file descriptor.
+///
+/// Connections accepted directly into the fixed file table via [crate::net::Incoming::direct] have
+/// no regular file descriptor at all, and can only be used from the thread that accepted them.
+///
+/// The slot in the fixed file table holds a reference to the socket of its own, which can only be
+/// released by the thread that owns the ring. Dropping a [Descriptor] on any other thread shuts the
+/// socket down right away so that the peer sees it closed, while the slot itself is released the
+/// next time the owning thread runs its ring.
+pub(crate) struct Descriptor {
+    fd: Option<OwnedFd>,
+    fixed: Option<(Remote, u32)>,
+}
+
+impl Descriptor {
+    /// Create a new [Descriptor] for the given file descriptor, registering it in the fixed file
+    /// table of the ring of the current thread if possible.
+    pub(crate) fn new(fd: OwnedFd) -> Descriptor {
+        let fixed = {
+            let mut uring = context::uring();
+            uring
+                .register_file(fd.as_raw_fd())
+                .map(|slot| (uring.remote(), slot))
+        };
+
+        Descriptor {
+            fd: Some(fd),
+            fixed,
+        }
+    }
+
+    /// Create a new [Descriptor] for a file that only exists in the given slot of the fixed file
+    /// table of the given ring.
+    pub(crate) fn direct(ring: Remote, slot: u32) -> Descriptor {
+        Descriptor {
+            fd: None,
+            fixed: Some((ring, slot)),
+        }
+    }
+
+    /// Returns the [Target] to issue events on this descriptor against.
+    pub(crate) fn target(&self) -> Target {
+        Target {
+            fd: self.as_raw_fd(),
+            fixed: self
+                .fixed
+                .as_ref()
+                .map(|(ring, slot)| (ring.owner(), *slot)),
+        }
+    }
+
+    /// Returns whether or not this descriptor has a slot in a fixed file table.
+    pub(crate) fn is_fixed(&self) -> bool {
+        self.fixed.is_some()
+    }
+}
+
+impl AsRawFd for Descriptor {
+    /// Returns the raw file descriptor, or `-1` for a direct descriptor.
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_ref().map(AsRawFd::as_raw_fd).unwrap_or(-1)
+    }
+}
+
+impl Drop for Descriptor {
+    fn drop(&mut self) {
+        if let Some((ref ring, slot)) = self.fixed {
+            // Closing our file descriptor won't close the socket while the slot still references
+            // it, so don't leave the peer hanging until the owner gets around to releasing it.
+            if !ring.is_owner() {
+                if let Some(ref fd) = self.fd {
+                    let _ = shutdown(fd.as_raw_fd(), Shutdown::Both);
+                }
+            }
+            ring.unregister_file(slot);
+        }
+    }
+}
+
+/// Implemented by the sockets in [crate::net] to hand the [Target] of their [Descriptor] to the
+/// futures that issue events on them.
+pub(crate) trait AsTarget {
+    fn as_target(&self) -> Target;
+}
+
+/// A [Target] is a snapshot of a [Descriptor] held by a [super::Completion], used to pick between
+/// the fixed slot and the file descriptor when the event is submitted.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Target {
+    fd: RawFd,
+    fixed: Option<(ThreadId, u32)>,
+}
+
+impl Target {
+    /// Returns the file descriptor to build the [io_uring::squeue::Entry] with, along with the
+    /// [Flags] it needs. The fixed slot is only valid on the ring it was registered with, so
+    /// events submitted to any other ring fall back to the file descriptor.
+    pub(crate) fn resolve(&self) -> (types::Fd, Flags) {
+        match self.fixed_slot() {
+            Some(slot) => (types::Fd(slot as RawFd), Flags::FIXED_FILE),
+            None => (types::Fd(self.fd), Flags::empty()),
+        }
+    }
+
+    /// Returns the fixed slot of the descriptor if it is registered with the ring of the current
+    /// thread.
+    pub(crate) fn fixed_slot(&self) -> Option<u32> {
+        match self.fixed {
+            Some((owner, slot)) if owner == thread::current().id() => Some(slot),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_drop_off_owner() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let fd = Descriptor::new(OwnedFd::from(stream));
+        if !fd.is_fixed() {
+            return;
+        }
+
+        // The ring of this thread never runs again, yet the peer must still see the close.
+        thread::spawn(move || drop(fd)).join().unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(peer.read(&mut [0u8; 16]).unwrap(), 0);
+    }
+}
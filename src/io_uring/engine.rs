@@ -1,14 +1,39 @@
-use std::{collections::VecDeque, io};
+use std::{
+    collections::{HashMap, VecDeque},
+    io, mem,
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    ptr,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
 
 use io_uring::{
-    squeue,
+    cqueue,
+    squeue::{self, Flags},
     types::{SubmitArgs, Timespec},
     IoUring,
 };
 use nix::libc;
 use slab::Slab;
 
-use super::{cancel::Cancel, Completion, CompletionStatus};
+use crate::time::wheel::{TimerWheel, WheelTimeout};
+
+use super::{
+    cancel::Cancel,
+    capabilities::Capabilities,
+    epoll::{with_result, Epoll},
+    link_timeout::LinkTimeout,
+    msg_ring::Received,
+    remote::Pending,
+    user_data::{Kind, UserData},
+    Backend, BufRing, Completion, CompletionStatus, FixedBufPool, Link, Mailbox, Remote,
+    UringBuilder,
+};
+
+/// The `IORING_ENTER_GETEVENTS` enter flag, which isn't exposed by the version of [io_uring] in
+/// use.
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
 
 /// A IO Uring driver for registering and monitoring I/O events and integration in a low level
 /// aasync framework. This leverages an internal [io_uring::IoUring] to monitor and handle I/O
@@ -25,12 +50,86 @@ use super::{cancel::Cancel, Completion, CompletionStatus};
 /// trait. This [Completion] is used to both generate the internal [io_uring::opcode] that is used
 /// to register the event with the underlying io_uring, but also allos for passing back the result
 /// of that event once its complete.
+///
+/// Each [UringDriver] is owned by the thread that created it, and its ring is only ever touched by
+/// that thread. This allows for the ring to be setup with `IORING_SETUP_SINGLE_ISSUER` and
+/// `IORING_SETUP_DEFER_TASKRUN`, see [UringBuilder] for details.
+///
+/// On hosts where io_uring is unavailable the driver can instead be backed by `epoll`, which
+/// executes the same events at reduced performance, see [Backend] for details.
 pub struct UringDriver {
-    uring: IoUring,
-    backlog: VecDeque<squeue::Entry>,
-    state: Slab<Box<dyn Completion>>,
-    submit_timeout: Timespec,
+    ring: Ring,
+    cqes: Vec<cqueue::Entry>,
+    backlog: VecDeque<Vec<squeue::Entry>>,
+    backlog_entries: usize,
+    backlog_limit: usize,
+    backlog_wakers: Vec<Waker>,
+    deferred: VecDeque<Deferred>,
+    cq_overflows: u64,
+    dropped: u32,
+    state: Slab<Event>,
+    generation: u32,
+    stale: u64,
+    submit_timeout: Duration,
+    submit_timer: Option<UserData>,
     min_completions: usize,
+    caps: Capabilities,
+    wheel: Arc<Mutex<TimerWheel>>,
+    wheel_armed: Option<u64>,
+    remote: Remote,
+    files: Option<FileTable>,
+    buf_rings: HashMap<u16, BufRing>,
+    next_bgid: u16,
+    fixed_bufs: Option<FixedBufPool>,
+    mailbox: Option<Mailbox>,
+    eventfd: Option<OwnedFd>,
+    messages: VecDeque<Received>,
+    message_wakers: Vec<Waker>,
+}
+
+/// The backend executing the events of a [UringDriver].
+enum Ring {
+    Uring(IoUring),
+    Epoll(Epoll),
+}
+
+/// The number of submissions the driver may queue up in the backlog beyond the limit set via
+/// [UringBuilder::backlog], reserved for its own bookkeeping, so that cancelations and re-armed
+/// multishot events aren't held off by a backlog full of new events.
+const BACKLOG_RESERVE: usize = 64;
+
+/// A bookkeeping submission of the driver, held back once the backlog has run through its
+/// reserve, see [BACKLOG_RESERVE], until the backlog has drained.
+enum Deferred {
+    /// Re-arm the multishot event with the given user_data.
+    Rearm(UserData),
+    /// Cancel the event with the given user_data.
+    Cancel(UserData),
+}
+
+/// The state of an event registered with the ring. Once deregistered the event is marked as
+/// cancelling, but its [Completion] is held on to until the kernel hands back the final completion
+/// for it, since the kernel may still be using the resources it owns until then.
+///
+/// An event registered with a linked timeout and its [LinkTimeout] point at one another via
+/// `link`, which is what tells a timeout apart from any other cancellation of the event. Should the
+/// event be canceled before the completion of its timeout comes in, the completion of the event is
+/// held back until then.
+struct Event {
+    user_data: UserData,
+    completion: Box<dyn Completion>,
+    cancelling: bool,
+    link: Option<UserData>,
+    held: Option<cqueue::Entry>,
+    timed_out: bool,
+}
+
+/// The allocator for the slots of the fixed file table of a ring, see [UringBuilder::fixed_files].
+/// The lower half of the table is handed out by [UringDriver::register_file] while the upper half
+/// is left to the kernel for accepting connections directly into the table.
+struct FileTable {
+    free: Vec<u32>,
+    direct: u32,
 }
 
 impl UringDriver {
@@ -41,82 +140,929 @@ impl UringDriver {
     /// This method will error if the kernel doesn't support the io_uring features we need, or is
     /// otherwise unable to create the necessary kernel and userspace abstractions to use the ring.
     pub fn new(entries: u32) -> io::Result<UringDriver> {
-        let uring = IoUring::builder()
-            // .setup_defer_taskrun()
-            // .setup_single_issuer()
-            .build(entries)?;
+        UringBuilder::new().entries(entries).build()
+    }
+
+    /// Returns a new [UringBuilder] to configure a [UringDriver] with.
+    pub fn builder() -> UringBuilder {
+        UringBuilder::new()
+    }
+
+    /// Set the maximum number of submissions held in the backlog, see [UringBuilder::backlog].
+    pub(super) fn set_backlog_limit(&mut self, limit: usize) {
+        self.backlog_limit = limit;
+    }
+
+    pub(super) fn from_uring(uring: IoUring, fixed_files: u32, caps: Capabilities) -> UringDriver {
+        UringDriver::from_ring(Ring::Uring(uring), fixed_files, caps)
+    }
 
+    pub(super) fn from_epoll(epoll: Epoll) -> UringDriver {
+        UringDriver::from_ring(Ring::Epoll(epoll), 0, Capabilities::default())
+    }
+
+    fn from_ring(ring: Ring, fixed_files: u32, caps: Capabilities) -> UringDriver {
         let backlog = VecDeque::with_capacity(1024);
         let state = Slab::with_capacity(1024);
-        let submit_timeout = Timespec::new().nsec(100_000_000);
+        let submit_timeout = Duration::from_millis(100);
         let min_completions = 1;
 
-        Ok(UringDriver {
-            uring,
+        UringDriver {
+            ring,
+            cqes: Vec::with_capacity(1024),
             backlog,
+            backlog_entries: 0,
+            backlog_limit: usize::MAX,
+            backlog_wakers: Vec::new(),
+            deferred: VecDeque::new(),
+            cq_overflows: 0,
+            dropped: 0,
             state,
+            generation: 0,
+            stale: 0,
             submit_timeout,
+            submit_timer: None,
             min_completions,
-        })
+            caps,
+            wheel: Arc::new(Mutex::new(TimerWheel::new())),
+            wheel_armed: None,
+            remote: Remote::new(),
+            files: (fixed_files > 0).then(|| FileTable {
+                free: (0..fixed_files / 2).rev().collect(),
+                direct: fixed_files / 2,
+            }),
+            buf_rings: HashMap::new(),
+            next_bgid: 0,
+            fixed_bufs: None,
+            mailbox: None,
+            eventfd: None,
+            messages: VecDeque::new(),
+            message_wakers: Vec::new(),
+        }
+    }
+
+    /// Returns the [Capabilities] of the kernel and of the ring, as probed when it was set up. With
+    /// the [Backend::Epoll] backend no capabilities are reported.
+    pub fn capabilities(&self) -> Capabilities {
+        self.caps
+    }
+
+    /// Returns the [Backend] executing the events of this driver, this is never [Backend::Auto].
+    pub fn backend(&self) -> Backend {
+        match self.ring {
+            Ring::Uring(_) => Backend::IoUring,
+            Ring::Epoll(_) => Backend::Epoll,
+        }
+    }
+
+    /// Returns a [Mailbox] that other threads can use to post messages to this ring, which are
+    /// then picked up via [super::Messages].
+    ///
+    /// # Errors
+    ///
+    /// This method will error with `EOPNOTSUPP` with the [Backend::Epoll] backend, or if the file
+    /// descriptor of the ring couldn't be duplicated for the [Mailbox] to hold on to.
+    pub fn mailbox(&mut self) -> io::Result<Mailbox> {
+        if let Some(ref mailbox) = self.mailbox {
+            return Ok(mailbox.clone());
+        }
+
+        let fd = self.uring()?.as_raw_fd();
+        // SAFETY: The ring file descriptor is valid for as long as we hold on to the ring.
+        let ring = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        let mailbox = Mailbox::new(Arc::new(ring));
+        self.mailbox = Some(mailbox.clone());
+        Ok(mailbox)
+    }
+
+    /// Register an eventfd with the ring, which the kernel signals whenever an event completes,
+    /// returning a duplicate of its file descriptor. This allows for the [UringDriver] of the
+    /// current thread to be embedded in a foreign event loop, such as an existing `epoll` based
+    /// one, that watches the eventfd and calls [UringDriver::run_ready] once it is readable,
+    /// instead of driving the ring via [UringDriver::run] on a dedicated thread. The eventfd is
+    /// registered once, calling this again returns another duplicate of the same eventfd.
+    ///
+    /// # Errors
+    ///
+    /// This method will error with `EOPNOTSUPP` with the [Backend::Epoll] backend, or if the
+    /// eventfd couldn't be created or registered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::{os::fd::AsRawFd, time::Duration};
+    ///
+    /// use futures::{executor::LocalPool, task::LocalSpawnExt};
+    /// use libuio::{context, time};
+    /// use nix::libc;
+    ///
+    /// let eventfd = context::uring()
+    ///     .register_eventfd()
+    ///     .expect("failed to register eventfd");
+    ///
+    /// let mut pool = LocalPool::new();
+    /// pool.spawner()
+    ///     .spawn_local(async {
+    ///         time::sleep(Duration::from_secs(1)).await;
+    ///         println!("One second later!");
+    ///     })
+    ///     .expect("failed to spawn task");
+    ///
+    /// loop {
+    ///     // Poll the tasks until they are all waiting on events, submitting those events to the
+    ///     // ring along the way.
+    ///     pool.run_until_stalled();
+    ///     context::uring().run_ready().expect("failed to run I/O loop");
+    ///
+    ///     // Stand in for the foreign event loop, which watches the eventfd alongside its own.
+    ///     let mut pollfd = libc::pollfd {
+    ///         fd: eventfd.as_raw_fd(),
+    ///         events: libc::POLLIN,
+    ///         revents: 0,
+    ///     };
+    ///     unsafe { libc::poll(&mut pollfd, 1, -1) };
+    ///
+    ///     // Handle whatever completed, waking up the tasks waiting on it, which are then polled
+    ///     // at the top of the loop.
+    ///     context::uring().run_ready().expect("failed to run I/O loop");
+    /// }
+    /// ```
+    pub fn register_eventfd(&mut self) -> io::Result<OwnedFd> {
+        if let Some(ref eventfd) = self.eventfd {
+            return eventfd.try_clone();
+        }
+
+        let uring = self.uring()?;
+        // SAFETY: A successful call to eventfd returns a new file descriptor that we own.
+        let eventfd = match unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) } {
+            fd if fd < 0 => return Err(io::Error::last_os_error()),
+            fd => unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+        uring.submitter().register_eventfd(eventfd.as_raw_fd())?;
+
+        let dup = eventfd.try_clone()?;
+        self.eventfd = Some(eventfd);
+        Ok(dup)
+    }
+
+    /// Returns the next message posted to this ring, or registers the given [Waker] to be woken
+    /// once one is.
+    pub(crate) fn next_message(&mut self, waker: &Waker) -> Option<Received> {
+        let message = self.messages.pop_front();
+        if message.is_none() && !self.message_wakers.iter().any(|w| w.will_wake(waker)) {
+            self.message_wakers.push(waker.clone());
+        }
+        message
+    }
+
+    /// Returns the underlying io_uring, or an `EOPNOTSUPP` error with the [Backend::Epoll]
+    /// backend.
+    pub(super) fn uring(&self) -> io::Result<&IoUring> {
+        match self.ring {
+            Ring::Uring(ref uring) => Ok(uring),
+            Ring::Epoll(_) => Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP)),
+        }
+    }
+
+    /// Register a new [BufRing] of `entries` buffers of `buf_size` bytes each with this ring,
+    /// under a newly allocated buffer group id. The [BufRing] stays registered until it is passed
+    /// to [UringDriver::unregister_buf_ring], or the driver is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will error if `entries` isn't a power of two no larger than `32768`, if the
+    /// buffer memory couldn't be allocated, if all buffer group ids are in use, or if the kernel
+    /// doesn't support buffer rings, which includes the [Backend::Epoll] backend.
+    pub fn register_buf_ring(&mut self, entries: u16, buf_size: usize) -> io::Result<BufRing> {
+        self.uring()?;
+        let bgid = (0..=u16::MAX)
+            .map(|offset| self.next_bgid.wrapping_add(offset))
+            .find(|bgid| !self.buf_rings.contains_key(bgid))
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOSPC))?;
+
+        let ring = BufRing::alloc(bgid, entries, buf_size)?;
+        // SAFETY: The ring memory is kept alive by the clone we hold on to until unregistered.
+        unsafe {
+            self.uring()?
+                .submitter()
+                .register_buf_ring(ring.ring_addr(), ring.entries(), bgid)?;
+        }
+        self.buf_rings.insert(bgid, ring.clone());
+        self.next_bgid = bgid.wrapping_add(1);
+        Ok(ring)
+    }
+
+    /// Unregister the given [BufRing] from this ring, any receives still using it will fail. The
+    /// memory backing it is freed once the last [super::BorrowedBuffer] is returned.
+    pub fn unregister_buf_ring(&mut self, ring: &BufRing) -> io::Result<()> {
+        self.uring()?.submitter().unregister_buf_ring(ring.bgid())?;
+        self.buf_rings.remove(&ring.bgid());
+        Ok(())
+    }
+
+    /// Register a new [FixedBufPool] of `count` buffers of `buf_size` bytes each with this ring.
+    /// Only a single pool can be registered with a ring at a time, and it stays registered until
+    /// [UringDriver::unregister_buffers] is called, or the driver is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will error if `count` is zero or larger than `16384`, if the buffer memory
+    /// couldn't be allocated, if a pool is already registered, or if the kernel refuses to pin
+    /// the memory, for instance due to `RLIMIT_MEMLOCK`.
+    ///
+    /// With the [Backend::Epoll] backend the pool is never registered, and its buffers are always
+    /// treated as regular buffers.
+    pub fn register_buffers(&mut self, count: u16, buf_size: usize) -> io::Result<FixedBufPool> {
+        if self.fixed_bufs.is_some() {
+            return Err(io::Error::from_raw_os_error(libc::EBUSY));
+        }
+
+        let pool = FixedBufPool::alloc(count, buf_size)?;
+        if let Ring::Uring(ref uring) = self.ring {
+            // SAFETY: The pool memory is kept alive by the clone we hold on to until unregistered.
+            unsafe {
+                uring.submitter().register_buffers(&pool.iovecs())?;
+            }
+            pool.set_registered(true);
+        }
+        self.fixed_bufs = Some(pool.clone());
+        Ok(pool)
+    }
+
+    /// Unregister the [FixedBufPool] registered with this ring if there is one, any of its
+    /// [super::FixedBuf]s used afterwards are treated as regular buffers. The memory backing it
+    /// is freed once the last [super::FixedBuf] is returned.
+    pub fn unregister_buffers(&mut self) -> io::Result<()> {
+        let uring = match self.ring {
+            Ring::Uring(ref uring) => uring,
+            Ring::Epoll(_) => {
+                self.fixed_bufs = None;
+                return Ok(());
+            }
+        };
+        if let Some(pool) = self.fixed_bufs.take() {
+            pool.set_registered(false);
+            if let Err(err) = uring.submitter().unregister_buffers() {
+                pool.set_registered(true);
+                self.fixed_bufs = Some(pool);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the [FixedBufPool] registered with this ring, if there is one.
+    pub fn fixed_buffers(&self) -> Option<FixedBufPool> {
+        self.fixed_bufs.clone()
+    }
+
+    /// Register the given file descriptor in a free slot of the fixed file table of this ring,
+    /// returning the slot or `None` if the table is full or disabled. Events issued against the
+    /// slot skip the file table lookup and reference counting the kernel otherwise does on every
+    /// submission.
+    pub(crate) fn register_file(&mut self, fd: RawFd) -> Option<u32> {
+        let uring = match self.ring {
+            Ring::Uring(ref uring) => uring,
+            Ring::Epoll(_) => return None,
+        };
+        let files = self.files.as_mut()?;
+        let slot = files.free.pop()?;
+        match uring.submitter().register_files_update(slot, &[fd]) {
+            Ok(_) => Some(slot),
+            Err(_) => {
+                files.free.push(slot);
+                None
+            }
+        }
+    }
+
+    /// Remove the file in the given slot from the fixed file table of this ring, this works for
+    /// both slots returned by [UringDriver::register_file] and those allocated by the kernel.
+    pub(crate) fn unregister_file(&mut self, slot: u32) {
+        let (uring, files) = match (&self.ring, self.files.as_mut()) {
+            (Ring::Uring(uring), Some(files)) => (uring, files),
+            _ => return,
+        };
+        if uring.submitter().register_files_update(slot, &[-1]).is_ok() && slot < files.direct {
+            files.free.push(slot);
+        }
+    }
+
+    /// Returns a [Remote] handle to this driver, that can be used to deregister events from any
+    /// thread.
+    pub(crate) fn remote(&self) -> Remote {
+        self.remote.clone()
+    }
+
+    /// Returns whether or not the current thread owns this driver.
+    pub(crate) fn is_owner(&self) -> bool {
+        self.remote.is_owner()
+    }
+
+    /// Returns a handle to the [TimerWheel] driven by this driver.
+    pub(crate) fn timer_wheel(&self) -> Arc<Mutex<TimerWheel>> {
+        self.wheel.clone()
+    }
+
+    fn lock_wheel(&self) -> MutexGuard<'_, TimerWheel> {
+        self.wheel
+            .lock()
+            .expect("failed to lock timer wheel: poisoned")
+    }
+
+    /// Ensure we have a kernel timeout in flight to wake us up in time for the next slot in the
+    /// [TimerWheel], we only ever need a new one if the wheel's next deadline moved earlier than
+    /// the one we have armed.
+    fn arm_wheel(&mut self) {
+        let (next, timeout) = {
+            let wheel = self.lock_wheel();
+            let next = match wheel.next_deadline() {
+                Some(next) => next,
+                None => return,
+            };
+            let timeout = wheel
+                .tick_instant(next)
+                .saturating_duration_since(Instant::now());
+            (next, timeout)
+        };
+
+        if matches!(self.wheel_armed, Some(armed) if armed <= next) {
+            return;
+        }
+        // With the reserve of the backlog used up the timeout is armed on a later iteration, once
+        // the backlog has drained.
+        if !self.has_reserve() {
+            return;
+        }
+        let entry = self.insert(WheelTimeout::new(timeout), Kind::Timer);
+        self.enqueue(&[entry]);
+        self.wheel_armed = Some(next);
+    }
+
+    /// Advance the [TimerWheel] to the current time, waking any tasks waiting on an expired
+    /// deadline.
+    fn fire_wheel(&mut self) {
+        let (now, wakers) = {
+            let mut wheel = self.lock_wheel();
+            let now = wheel.now_tick(Instant::now());
+            (now, wheel.advance(now))
+        };
+        if matches!(self.wheel_armed, Some(armed) if armed <= now) {
+            self.wheel_armed = None;
+        }
+
+        for waker in wakers {
+            waker.wake();
+        }
     }
 
     fn clear_backlog(&mut self) -> io::Result<()> {
-        let (submitter, mut sq, _) = self.uring.split();
-        loop {
-            if sq.is_full() {
+        self.submit_backlog()?;
+
+        // Catch up on any bookkeeping held back while the backlog was out of reserve.
+        while self.backlog_entries < self.reserve_limit() {
+            match self.deferred.pop_front() {
+                Some(deferred) => self.submit_deferred(deferred),
+                None => break,
+            }
+        }
+
+        // Let any tasks held back by a full backlog try again now that there is room.
+        if self.backlog_entries < self.backlog_limit {
+            for waker in self.backlog_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    /// Move as much of the backlog into the submission queue as fits.
+    fn submit_backlog(&mut self) -> io::Result<()> {
+        let uring = match self.ring {
+            Ring::Uring(ref mut uring) => uring,
+            Ring::Epoll(_) => return Ok(()),
+        };
+        let (submitter, mut sq, _) = uring.split();
+        while let Some(chain) = self.backlog.front() {
+            // Linked entries have to land in the submission queue together, so make sure there is
+            // room for the whole chain, submitting what we have to free up space if needed.
+            if sq.capacity() - sq.len() < chain.len() {
+                sq.sync();
                 match submitter.submit() {
                     Ok(_) => (),
                     Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => break,
                     Err(err) => return Err(err),
                 }
+                sq.sync();
+
+                if sq.capacity() - sq.len() < chain.len() {
+                    break;
+                }
             }
-            sq.sync();
 
-            match self.backlog.pop_front() {
-                Some(sqe) => unsafe {
-                    let _ = sq.push(&sqe);
-                },
-                None => break,
+            unsafe {
+                let _ = sq.push_multiple(chain);
             }
+            self.backlog_entries -= chain.len();
+            self.backlog.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Check whether there is room for a new submission, without the backlog growing beyond the
+    /// limit set via [UringBuilder::backlog]. If the backlog is full the waker of the given
+    /// [Context] is woken once it has drained below the limit again, this is used by futures to
+    /// hold off registering new events while the kernel catches up.
+    ///
+    /// The limit applies to every registration, [UringDriver::register] and friends fail with
+    /// `EAGAIN` while the backlog is full. Only the driver's own bookkeeping, such as cancelations
+    /// and re-armed multishot events, may use a small reserve beyond the limit, and is held back
+    /// until the backlog drains once that reserve is used up as well.
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.backlog_entries < self.backlog_limit {
+            return Poll::Ready(());
+        }
+        if !self
+            .backlog_wakers
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            self.backlog_wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Fail with `EAGAIN` if the backlog is full, see [UringDriver::poll_ready].
+    fn check_backlog(&self) -> io::Result<()> {
+        if self.backlog_entries >= self.backlog_limit {
+            return Err(io::Error::from_raw_os_error(libc::EAGAIN));
         }
         Ok(())
     }
 
-    fn enqueue(&mut self, entry: squeue::Entry) {
-        // Push the new entry onto the submission queue, or fallback to our local VecDeque on
+    /// The limit of the backlog for bookkeeping submissions, see [BACKLOG_RESERVE].
+    fn reserve_limit(&self) -> usize {
+        self.backlog_limit.saturating_add(BACKLOG_RESERVE)
+    }
+
+    /// Returns whether a bookkeeping submission can go ahead right away, rather than being
+    /// deferred until the backlog has drained. Bookkeeping is submitted in order, so nothing goes
+    /// ahead of what was deferred before.
+    fn has_reserve(&self) -> bool {
+        self.deferred.is_empty() && self.backlog_entries < self.reserve_limit()
+    }
+
+    /// Submit the given bookkeeping, or hold it back until the backlog has drained if it has run
+    /// through its reserve.
+    fn defer(&mut self, deferred: Deferred) {
+        if self.has_reserve() {
+            self.submit_deferred(deferred);
+        } else {
+            self.deferred.push_back(deferred);
+        }
+    }
+
+    fn submit_deferred(&mut self, deferred: Deferred) {
+        match deferred {
+            Deferred::Rearm(user_data) => {
+                let event = match self.event_mut(user_data) {
+                    Some(event) => event,
+                    None => return,
+                };
+                // The event was deregistered while waiting to be re-armed, and isn't in flight, so
+                // there is nothing left to cancel.
+                if event.cancelling {
+                    self.state.remove(user_data.index());
+                    return;
+                }
+                let entry = event.completion.as_entry().user_data(user_data.into());
+                self.enqueue(&[entry]);
+            }
+            Deferred::Cancel(user_data) => {
+                if self.event_mut(user_data).is_none() {
+                    return;
+                }
+                let entry = self.insert(
+                    Cancel::new(user_data.into(), self.caps.cancel_all()),
+                    Kind::Cancel,
+                );
+                self.enqueue(&[entry]);
+            }
+        }
+    }
+
+    fn enqueue(&mut self, entries: &[squeue::Entry]) {
+        // Push the new entries onto the submission queue, or fallback to our local VecDeque on
         // error. The error in question here, is a queue full error, and is meant to be retried,
         // which is handled in the clear_backlog() fn above.
+        let uring = match self.ring {
+            Ring::Uring(ref mut uring) => uring,
+            Ring::Epoll(ref mut epoll) => return epoll.submit(entries),
+        };
         unsafe {
-            if self.uring.submission().push(&entry).is_err() {
-                self.backlog.push_back(entry);
+            if uring.submission().push_multiple(entries).is_err() {
+                self.backlog_entries += entries.len();
+                self.backlog.push_back(entries.to_vec());
             }
         }
     }
 
+    /// Store the given [Completion] under a new state index, returning its entry tagged with the
+    /// [UserData] identifying it.
+    fn insert(&mut self, mut op: impl Completion + 'static, kind: Kind) -> squeue::Entry {
+        let entry = self.state.vacant_entry();
+        let user_data = UserData::new(entry.key(), self.generation, kind);
+        self.generation = UserData::next_generation(self.generation);
+
+        let sqe = op.as_entry().user_data(user_data.into());
+        entry.insert(Event {
+            user_data,
+            completion: Box::new(op),
+            cancelling: false,
+            link: None,
+            held: None,
+            timed_out: false,
+        });
+        sqe
+    }
+
+    /// Returns the state of the event identified by the given user_data, if it is still around.
+    fn event_mut(&mut self, user_data: UserData) -> Option<&mut Event> {
+        self.state
+            .get_mut(user_data.index())
+            .filter(|event| event.user_data == user_data)
+    }
+
     /// Register a new event on the io_uring, this will handle storing the passed in [Completion]
-    /// and registering it with the io_uring. Once done it will return the state index to be used
-    /// for calls to [UringDriver::deregister] in the event the future that generates this
-    /// [Completion] is dropped before it completes.
-    pub fn register(&mut self, mut op: impl Completion + 'static) -> usize {
-        let entry = op.as_entry();
-        let index = self.state.insert(Box::new(op));
-        self.enqueue(entry.user_data(index as _));
-        index
-    }
-
-    /// Remove an event from the io_uring, this is a best effort attempt at deregistering a given
-    /// event. It will remove the state object, and then issue an async cancel event to cleanup
-    /// pending events if they still happen to be on the io_uring. Note this will not guarantee
-    /// that the event doesn't trigger before the canel finishes.
-    pub fn deregister(&mut self, index: usize) {
-        if self.state.try_remove(index).is_none() {
+    /// and registering it with the io_uring. Once done it will return the user_data identifying
+    /// the event to be used for calls to [UringDriver::deregister] in the event the future that
+    /// generates this [Completion] is dropped before it completes.
+    ///
+    /// The user_data combines the state index of the event with a generation, so that it remains
+    /// unique after the event is done and its state index reused.
+    ///
+    /// # Errors
+    ///
+    /// This method will error with `EAGAIN` if the backlog is full, see [UringDriver::poll_ready],
+    /// in which case the [Completion] is dropped without being registered.
+    pub fn register(&mut self, op: impl Completion + 'static) -> io::Result<u64> {
+        self.check_backlog()?;
+        let entry = self.insert(op, Kind::Op);
+        let user_data = entry.get_user_data();
+        self.enqueue(&[entry]);
+        Ok(user_data)
+    }
+
+    /// Register a new event on the io_uring like [UringDriver::register], with a linked timeout.
+    /// This submits the event with an `IORING_OP_LINK_TIMEOUT` linked to it, such that if the
+    /// event hasn't completed by the time `timeout` elapses the kernel will cancel it, and the
+    /// [Completion] will be resolved with a result of `-ETIMEDOUT`. Any other cancellation of the
+    /// event is still resolved with a result of `-ECANCELED`.
+    ///
+    /// # Errors
+    ///
+    /// This method will error with `EAGAIN` if the backlog is full, like [UringDriver::register].
+    pub fn register_with_timeout(
+        &mut self,
+        op: impl Completion + 'static,
+        timeout: Duration,
+    ) -> io::Result<u64> {
+        self.check_backlog()?;
+        let entry = self.insert(op, Kind::Op);
+        let user_data = entry.get_user_data();
+        let link_entry = self.insert(LinkTimeout::new(timeout), Kind::LinkTimeout);
+
+        let (op, link) = (UserData::from(user_data), link_entry.get_user_data().into());
+        if let Some(event) = self.event_mut(op) {
+            event.link = Some(link);
+        }
+        if let Some(event) = self.event_mut(link) {
+            event.link = Some(op);
+        }
+
+        self.enqueue(&[entry.flags(Flags::IO_LINK), link_entry]);
+        Ok(user_data)
+    }
+
+    /// Register a chain of events on the io_uring, which are submitted as a single unit and
+    /// executed by the kernel one after the other. Each event is linked to the one before it as
+    /// given by its [Link], which is ignored for the first event. Once an event fails, any events
+    /// soft linked after it are resolved with a result of `-ECANCELED` rather than executed, see
+    /// [Link] for details. Returns the user_data of each event in the chain, in order.
+    ///
+    /// # Errors
+    ///
+    /// This method will error with `EINVAL` if the chain is longer than the submission queue, and
+    /// with `EAGAIN` if the backlog is full, like [UringDriver::register]. In either case none of
+    /// the events are registered.
+    pub fn register_chain<C>(
+        &mut self,
+        ops: impl IntoIterator<Item = (C, Link)>,
+    ) -> io::Result<Vec<u64>>
+    where
+        C: Completion + 'static,
+    {
+        let ops: Vec<_> = ops.into_iter().collect();
+        if let Ring::Uring(ref mut uring) = self.ring {
+            if ops.len() > uring.submission().capacity() {
+                return Err(io::Error::from_raw_os_error(libc::EINVAL));
+            }
+        }
+        self.check_backlog()?;
+
+        let mut entries: Vec<squeue::Entry> = Vec::with_capacity(ops.len());
+        for (op, link) in ops {
+            let entry = self.insert(op, Kind::Op);
+            if let Some(prev) = entries.pop() {
+                let flags = match link {
+                    Link::Soft => Flags::IO_LINK,
+                    Link::Hard => Flags::IO_HARDLINK,
+                };
+                entries.push(prev.flags(flags));
+            }
+            entries.push(entry);
+        }
+
+        let user_data = entries.iter().map(|entry| entry.get_user_data()).collect();
+        if !entries.is_empty() {
+            self.enqueue(&entries);
+        }
+        Ok(user_data)
+    }
+
+    /// Remove an event from the io_uring, this issues an async cancel event for the given event
+    /// and marks it as cancelling. The state object is kept around, owning any resources the
+    /// kernel may still be using, until the final completion for the event comes back, which is
+    /// then dropped rather than resolved. Events that never made it out of the backlog are removed
+    /// right away. Note this will not guarantee that the event doesn't complete successfully
+    /// before the cancel finishes.
+    ///
+    /// Deregistering an event that is already done is a no-op, even if its state index has since
+    /// been reused by another event.
+    pub fn deregister(&mut self, user_data: u64) {
+        let user_data = UserData::from(user_data);
+        if user_data.kind() != Some(Kind::Op) {
+            return;
+        }
+        match self.event_mut(user_data) {
+            Some(event) if !event.cancelling => event.cancelling = true,
+            _ => return,
+        }
+
+        if self.remove_backlogged(user_data) {
+            return;
+        }
+        self.defer(Deferred::Cancel(user_data));
+    }
+
+    /// Remove the chain of entries for the event with the given user_data from the backlog, along
+    /// with the state of every event in that chain, returning whether it was found.
+    fn remove_backlogged(&mut self, user_data: UserData) -> bool {
+        let position = self.backlog.iter().position(|chain| {
+            chain
+                .iter()
+                .any(|entry| UserData::from(entry.get_user_data()) == user_data)
+        });
+        let chain = match position.and_then(|position| self.backlog.remove(position)) {
+            Some(chain) => chain,
+            None => return false,
+        };
+
+        self.backlog_entries -= chain.len();
+        for entry in chain {
+            let user_data = UserData::from(entry.get_user_data());
+            if self.event_mut(user_data).is_some() {
+                self.state.remove(user_data.index());
+            }
+        }
+        true
+    }
+
+    /// Cancel the event with the given user_data, unlike [UringDriver::deregister] the
+    /// [Completion] stays registered, and is resolved with whatever result the kernel hands back
+    /// for the canceled event, typically `-ECANCELED`.
+    pub fn cancel(&mut self, user_data: u64) {
+        let user_data = UserData::from(user_data);
+        if self.event_mut(user_data).is_none() {
+            return;
+        }
+        self.defer(Deferred::Cancel(user_data));
+    }
+
+    /// Cancel the event with the given user_data like [UringDriver::cancel], on behalf of a timeout
+    /// that isn't linked in the kernel, such as one tracked by the paused clock. The [Completion]
+    /// is resolved with a result of `-ETIMEDOUT` in place of `-ECANCELED`.
+    pub(crate) fn expire(&mut self, user_data: u64) {
+        match self.event_mut(UserData::from(user_data)) {
+            Some(event) => event.timed_out = true,
+            None => return,
+        }
+        self.cancel(user_data);
+    }
+
+    /// Returns the number of events that have been deregistered, but whose final completion
+    /// hasn't been handed back by the kernel yet.
+    pub fn cancelling(&self) -> usize {
+        self.state
+            .iter()
+            .filter(|(_, event)| event.cancelling)
+            .count()
+    }
+
+    /// Returns the number of completions that were dropped since they didn't match the event
+    /// holding their state index, which happens when a completion arrives after its event is done.
+    pub fn stale_completions(&self) -> u64 {
+        self.stale
+    }
+
+    /// Returns the number of submissions waiting in the backlog for room in the submission queue,
+    /// counting every entry of a chain of linked events, including linked timeouts.
+    pub fn backlog_len(&self) -> usize {
+        self.backlog_entries
+    }
+
+    /// Returns the number of iterations of the event loop that found the completion queue
+    /// overflowed, signaled by the kernel via `IORING_SQ_CQ_OVERFLOW`. The kernel holds on to
+    /// the completions that didn't fit until there is room again, so this is a sign that the ring
+    /// is undersized for the number of events in flight, see [UringBuilder::entries].
+    pub fn cq_overflows(&self) -> u64 {
+        self.cq_overflows
+    }
+
+    /// Returns the number of completions the kernel dropped since the completion queue was full,
+    /// which only happens on kernels without `IORING_FEAT_NODROP` or when the kernel runs out of
+    /// memory to hold on to them.
+    pub fn dropped_completions(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Submit any pending events in the submission queue and wait for the configured number of
+    /// completions or the timeout to expire.
+    ///
+    /// With `IORING_SETUP_SQPOLL` the kernel poller picks up submissions on its own, so we only
+    /// need to enter the kernel if there is nothing to reap and we have to wait, or if the poller
+    /// has gone idle and needs waking.
+    ///
+    /// Kernels without [Capabilities::ext_arg] can't take a timeout when entering the kernel, so
+    /// instead we keep a timeout event in flight on the ring to bound the wait.
+    fn submit(&mut self) -> io::Result<usize> {
+        let uring = match self.ring {
+            Ring::Uring(ref mut uring) => uring,
+            Ring::Epoll(ref mut epoll) => return epoll.wait(self.submit_timeout).map(|_| 0),
+        };
+
+        if uring.params().is_setup_sqpoll() && !uring.completion().is_empty() {
+            if !uring.submission().need_wakeup() {
+                return Ok(0);
+            }
+            return uring.submitter().submit();
+        }
+
+        if !self.caps.ext_arg() {
+            if self.submit_timer.is_none() {
+                // Without room for the timer, only submit rather than waiting without a timeout.
+                if !self.has_reserve() {
+                    return self.uring()?.submitter().submit();
+                }
+                let entry = self.insert(WheelTimeout::new(self.submit_timeout), Kind::Timer);
+                self.submit_timer = Some(UserData::from(entry.get_user_data()));
+                self.enqueue(&[entry]);
+            }
+            return self
+                .uring()?
+                .submitter()
+                .submit_and_wait(self.min_completions);
+        }
+
+        let timespec = Timespec::from(self.submit_timeout);
+        let args = SubmitArgs::new().timespec(&timespec);
+        uring
+            .submitter()
+            .submit_with_args(self.min_completions, &args)
+    }
+
+    /// Submit any pending events in the submission queue, and pick up any completions the kernel
+    /// has ready without waiting for them. With `IORING_SETUP_DEFER_TASKRUN` completions are only
+    /// posted once we ask for them, so we enter the kernel even if there is nothing to submit.
+    fn submit_nowait(&mut self) -> io::Result<usize> {
+        let uring = match self.ring {
+            Ring::Uring(ref mut uring) => uring,
+            Ring::Epoll(ref mut epoll) => return epoll.wait(Duration::ZERO).map(|_| 0),
+        };
+        if uring.params().is_setup_sqpoll() {
+            return uring.submitter().submit();
+        }
+
+        let to_submit = uring.submission().len();
+        // SAFETY: Entering the ring with no arguments only submits and reaps events.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                uring.as_raw_fd(),
+                to_submit as libc::c_uint,
+                0 as libc::c_uint,
+                IORING_ENTER_GETEVENTS,
+                ptr::null::<libc::sigset_t>(),
+                0 as libc::size_t,
+            )
+        };
+        match ret {
+            ret if ret < 0 => Err(io::Error::last_os_error()),
+            ret => Ok(ret as usize),
+        }
+    }
+
+    /// Resolve the [Completion] of the event the given completion queue entry belongs to.
+    fn resolve(&mut self, mut cqe: cqueue::Entry) {
+        let user_data = UserData::from(cqe.user_data());
+
+        // Messages posted by other rings have no state, queue them up for [super::Messages].
+        if matches!(
+            user_data.kind(),
+            Some(Kind::Message | Kind::File | Kind::Wake)
+        ) {
+            if let Some(message) = Received::decode(&cqe) {
+                self.messages.push_back(message);
+                for waker in self.message_wakers.drain(..) {
+                    waker.wake();
+                }
+            }
+            return;
+        }
+
+        // Lookup the state for this event, and if not found, or if its state index has since been
+        // reused by another event, just drop the completion and continue onto the next one.
+        let event = match self.state.get_mut(user_data.index()) {
+            Some(event) if event.user_data == user_data => event,
+            _ => {
+                self.stale += 1;
+                return;
+            }
+        };
+
+        if user_data.kind() == Some(Kind::LinkTimeout) {
+            if let Some(op) = event.link {
+                self.resolve_link(op, cqe.result());
+            }
+            self.resolve_event(user_data, cqe);
             return;
         }
 
-        let mut op = Cancel::new(index);
-        let entry = op.as_entry();
-        let index = self.state.insert(Box::new(op));
-        self.enqueue(entry.user_data(index as _));
+        if cqe.result() == -libc::ECANCELED && !cqueue::more(cqe.flags()) {
+            // Whether or not a canceled event timed out is only known once the completion of its
+            // linked timeout comes in.
+            if event.link.is_some() {
+                event.held = Some(cqe);
+                return;
+            }
+            if event.timed_out {
+                cqe = with_result(&cqe, -libc::ETIMEDOUT);
+            }
+        }
+        self.resolve_event(user_data, cqe);
+    }
+
+    /// Resolve the [Completion] of the event with the given user_data, which must still be around.
+    fn resolve_event(&mut self, user_data: UserData, cqe: cqueue::Entry) {
+        let event = &mut self.state[user_data.index()];
+
+        // Resolve the [Completion] and handle the result, nobody is waiting on the result of a
+        // cancelling event, but resolving it still tells us whether this is its final completion.
+        use CompletionStatus::*;
+        match event.completion.resolve(cqe) {
+            Armed => {
+                // Do nothing we are already armed, and we don't want to remove the state yet since
+                // this is likely a multi-shot event and we are awaiting new events to be generated.
+            }
+            Rearm if !event.cancelling => {
+                // We have a multi-shot requesting that we re-arm it, so lets go ahead and do that
+                // so that we continue to get new updates.
+                self.defer(Deferred::Rearm(user_data));
+            }
+            Rearm | Finalized => {
+                // Our event is handled and done, or was canceled, go ahead and clean up our state
+                // entry so its slot can be reused.
+                self.state.remove(user_data.index());
+                if self.submit_timer == Some(user_data) {
+                    self.submit_timer = None;
+                }
+            }
+        };
+    }
+
+    /// Handle the completion of the linked timeout of the given event, the timeout fired if its
+    /// result is `-ETIME`, in which case the event was canceled on its behalf. Any completion of
+    /// the event held back waiting on this is resolved now.
+    fn resolve_link(&mut self, op: UserData, result: i32) {
+        let event = match self.event_mut(op) {
+            Some(event) => event,
+            None => return,
+        };
+        event.link = None;
+        event.timed_out |= result == -libc::ETIME;
+        if let Some(cqe) = event.held.take() {
+            self.resolve(cqe);
+        }
     }
 
     /// Execute an iteration of the io_uring event loop, this will handle submitting any pending
@@ -125,67 +1071,386 @@ impl UringDriver {
     /// returning control back to the caller which should then check for any now awoken async tasks
     /// with pending data to hand off.
     pub fn run(&mut self) -> io::Result<()> {
+        self.run_once(true).map(drop)
+    }
+
+    /// Execute an iteration of the io_uring event loop like [UringDriver::run], without waiting
+    /// for any events to complete. This submits any pending events and handles any completed
+    /// events, waking up the tasks waiting on them, and returns the number of completion events
+    /// that were handled. This also resets the eventfd registered via
+    /// [UringDriver::register_eventfd], so that it only becomes readable again once another event
+    /// completes.
+    ///
+    /// Note that a foreign event loop calling this should also call it periodically while any
+    /// timers are pending, as well as after polling tasks that may have registered new events.
+    pub fn run_ready(&mut self) -> io::Result<usize> {
+        self.run_once(false)
+    }
+
+    fn run_once(&mut self, wait: bool) -> io::Result<usize> {
         // First we need to create new [SubmitArgs] such that we can supply our timeout, since we
         // do not want to block the overall event loop in the executor for an indeterminate period
         // of time potentially starving tasks from execution time.
-        let args = SubmitArgs::new().timespec(&self.submit_timeout);
+        self.arm_wheel();
 
-        // Now we submit any pending events in our submission queue and we wait.
-        match self
-            .uring
-            .submitter()
-            .submit_with_args(self.min_completions, &args)
-        {
+        // Pick up any work handed off to us by other threads.
+        for pending in self.remote.take() {
+            match pending {
+                Pending::Deregister(user_data) => self.deregister(user_data),
+                Pending::Expire(user_data) => self.expire(user_data),
+                Pending::Unregister(slot) => self.unregister_file(slot),
+            }
+        }
+
+        // Now we submit any pending events in our submission queue and we wait, if asked to.
+        let submitted = match wait {
+            true => self.submit(),
+            false => self.submit_nowait(),
+        };
+        match submitted {
             Ok(_) => {}
             Err(e) => match e.raw_os_error() {
                 Some(libc::EBUSY) => {} // The ring is currently busy just continue on.
                 Some(libc::ETIME) => {} // We timed out just continue on.
+                Some(libc::EINTR) => {} // We were interrupted by a signal just continue on.
                 _ => return Err(e),     // The ring is broken terminate.
             },
         }
 
+        // Reset the eventfd, if any, now that the kernel has posted the completions it had for us,
+        // any completions posted from here on signal it again.
+        if let Some(ref eventfd) = self.eventfd {
+            // The eventfd is nonblocking, it is fine for there to be nothing to read.
+            let mut count = [0u8; 8];
+            // SAFETY: The buffer is valid for writes of its length.
+            unsafe { libc::read(eventfd.as_raw_fd(), count.as_mut_ptr().cast(), count.len()) };
+        }
+
         // Now we clear any submission events we can that overflowed into our backlog, this should
         // usually be empty hopefully, but just in case lets make sure to handle them.
         self.clear_backlog()?;
 
-        // Finally iterate over any completion events we have, looking up their state objects and
+        // Keep track of the completion queue overflowing, the overflowed completions are flushed
+        // into the completion queue the next time we enter the kernel to wait for completions.
+        if let Ring::Uring(ref mut uring) = self.ring {
+            if uring.submission().cq_overflow() {
+                self.cq_overflows += 1;
+            }
+            self.dropped = uring.completion().overflow();
+        }
+
+        // Then iterate over any completion events we have, looking up their state objects and
         // calling [Completion::resolve] on any completed events.
-        let (_, mut sq, mut cq) = self.uring.split();
-        for cqe in &mut cq {
-            let user_data = cqe.user_data();
-
-            // Lookup the state for this event, and if not found just drop the completion and
-            // continue onto the next one.
-            let state = match self.state.get_mut(user_data as usize) {
-                Some(state) => state,
-                None => continue,
-            };
+        let mut cqes = mem::take(&mut self.cqes);
+        match self.ring {
+            Ring::Uring(ref mut uring) => cqes.extend(uring.completion()),
+            Ring::Epoll(ref mut epoll) => cqes.extend(epoll.completions()),
+        }
+        let completed = cqes.len();
+        for cqe in cqes.drain(..) {
+            self.resolve(cqe);
+        }
+        self.cqes = cqes;
 
-            // Resolve the [Completion] and handle the result.
-            use CompletionStatus::*;
-            match state.resolve(cqe) {
-                Armed => {
-                    // Do nothing we are already armed, and we don't want to remove the state yet
-                    // since this is likely a multi-shot event and we are awaiting new events to be
-                    // generated.
-                }
-                Rearm => {
-                    // We have a multi-shot requesting that we re-arm it, so lets go ahead and do
-                    // that so that we continue to get new updates.
-                    let entry = state.as_entry().user_data(user_data);
-                    unsafe {
-                        if sq.push(&entry).is_err() {
-                            self.backlog.push_back(entry);
-                        }
-                    }
-                }
-                Finalized => {
-                    // Our event is handled and done, go ahead and clean up our state entry so its
-                    // slot can be reused.
-                    self.state.remove(user_data as usize);
-                }
+        // Finally fire off any deadlines that have expired in our timer wheel.
+        self.fire_wheel();
+        Ok(completed)
+    }
+}
+
+impl Drop for UringDriver {
+    fn drop(&mut self) {
+        // The ring outlives us for as long as a [Mailbox] holds on to it, make sure nothing else
+        // is posted to it since no one will pick it up.
+        if let Some(ref mailbox) = self.mailbox {
+            mailbox.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::fd::AsRawFd,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use futures::task::{waker, ArcWake};
+    use io_uring::{cqueue, opcode, squeue, types};
+    use nix::libc;
+
+    use super::*;
+
+    /// Polls a socket that never becomes readable, flagging when its state is dropped.
+    struct NeverReady {
+        fd: RawFd,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Completion for NeverReady {
+        fn resolve(&self, _: cqueue::Entry) -> CompletionStatus {
+            CompletionStatus::Finalized
+        }
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::PollAdd::new(types::Fd(self.fd), libc::POLLIN as _).build()
+        }
+    }
+
+    impl Drop for NeverReady {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_deregister_cancelling() {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut driver = UringDriver::new(8).unwrap();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let index = driver
+            .register(NeverReady {
+                fd: sock.as_raw_fd(),
+                dropped: dropped.clone(),
+            })
+            .unwrap();
+        driver.run().unwrap();
+
+        // The state is held on to until the kernel confirms the cancellation.
+        driver.deregister(index);
+        assert!(!dropped.load(Ordering::SeqCst));
+        assert_eq!(driver.cancelling(), 1);
+
+        while driver.cancelling() > 0 {
+            driver.run().unwrap();
+        }
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_stale_completions() {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut driver = UringDriver::new(8).unwrap();
+        let old = driver.register(Cancel::new(u64::MAX, true)).unwrap();
+        while driver.state.contains(UserData::from(old).index()) {
+            driver.run().unwrap();
+        }
+
+        // The new event reuses the state index, but not the user_data of the old one.
+        let dropped = Arc::new(AtomicBool::new(false));
+        let new = driver
+            .register(NeverReady {
+                fd: sock.as_raw_fd(),
+                dropped: dropped.clone(),
+            })
+            .unwrap();
+        assert_eq!(UserData::from(new).index(), UserData::from(old).index());
+        assert_ne!(new, old);
+
+        // Neither deregistering the old event, nor a late completion for it affects the new one.
+        driver.deregister(old);
+        assert_eq!(driver.cancelling(), 0);
+        driver.enqueue(&[opcode::Nop::new().build().user_data(old)]);
+        while driver.stale_completions() == 0 {
+            driver.run().unwrap();
+        }
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        driver.deregister(new);
+        while driver.cancelling() > 0 {
+            driver.run().unwrap();
+        }
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    /// Polls a socket that never becomes readable, recording the result it is resolved with.
+    struct PollResult {
+        fd: RawFd,
+        result: Arc<Mutex<Option<i32>>>,
+    }
+
+    impl Completion for PollResult {
+        fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+            *self.result.lock().unwrap() = Some(value.result());
+            CompletionStatus::Finalized
+        }
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::PollAdd::new(types::Fd(self.fd), libc::POLLIN as _).build()
+        }
+    }
+
+    #[test]
+    fn test_timeout_results() {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut driver = UringDriver::new(8).unwrap();
+        let register = |driver: &mut UringDriver, timeout| {
+            let result = Arc::new(Mutex::new(None));
+            let op = PollResult {
+                fd: sock.as_raw_fd(),
+                result: result.clone(),
             };
+            (driver.register_with_timeout(op, timeout).unwrap(), result)
+        };
+        let wait = |driver: &mut UringDriver, result: &Arc<Mutex<Option<i32>>>| loop {
+            if let Some(result) = *result.lock().unwrap() {
+                return result;
+            }
+            driver.run().unwrap();
+        };
+
+        // Only a fired timeout is reported as such, any other cancellation is left as is.
+        let (_, result) = register(&mut driver, Duration::from_millis(10));
+        assert_eq!(wait(&mut driver, &result), -libc::ETIMEDOUT);
+
+        let (user_data, result) = register(&mut driver, Duration::from_secs(60));
+        driver.run_ready().unwrap();
+        driver.cancel(user_data);
+        assert_eq!(wait(&mut driver, &result), -libc::ECANCELED);
+
+        let (user_data, result) = register(&mut driver, Duration::from_secs(60));
+        driver.run_ready().unwrap();
+        driver.expire(user_data);
+        assert_eq!(wait(&mut driver, &result), -libc::ETIMEDOUT);
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut driver = UringDriver::new(8).unwrap();
+        driver.caps = Capabilities::default();
+
+        // Without ext-arg an idle ring is woken up by our own timeout event.
+        let start = Instant::now();
+        driver.run().unwrap();
+        driver.run().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Without cancel-all events are canceled by user_data alone.
+        let dropped = Arc::new(AtomicBool::new(false));
+        let index = driver
+            .register(NeverReady {
+                fd: sock.as_raw_fd(),
+                dropped: dropped.clone(),
+            })
+            .unwrap();
+        driver.run().unwrap();
+        driver.deregister(index);
+        while driver.cancelling() > 0 {
+            driver.run().unwrap();
         }
-        Ok(())
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_backpressure() {
+        struct Flag(AtomicBool);
+
+        impl ArcWake for Flag {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut driver = UringBuilder::new().entries(2).backlog(1).build().unwrap();
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = waker(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        // Fill up the submission queue, after which events end up in the backlog.
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            assert!(driver.poll_ready(&mut cx).is_ready());
+            ids.push(
+                driver
+                    .register(NeverReady {
+                        fd: sock.as_raw_fd(),
+                        dropped: dropped.clone(),
+                    })
+                    .unwrap(),
+            );
+        }
+        assert_eq!(driver.backlog_len(), 1);
+        assert!(driver.poll_ready(&mut cx).is_pending());
+
+        // Registering beyond the limit is refused outright.
+        let err = driver.register(Cancel::new(u64::MAX, true)).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EAGAIN));
+        let err = driver
+            .register_chain([(Cancel::new(u64::MAX, true), Link::Soft)])
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EAGAIN));
+        assert_eq!(driver.backlog_len(), 1);
+
+        driver.run().unwrap();
+        assert_eq!(driver.backlog_len(), 0);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(driver.poll_ready(&mut cx).is_ready());
+
+        // Bookkeeping may go beyond the limit, but is held back once the reserve is used up too.
+        for _ in 0..BACKLOG_RESERVE + 2 {
+            driver.enqueue(&[opcode::Nop::new().build().user_data(u64::MAX)]);
+        }
+        for id in ids {
+            driver.deregister(id);
+        }
+        assert_eq!(driver.deferred.len(), 3);
+        while driver.cancelling() > 0 {
+            driver.run().unwrap();
+        }
+        assert!(driver.deferred.is_empty());
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_cq_overflow() {
+        let mut driver = UringBuilder::new().entries(2).build().unwrap();
+        let cq_entries = driver.uring().unwrap().params().cq_entries() as usize;
+
+        // Complete twice as many events as fit in the completion queue before reaping any.
+        for _ in 0..cq_entries {
+            driver.enqueue(&[opcode::Nop::new().build().user_data(u64::MAX)]);
+            driver.enqueue(&[opcode::Nop::new().build().user_data(u64::MAX)]);
+            driver.uring().unwrap().submit().unwrap();
+        }
+        assert_eq!(driver.cq_overflows(), 0);
+        while driver.stale_completions() < 2 * cq_entries as u64 {
+            driver.run().unwrap();
+        }
+        assert!(driver.cq_overflows() > 0);
+        assert_eq!(driver.dropped_completions(), 0);
+    }
+
+    #[test]
+    fn test_run_ready() {
+        let mut driver = UringDriver::new(8).unwrap();
+        let eventfd = driver.register_eventfd().unwrap();
+        let readable = |timeout: i32| {
+            let mut pollfd = libc::pollfd {
+                fd: eventfd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            unsafe { libc::poll(&mut pollfd, 1, timeout) == 1 }
+        };
+
+        // Nothing is in flight, so there is nothing to handle.
+        assert_eq!(driver.run_ready().unwrap(), 0);
+        assert!(!readable(0));
+
+        // The eventfd is signaled once the event completes, and reset once it is handled.
+        driver.enqueue(&[opcode::Nop::new().build().user_data(u64::MAX)]);
+        assert_eq!(driver.run_ready().unwrap(), 1);
+        let timeout = WheelTimeout::new(Duration::from_millis(10));
+        driver.register(timeout).unwrap();
+        assert_eq!(driver.run_ready().unwrap(), 0);
+        assert!(readable(1000));
+        assert_eq!(driver.run_ready().unwrap(), 1);
+        assert!(!readable(0));
     }
 }
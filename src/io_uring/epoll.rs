@@ -0,0 +1,679 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    time::{Duration, Instant},
+};
+
+use io_uring::{cqueue, opcode, squeue};
+use nix::libc;
+
+/// The submission flags we care about, which aren't exposed by the version of [io_uring] in use
+/// in a form we can read back out of an entry.
+const IOSQE_IO_LINK: u8 = 1 << 2;
+const IOSQE_IO_HARDLINK: u8 = 1 << 3;
+const IOSQE_BUFFER_SELECT: u8 = 1 << 5;
+
+/// The `IORING_ASYNC_CANCEL_ALL` cancel flag.
+const IORING_ASYNC_CANCEL_ALL: u32 = 1 << 0;
+
+/// The maximum number of readiness events to pick up per call to `epoll_wait`.
+const MAX_EVENTS: usize = 256;
+
+/// The raw layout of a submission queue entry, see `struct io_uring_sqe`. The entries built via
+/// [io_uring::opcode] are opaque, so the [Epoll] backend reads them back out through this.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    file_index: u32,
+    addr3: u64,
+    pad: u64,
+}
+
+impl Sqe {
+    fn is_linked(&self) -> bool {
+        self.flags & (IOSQE_IO_LINK | IOSQE_IO_HARDLINK) != 0
+    }
+
+    /// Returns the duration of a timeout entry.
+    fn timeout(&self) -> Duration {
+        // SAFETY: Timeout entries point at a `__kernel_timespec` kept alive by their completion.
+        let ts = unsafe { &*(self.addr as *const KernelTimespec) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    }
+}
+
+impl From<&squeue::Entry> for Sqe {
+    fn from(entry: &squeue::Entry) -> Self {
+        // SAFETY: A [squeue::Entry] is a `repr(C)` wrapper around `struct io_uring_sqe`.
+        unsafe { mem::transmute::<squeue::Entry, Sqe>(entry.clone()) }
+    }
+}
+
+/// The raw layout of a completion queue entry, see `struct io_uring_cqe`.
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+fn cqe(user_data: u64, res: i32) -> cqueue::Entry {
+    let cqe = Cqe {
+        user_data,
+        res,
+        flags: 0,
+    };
+    // SAFETY: A [cqueue::Entry] is a `repr(C)` wrapper around `struct io_uring_cqe`.
+    unsafe { mem::transmute::<Cqe, cqueue::Entry>(cqe) }
+}
+
+/// Returns a copy of the given completion event with its result replaced.
+pub(super) fn with_result(entry: &cqueue::Entry, res: i32) -> cqueue::Entry {
+    let cqe = Cqe {
+        user_data: entry.user_data(),
+        res,
+        flags: entry.flags(),
+    };
+    // SAFETY: A [cqueue::Entry] is a `repr(C)` wrapper around `struct io_uring_cqe`.
+    unsafe { mem::transmute::<Cqe, cqueue::Entry>(cqe) }
+}
+
+#[repr(C)]
+struct KernelTimespec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+/// The outcome of attempting to execute an entry.
+enum Attempt {
+    /// The entry completed with the given result.
+    Done(i32),
+    /// The entry has to wait for the file descriptor to become ready for the given events.
+    Ready(RawFd, u32),
+    /// The entry has to wait for the deadline to pass.
+    Sleep(Instant),
+}
+
+/// An entry waiting on readiness or a deadline, along with the rest of its chain of linked
+/// entries.
+struct Waiting {
+    sqe: Sqe,
+    attempt: Attempt,
+    link: Option<(Sqe, Instant)>,
+    rest: VecDeque<Sqe>,
+}
+
+/// A readiness based backend for the [super::UringDriver], for hosts where io_uring is
+/// unavailable. This executes the entries that would otherwise be submitted to the ring itself,
+/// using nonblocking syscalls once `epoll` reports the file descriptor involved as ready, and
+/// hands back completions in the same form the ring would.
+///
+/// Only the subset of operations used by this crate is supported, anything relying on resources
+/// registered with a ring, such as buffer selection or direct descriptors, completes with an
+/// error, and any other operation such as `MkDirAt` completes with `EINVAL`. File descriptors are
+/// only switched to nonblocking mode for the duration of the syscalls issued against them, since
+/// the flag is shared with every other user of the file.
+pub(crate) struct Epoll {
+    epfd: OwnedFd,
+    waiting: HashMap<u64, Waiting>,
+    fds: HashMap<RawFd, Vec<u64>>,
+    timers: BTreeSet<(Instant, u64)>,
+    completed: VecDeque<cqueue::Entry>,
+}
+
+impl Epoll {
+    pub(crate) fn new() -> io::Result<Epoll> {
+        // SAFETY: We check the result before taking ownership of the descriptor.
+        let epfd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Epoll {
+            epfd: unsafe { OwnedFd::from_raw_fd(epfd) },
+            waiting: HashMap::new(),
+            fds: HashMap::new(),
+            timers: BTreeSet::new(),
+            completed: VecDeque::new(),
+        })
+    }
+
+    /// Submit the given entries, which may contain chains of linked entries, executing them right
+    /// away where possible.
+    pub(crate) fn submit(&mut self, entries: &[squeue::Entry]) {
+        let mut chain = VecDeque::new();
+        for entry in entries {
+            let sqe = Sqe::from(entry);
+            chain.push_back(sqe);
+            if !sqe.is_linked() {
+                self.start(mem::take(&mut chain));
+            }
+        }
+        self.start(chain);
+    }
+
+    /// Returns whether or not there are completions ready to be reaped.
+    pub(crate) fn has_completions(&self) -> bool {
+        !self.completed.is_empty()
+    }
+
+    /// Take all completions that are ready.
+    pub(crate) fn completions(&mut self) -> impl Iterator<Item = cqueue::Entry> + '_ {
+        self.completed.drain(..)
+    }
+
+    /// Wait for up to `timeout` for file descriptors to become ready or deadlines to pass,
+    /// executing the entries waiting on them. This doesn't block if completions are ready already.
+    pub(crate) fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+        self.fire_timers();
+
+        let now = Instant::now();
+        let timeout = match self.timers.first() {
+            _ if self.has_completions() => Duration::ZERO,
+            Some((deadline, _)) => timeout.min(deadline.saturating_duration_since(now)),
+            None => timeout,
+        };
+
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        // Round up so that we don't wake up just before the next deadline.
+        let millis = timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32;
+        let count = unsafe {
+            libc::epoll_wait(
+                self.epfd.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as i32,
+                millis,
+            )
+        };
+        if count < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINTR) {
+                return Err(err);
+            }
+        }
+
+        for event in events.iter().take(count.max(0) as usize) {
+            self.ready(event.u64 as RawFd, event.events);
+        }
+        self.fire_timers();
+        Ok(())
+    }
+
+    /// Start the first entry of the given chain, along with its linked timeout if it has one.
+    fn start(&mut self, mut chain: VecDeque<Sqe>) {
+        let sqe = match chain.pop_front() {
+            Some(sqe) => sqe,
+            None => return,
+        };
+        let link = match chain.front() {
+            Some(next) if next.opcode == opcode::LinkTimeout::CODE => chain
+                .pop_front()
+                .map(|link| (link, Instant::now() + link.timeout())),
+            _ => None,
+        };
+
+        if sqe.opcode == opcode::AsyncCancel::CODE {
+            let res = self.cancel(sqe.addr, sqe.op_flags & IORING_ASYNC_CANCEL_ALL != 0);
+            return self.finish(sqe, res, link, chain);
+        }
+
+        match attempt(&sqe, false) {
+            Attempt::Done(res) => self.finish(sqe, res, link, chain),
+            attempt => self.wait_on(Waiting {
+                sqe,
+                attempt,
+                link,
+                rest: chain,
+            }),
+        }
+    }
+
+    /// Park the given entry until it is ready to be attempted again.
+    fn wait_on(&mut self, waiting: Waiting) {
+        let user_data = waiting.sqe.user_data;
+        match waiting.attempt {
+            Attempt::Ready(fd, _) => {
+                self.fds.entry(fd).or_default().push(user_data);
+            }
+            Attempt::Sleep(deadline) => {
+                self.timers.insert((deadline, user_data));
+            }
+            Attempt::Done(_) => unreachable!("completed entries don't wait"),
+        }
+        if let Some((_, deadline)) = waiting.link {
+            self.timers.insert((deadline, user_data));
+        }
+
+        let fd = match waiting.attempt {
+            Attempt::Ready(fd, _) => Some(fd),
+            _ => None,
+        };
+        self.waiting.insert(user_data, waiting);
+        if let Some(fd) = fd {
+            if let Err(err) = self.update_interest(fd) {
+                let res = -err.raw_os_error().unwrap_or(libc::EINVAL);
+                self.abort(user_data, res);
+            }
+        }
+    }
+
+    /// Complete the given entry, and move on to the rest of its chain. Unless the chain is hard
+    /// linked, the rest of the chain is canceled if the entry failed.
+    fn finish(&mut self, sqe: Sqe, res: i32, link: Option<(Sqe, Instant)>, rest: VecDeque<Sqe>) {
+        self.completed.push_back(cqe(sqe.user_data, res));
+        if let Some((link, _)) = link {
+            self.completed
+                .push_back(cqe(link.user_data, -libc::ECANCELED));
+        }
+
+        if res < 0 && sqe.flags & IOSQE_IO_HARDLINK == 0 {
+            for sqe in rest {
+                self.completed
+                    .push_back(cqe(sqe.user_data, -libc::ECANCELED));
+            }
+        } else {
+            self.start(rest);
+        }
+    }
+
+    /// Stop waiting on the given entry, and complete it with the given result.
+    fn abort(&mut self, user_data: u64, res: i32) -> bool {
+        let waiting = match self.remove(user_data) {
+            Some(waiting) => waiting,
+            None => return false,
+        };
+        self.finish(waiting.sqe, res, waiting.link, waiting.rest);
+        true
+    }
+
+    /// Stop waiting on the given entry, returning it if it was waiting.
+    fn remove(&mut self, user_data: u64) -> Option<Waiting> {
+        let waiting = self.waiting.remove(&user_data)?;
+        match waiting.attempt {
+            Attempt::Ready(fd, _) => {
+                if let Some(waiters) = self.fds.get_mut(&fd) {
+                    waiters.retain(|waiter| *waiter != user_data);
+                }
+                let _ = self.update_interest(fd);
+            }
+            Attempt::Sleep(deadline) => {
+                self.timers.remove(&(deadline, user_data));
+            }
+            Attempt::Done(_) => (),
+        }
+        if let Some((_, deadline)) = waiting.link {
+            self.timers.remove(&(deadline, user_data));
+        }
+        Some(waiting)
+    }
+
+    /// Cancel the entry waiting with the given user_data, returning the result for the cancel
+    /// entry itself.
+    fn cancel(&mut self, user_data: u64, all: bool) -> i32 {
+        match self.abort(user_data, -libc::ECANCELED) {
+            true if all => 1,
+            true => 0,
+            false => -libc::ENOENT,
+        }
+    }
+
+    /// Attempt all entries waiting on the given file descriptor again, now that it is ready.
+    fn ready(&mut self, fd: RawFd, events: u32) {
+        let waiters = match self.fds.get(&fd) {
+            Some(waiters) => waiters.clone(),
+            None => return,
+        };
+
+        let error = (libc::EPOLLERR | libc::EPOLLHUP) as u32;
+        for user_data in waiters {
+            let sqe = match self.waiting.get(&user_data) {
+                Some(Waiting {
+                    sqe,
+                    attempt: Attempt::Ready(_, interest),
+                    ..
+                }) if events & (interest | error) != 0 => *sqe,
+                _ => continue,
+            };
+            if let Attempt::Done(res) = attempt(&sqe, true) {
+                self.abort(user_data, res);
+            }
+        }
+    }
+
+    /// Complete the entries whose deadline has passed, timeouts complete with `-ETIME` while
+    /// entries with an expired linked timeout are canceled.
+    fn fire_timers(&mut self) {
+        let now = Instant::now();
+        while let Some(&(deadline, user_data)) = self.timers.first() {
+            if deadline > now {
+                break;
+            }
+            self.timers.pop_first();
+
+            let waiting = match self.waiting.get(&user_data) {
+                Some(waiting) => waiting,
+                None => continue,
+            };
+            if matches!(waiting.attempt, Attempt::Sleep(sleep) if sleep == deadline) {
+                self.abort(user_data, -libc::ETIME);
+                continue;
+            }
+
+            let link = waiting.link.map(|(link, _)| link.user_data);
+            if let Some(waiting) = self.remove(user_data) {
+                self.finish(waiting.sqe, -libc::ECANCELED, None, waiting.rest);
+            }
+            if let Some(link) = link {
+                self.completed.push_back(cqe(link, -libc::ETIME));
+            }
+        }
+    }
+
+    /// Update the interest registered for the given file descriptor to cover all entries waiting
+    /// on it, removing it altogether once nothing is waiting.
+    fn update_interest(&mut self, fd: RawFd) -> io::Result<()> {
+        let interest = self
+            .fds
+            .get(&fd)
+            .into_iter()
+            .flatten()
+            .filter_map(|user_data| match self.waiting.get(user_data) {
+                Some(Waiting {
+                    attempt: Attempt::Ready(_, interest),
+                    ..
+                }) => Some(*interest),
+                _ => None,
+            })
+            .fold(0, |acc, interest| acc | interest);
+
+        let epfd = self.epfd.as_raw_fd();
+        if interest == 0 {
+            self.fds.remove(&fd);
+            unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_DEL, fd, std::ptr::null_mut()) };
+            return Ok(());
+        }
+
+        let mut event = libc::epoll_event {
+            events: interest,
+            u64: fd as u64,
+        };
+        // The descriptor may have been closed and reused since we last saw it, so fallback from
+        // modifying to adding it and the other way around.
+        for op in [
+            libc::EPOLL_CTL_MOD,
+            libc::EPOLL_CTL_ADD,
+            libc::EPOLL_CTL_MOD,
+        ] {
+            if unsafe { libc::epoll_ctl(epfd, op, fd, &mut event) } == 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if !matches!(err.raw_os_error(), Some(libc::ENOENT | libc::EEXIST)) {
+                return Err(err);
+            }
+        }
+        Err(io::Error::from_raw_os_error(libc::EINVAL))
+    }
+}
+
+/// Attempt to execute the given entry without blocking, `retry` is set when the entry is attempted
+/// again after waiting on readiness.
+fn attempt(sqe: &Sqe, retry: bool) -> Attempt {
+    let fd = sqe.fd;
+    let ptr = sqe.addr as *mut libc::c_void;
+    let len = sqe.len as usize;
+    let flags = sqe.op_flags as i32 | libc::MSG_DONTWAIT;
+    let read = libc::EPOLLIN as u32;
+    let write = libc::EPOLLOUT as u32;
+
+    // SAFETY: The pointers in the entry are kept alive by its completion, in the same way they
+    // would be when submitted to the ring.
+    unsafe {
+        match sqe.opcode {
+            opcode::Nop::CODE => Attempt::Done(0),
+            opcode::Timeout::CODE => Attempt::Sleep(Instant::now() + sqe.timeout()),
+            opcode::Accept::CODE if sqe.file_index != 0 => Attempt::Done(-libc::ENFILE),
+            opcode::Accept::CODE => nonblocking(fd, || {
+                let addr = sqe.addr as *mut libc::sockaddr;
+                let len = sqe.off as *mut libc::socklen_t;
+                result(
+                    libc::accept4(fd, addr, len, sqe.op_flags as i32) as isize,
+                    fd,
+                    read,
+                )
+            }),
+            opcode::Connect::CODE if retry => {
+                let mut err: libc::c_int = 0;
+                let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+                let ret = libc::getsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_ERROR,
+                    &mut err as *mut _ as *mut libc::c_void,
+                    &mut len,
+                );
+                match (ret, err) {
+                    (0, 0) => Attempt::Done(0),
+                    (0, err) => Attempt::Done(-err),
+                    _ => result(ret as isize, fd, write),
+                }
+            }
+            opcode::Connect::CODE => nonblocking(fd, || {
+                let addr = sqe.addr as *const libc::sockaddr;
+                let ret = libc::connect(fd, addr, sqe.off as libc::socklen_t);
+                if ret < 0 && last_errno() == libc::EINPROGRESS {
+                    return Attempt::Ready(fd, write);
+                }
+                result(ret as isize, fd, write)
+            }),
+            opcode::Recv::CODE | opcode::RecvMsg::CODE if sqe.flags & IOSQE_BUFFER_SELECT != 0 => {
+                Attempt::Done(-libc::EOPNOTSUPP)
+            }
+            opcode::Recv::CODE => result(libc::recv(fd, ptr, len, flags), fd, read),
+            opcode::RecvMsg::CODE => {
+                result(libc::recvmsg(fd, ptr as *mut libc::msghdr, flags), fd, read)
+            }
+            opcode::Send::CODE | opcode::SendZc::CODE if sqe.off != 0 => {
+                let addr = sqe.off as *const libc::sockaddr;
+                let addr_len = sqe.file_index as u16 as libc::socklen_t;
+                let ret = libc::sendto(fd, ptr, len, flags, addr, addr_len);
+                result(ret, fd, write)
+            }
+            opcode::Send::CODE | opcode::SendZc::CODE => {
+                result(libc::send(fd, ptr, len, flags), fd, write)
+            }
+            opcode::SendMsg::CODE | opcode::SendMsgZc::CODE => result(
+                libc::sendmsg(fd, ptr as *const libc::msghdr, flags),
+                fd,
+                write,
+            ),
+            opcode::Read::CODE | opcode::ReadFixed::CODE if sqe.off == u64::MAX => {
+                nonblocking(fd, || result(libc::read(fd, ptr, len), fd, read))
+            }
+            opcode::Read::CODE | opcode::ReadFixed::CODE => {
+                result(libc::pread(fd, ptr, len, sqe.off as libc::off_t), fd, read)
+            }
+            opcode::Write::CODE | opcode::WriteFixed::CODE if sqe.off == u64::MAX => {
+                nonblocking(fd, || result(libc::write(fd, ptr, len), fd, write))
+            }
+            opcode::Write::CODE | opcode::WriteFixed::CODE => result(
+                libc::pwrite(fd, ptr, len, sqe.off as libc::off_t),
+                fd,
+                write,
+            ),
+            opcode::PollAdd::CODE => {
+                // Multi-shot polls are treated as single-shot, their completions re-arm them.
+                let mut pollfd = libc::pollfd {
+                    fd,
+                    events: sqe.op_flags as i16,
+                    revents: 0,
+                };
+                match libc::poll(&mut pollfd, 1, 0) {
+                    ret if ret < 0 => Attempt::Done(-last_errno()),
+                    0 => Attempt::Ready(fd, sqe.op_flags),
+                    _ => Attempt::Done(pollfd.revents as u16 as i32),
+                }
+            }
+            _ => Attempt::Done(-libc::EINVAL),
+        }
+    }
+}
+
+/// Map the return value of a syscall to an [Attempt], waiting for the given events on the file
+/// descriptor if it would block.
+fn result(ret: isize, fd: RawFd, events: u32) -> Attempt {
+    if ret >= 0 {
+        return Attempt::Done(ret as i32);
+    }
+    match last_errno() {
+        libc::EAGAIN | libc::EINTR => Attempt::Ready(fd, events),
+        errno => Attempt::Done(-errno),
+    }
+}
+
+fn last_errno() -> i32 {
+    io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EINVAL)
+}
+
+/// Run the given syscall with the file descriptor in nonblocking mode, restoring its flags
+/// afterwards. The flags belong to the open file rather than the descriptor, so leaving them set
+/// would change the behaviour of every other user of the file as well.
+fn nonblocking(fd: RawFd, f: impl FnOnce() -> Attempt) -> Attempt {
+    // SAFETY: These calls only touch the file status flags of the descriptor.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 {
+            return Attempt::Done(-last_errno());
+        }
+        if flags & libc::O_NONBLOCK != 0 {
+            return f();
+        }
+        if libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Attempt::Done(-last_errno());
+        }
+        let attempt = f();
+        libc::fcntl(fd, libc::F_SETFL, flags);
+        attempt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        os::fd::AsRawFd,
+    };
+
+    use io_uring::types;
+
+    use super::*;
+
+    fn reap(epoll: &mut Epoll, count: usize) -> Vec<(u64, i32)> {
+        let mut results = Vec::new();
+        while results.len() < count {
+            epoll.wait(Duration::from_millis(100)).unwrap();
+            results.extend(
+                epoll
+                    .completions()
+                    .map(|cqe| (cqe.user_data(), cqe.result())),
+            );
+        }
+        results
+    }
+
+    #[test]
+    fn test_epoll_recv() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut epoll = Epoll::new().unwrap();
+        let mut buf = [0u8; 16];
+        let recv = opcode::Recv::new(types::Fd(server.as_raw_fd()), buf.as_mut_ptr(), 16);
+        epoll.submit(&[recv.build().user_data(1)]);
+        assert!(!epoll.has_completions());
+
+        client.write_all(b"hello").unwrap();
+        assert_eq!(reap(&mut epoll, 1), vec![(1, 5)]);
+        assert_eq!(&buf[..5], b"hello");
+
+        let send = opcode::Send::new(types::Fd(server.as_raw_fd()), buf.as_ptr(), 5);
+        epoll.submit(&[send.build().user_data(2)]);
+        assert_eq!(reap(&mut epoll, 1), vec![(2, 5)]);
+        let mut out = [0u8; 5];
+        client.read_exact(&mut out).unwrap();
+        assert_eq!(&out, b"hello");
+    }
+
+    #[test]
+    fn test_epoll_timeouts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut epoll = Epoll::new().unwrap();
+        let mut buf = [0u8; 16];
+        let timespec = types::Timespec::from(Duration::from_millis(10));
+        let recv = opcode::Recv::new(types::Fd(server.as_raw_fd()), buf.as_mut_ptr(), 16);
+        epoll.submit(&[
+            recv.build().user_data(1).flags(squeue::Flags::IO_LINK),
+            opcode::LinkTimeout::new(&timespec).build().user_data(2),
+        ]);
+        let mut results = reap(&mut epoll, 2);
+        results.sort();
+        assert_eq!(results, vec![(1, -libc::ECANCELED), (2, -libc::ETIME)]);
+
+        // Canceling a waiting entry completes it right away.
+        epoll.submit(&[opcode::Timeout::new(&timespec).build().user_data(3)]);
+        epoll.submit(&[opcode::AsyncCancel::new(3).build().user_data(4)]);
+        assert_eq!(reap(&mut epoll, 2), vec![(3, -libc::ECANCELED), (4, 0)]);
+        assert!(epoll.timers.is_empty());
+        assert!(epoll.fds.is_empty());
+    }
+
+    #[test]
+    fn test_epoll_file_flags() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (rx, tx) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        let flags = || unsafe { libc::fcntl(rx.as_raw_fd(), libc::F_GETFL) };
+
+        // Reading from the pipe must not leave it in nonblocking mode for anyone else using it.
+        let mut epoll = Epoll::new().unwrap();
+        let mut buf = [0u8; 16];
+        let read = opcode::Read::new(types::Fd(rx.as_raw_fd()), buf.as_mut_ptr(), 16)
+            .offset(u64::MAX)
+            .build()
+            .user_data(1);
+        epoll.submit(&[read]);
+        assert_eq!(flags() & libc::O_NONBLOCK, 0);
+
+        assert_eq!(
+            unsafe { libc::write(tx.as_raw_fd(), b"hi".as_ptr() as _, 2) },
+            2
+        );
+        assert_eq!(reap(&mut epoll, 1), vec![(1, 2)]);
+        assert_eq!(flags() & libc::O_NONBLOCK, 0);
+
+        // Operations this backend doesn't know about fail outright.
+        let mkdir = opcode::MkDirAt::new(types::Fd(libc::AT_FDCWD), c"dir".as_ptr());
+        epoll.submit(&[mkdir.build().user_data(2)]);
+        assert_eq!(reap(&mut epoll, 1), vec![(2, -libc::EINVAL)]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    alloc::{self, Layout},
+    fmt, io,
+    ops::{Deref, DerefMut},
+    slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, ThreadId},
+};
+
+use nix::libc;
+
+use crate::context;
+
+/// The alignment of the memory backing a pool, so that each buffer pins as few pages as possible.
+const PAGE_SIZE: usize = 4096;
+
+/// The largest number of buffers the kernel accepts in a single registration.
+const MAX_BUFFERS: u16 = 1 << 14;
+
+struct Inner {
+    buf_size: usize,
+    count: u16,
+    bufs: *mut u8,
+    layout: Layout,
+    free: Mutex<Vec<u16>>,
+    owner: ThreadId,
+    registered: AtomicBool,
+}
+
+// SAFETY: The raw pointer is an owned allocation, and each buffer is only ever handed out to a
+// single [FixedBuf] at a time via the mutex guarded free list.
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl Inner {
+    fn lock_free(&self) -> MutexGuard<'_, Vec<u16>> {
+        self.free
+            .lock()
+            .expect("failed to lock fixed buffer pool: poisoned")
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // SAFETY: The allocation was made with this layout in [FixedBufPool::alloc], and the
+        // driver holds on to a [FixedBufPool] for as long as it is registered with the kernel.
+        unsafe { alloc::dealloc(self.bufs, self.layout) }
+    }
+}
+
+/// A [FixedBufPool] is an arena of equally sized buffers registered with the ring of a
+/// [super::UringDriver]. The kernel pins the pages of registered buffers once up front, rather
+/// than on every event, which makes them a good fit for services that push a lot of data through
+/// a fixed set of buffers, like proxies.
+///
+/// Buffers are checked out of the pool as [FixedBuf]s, which are returned to the pool once
+/// dropped. Each ring can only have a single pool registered at a time, and a pool belongs to the
+/// ring of the thread that created it. Events using a [FixedBuf] submitted to any other ring, or
+/// after the pool has been unregistered, fall back to treating it as a regular buffer.
+///
+/// A pool can be registered with each worker of the runtime via
+/// [super::UringBuilder::fixed_buffers] and retrieved with [FixedBufPool::current].
+///
+/// # Examples
+///
+/// ```no_run
+/// use libuio::{io_uring::FixedBufPool, net::TcpStream};
+///
+/// async fn echo(mut conn: TcpStream) -> std::io::Result<()> {
+///     let pool = FixedBufPool::new(16, 16 * 1024)?;
+///     let mut buf = pool.checkout().expect("pool is empty");
+///     loop {
+///         let (read, returned) = conn.read_fixed(buf).await;
+///         if read? == 0 {
+///             return Ok(());
+///         }
+///         let (written, returned) = conn.write_fixed(returned).await;
+///         written?;
+///         buf = returned;
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FixedBufPool {
+    inner: Arc<Inner>,
+}
+
+impl FixedBufPool {
+    /// Create a new [FixedBufPool] of `count` buffers of `buf_size` bytes each, registered with
+    /// the [super::UringDriver] of the current thread. See [super::UringDriver::register_buffers].
+    ///
+    /// # Errors
+    ///
+    /// This method will error if `count` is zero or larger than `16384`, if the buffer memory
+    /// couldn't be allocated, if the ring already has buffers registered, or if the kernel refuses
+    /// to pin the memory, for instance due to `RLIMIT_MEMLOCK`.
+    pub fn new(count: u16, buf_size: usize) -> io::Result<FixedBufPool> {
+        context::uring().register_buffers(count, buf_size)
+    }
+
+    /// Returns the [FixedBufPool] registered with the [super::UringDriver] of the current thread,
+    /// if there is one.
+    pub fn current() -> Option<FixedBufPool> {
+        context::uring().fixed_buffers()
+    }
+
+    /// Allocate a new [FixedBufPool] with all of its buffers available, this needs to be
+    /// registered with the kernel before use.
+    pub(crate) fn alloc(count: u16, buf_size: usize) -> io::Result<FixedBufPool> {
+        if count == 0 || count > MAX_BUFFERS || buf_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fixed buffer pools must hold between 1 and 16384 buffers with a non-zero size",
+            ));
+        }
+
+        let layout = (count as usize)
+            .checked_mul(buf_size)
+            .and_then(|size| Layout::from_size_align(size, PAGE_SIZE).ok())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        // SAFETY: The layout has a non-zero size. The memory is zeroed so that the full capacity
+        // of each buffer is always initialized, see [FixedBuf::set_len].
+        let bufs = unsafe { alloc::alloc_zeroed(layout) };
+        if bufs.is_null() {
+            return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+        }
+
+        let inner = Inner {
+            buf_size,
+            count,
+            bufs,
+            layout,
+            free: Mutex::new((0..count).rev().collect()),
+            owner: thread::current().id(),
+            registered: AtomicBool::new(false),
+        };
+        Ok(FixedBufPool {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Check out a free buffer from this pool, or `None` if all buffers are in use. The buffer is
+    /// returned to the pool once the [FixedBuf] is dropped.
+    pub fn checkout(&self) -> Option<FixedBuf> {
+        let index = self.inner.lock_free().pop()?;
+        Some(FixedBuf {
+            pool: self.clone(),
+            index,
+            len: 0,
+        })
+    }
+
+    /// Returns the number of buffers that are free to be checked out.
+    pub fn available(&self) -> usize {
+        self.inner.lock_free().len()
+    }
+
+    /// Returns the size of each buffer in this pool.
+    pub fn buf_size(&self) -> usize {
+        self.inner.buf_size
+    }
+
+    /// Returns the [libc::iovec]s describing each buffer, for registration with the kernel.
+    pub(crate) fn iovecs(&self) -> Vec<libc::iovec> {
+        (0..self.inner.count)
+            .map(|index| libc::iovec {
+                iov_base: self.buf_ptr(index) as _,
+                iov_len: self.inner.buf_size,
+            })
+            .collect()
+    }
+
+    /// Mark whether or not this pool is currently registered with the kernel.
+    pub(crate) fn set_registered(&self, registered: bool) {
+        self.inner.registered.store(registered, Ordering::Release);
+    }
+
+    /// Returns the index of the given buffer if it can be used as a registered buffer by an event
+    /// submitted from the current thread, that is if the current thread owns the ring this pool
+    /// is registered with.
+    pub(crate) fn fixed_index(&self, index: u16) -> Option<u16> {
+        let inner = &self.inner;
+        let usable =
+            inner.owner == thread::current().id() && inner.registered.load(Ordering::Acquire);
+        usable.then_some(index)
+    }
+
+    pub(crate) fn buf_ptr(&self, index: u16) -> *mut u8 {
+        // SAFETY: Indexes are always less than the number of buffers in the pool.
+        unsafe { self.inner.bufs.add(index as usize * self.inner.buf_size) }
+    }
+}
+
+impl fmt::Debug for FixedBufPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedBufPool")
+            .field("count", &self.inner.count)
+            .field("buf_size", &self.inner.buf_size)
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+/// A [FixedBuf] is a buffer checked out from a [FixedBufPool], which can be used with the fixed
+/// buffer events such as [crate::net::TcpStream::read_fixed]. It dereferences to its first
+/// [FixedBuf::len] bytes, which is the data read by the last read into it, or the data to write.
+pub struct FixedBuf {
+    pool: FixedBufPool,
+    index: u16,
+    len: usize,
+}
+
+impl FixedBuf {
+    /// Returns the size of this buffer.
+    pub fn capacity(&self) -> usize {
+        self.pool.inner.buf_size
+    }
+
+    /// Set the length of the data in this buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than [FixedBuf::capacity].
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity());
+        self.len = len;
+    }
+
+    /// Clear the data in this buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Append the given data to the data in this buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data doesn't fit in the remaining capacity of the buffer.
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        let len = self.len + data.len();
+        assert!(len <= self.capacity());
+        // SAFETY: The destination is within the bounds of this buffer, which we own exclusively.
+        unsafe {
+            self.as_mut_ptr()
+                .add(self.len)
+                .copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+        self.len = len;
+    }
+
+    /// Returns the index of this buffer within its [FixedBufPool].
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the [FixedBufPool] this buffer was checked out from.
+    pub(crate) fn pool(&self) -> &FixedBufPool {
+        &self.pool
+    }
+
+    pub(crate) fn as_mut_ptr(&self) -> *mut u8 {
+        self.pool.buf_ptr(self.index)
+    }
+}
+
+impl Deref for FixedBuf {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The buffer is checked out to us alone, and is fully initialized.
+        unsafe { slice::from_raw_parts(self.as_mut_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for FixedBuf {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: See [FixedBuf::deref].
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for FixedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedBuf")
+            .field("index", &self.index)
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl Drop for FixedBuf {
+    fn drop(&mut self) {
+        self.pool.inner.lock_free().push(self.index);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{BorrowedBuffer, FixedBuf};
+
+/// An [IoBuf] is a buffer that can be handed to the kernel for the duration of an event that reads
+/// from it, such as a send. Ownership of the buffer is passed to the event and handed back once it
+/// completes, which keeps the buffer alive for as long as the kernel might access it even if the
+/// future driving the event is dropped early.
+///
+/// # Safety
+///
+/// Implementors must ensure that the memory behind [IoBuf::stable_ptr] stays valid and doesn't
+/// move for as long as the buffer lives, regardless of the buffer itself being moved, and that the
+/// first [IoBuf::bytes_init] bytes are initialized.
+pub unsafe trait IoBuf: Send + Unpin + 'static {
+    /// Returns a pointer to the start of the buffer.
+    fn stable_ptr(&self) -> *const u8;
+
+    /// Returns the number of initialized bytes in the buffer, this is the amount of data written
+    /// by events that read from the buffer.
+    fn bytes_init(&self) -> usize;
+
+    /// Returns the total size of the buffer.
+    fn bytes_total(&self) -> usize;
+}
+
+/// An [IoBufMut] is a buffer that can be handed to the kernel for the duration of an event that
+/// writes to it, such as a receive. See [IoBuf] for details on the ownership of the buffer.
+///
+/// The kernel writes into the full [IoBuf::bytes_total] bytes of the buffer, which for a [Vec] is
+/// its capacity rather than its length. So `Vec::with_capacity(1024)` receives up to 1024 bytes,
+/// while `Vec::new()` has no room at all and receives into it fail with `EINVAL`.
+///
+/// # Safety
+///
+/// Implementors must uphold the same guarantees as for [IoBuf], for [IoBufMut::stable_mut_ptr] and
+/// the full [IoBuf::bytes_total] bytes of the buffer.
+pub unsafe trait IoBufMut: IoBuf {
+    /// Returns a mutable pointer to the start of the buffer.
+    fn stable_mut_ptr(&mut self) -> *mut u8;
+
+    /// Mark the first `pos` bytes of the buffer as initialized, this is called once the kernel has
+    /// written `pos` bytes into the buffer.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the first `pos` bytes of the buffer are in fact initialized.
+    unsafe fn set_init(&mut self, pos: usize);
+}
+
+unsafe impl IoBuf for Vec<u8> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+unsafe impl IoBufMut for Vec<u8> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if self.len() < pos {
+            self.set_len(pos);
+        }
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, _: usize) {}
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl<const N: usize> IoBuf for &'static [u8; N] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        N
+    }
+
+    fn bytes_total(&self) -> usize {
+        N
+    }
+}
+
+unsafe impl IoBuf for &'static str {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for FixedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        FixedBuf::as_mut_ptr(self)
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+unsafe impl IoBufMut for FixedBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        FixedBuf::as_mut_ptr(self)
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.set_len(pos);
+    }
+}
+
+unsafe impl IoBuf for BorrowedBuffer {
+    fn stable_ptr(&self) -> *const u8 {
+        self[..].as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
@@ -0,0 +1,33 @@
+use std::{pin::Pin, time::Duration};
+
+use io_uring::{opcode, types::Timespec};
+
+use super::{Completion, CompletionStatus};
+
+/// A linked timeout operation, this must be submitted directly after an operation with the
+/// [io_uring::squeue::Flags::IO_LINK] flag set, and will cancel that operation if it hasn't
+/// completed by the time the timeout elapses.
+pub struct LinkTimeout {
+    timespec: Pin<Box<Timespec>>,
+}
+
+impl LinkTimeout {
+    /// Create a new [LinkTimeout] event that will fire after the given [Duration].
+    pub fn new(timeout: Duration) -> LinkTimeout {
+        LinkTimeout {
+            timespec: Box::pin(Timespec::from(timeout)),
+        }
+    }
+}
+
+impl Completion for LinkTimeout {
+    fn resolve(&self, _: io_uring::cqueue::Entry) -> CompletionStatus {
+        // Whether we fired or the linked operation completed first we are done, the linked
+        // operation is responsible for reporting the actual result.
+        CompletionStatus::Finalized
+    }
+
+    fn as_entry(&mut self) -> io_uring::squeue::Entry {
+        opcode::LinkTimeout::new(&*self.timespec as *const _).build()
+    }
+}
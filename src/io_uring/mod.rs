@@ -1,19 +1,47 @@
 //! The [crate::io_uring] module represents a simplified interface ontop of the [io_uring::IoUring]
 //! implementation. This module distills the implementation down to three components:
 //! - The [Completion] trait which futures implement to handle async I/O results and event
-//! creation.
+//!   creation.
 //! - The [CompletionStatus] enum which handles informing the [UringDriver] what to do with the
-//! result of a [Completion]
+//!   result of a [Completion]
 //! - The [UringDriver] which handles driving the async I/O and coordinating the execution with a
-//! higher level executor.
+//!   higher level executor.
 //!
 //! The [UringDriver] is the main async I/O event loop and is exposed via
 //! [thread_local::ThreadLocal] types in the [crate::context] module. It is generally unneeded to
 //! create instances of a [UringDriver] directly.
 
+mod async_fd;
+mod buf_ring;
+mod builder;
 mod cancel;
+mod capabilities;
+mod chain;
 mod completion;
+mod descriptor;
 mod engine;
+mod epoll;
+mod fixed_buf;
+mod io_buf;
+mod link_timeout;
+mod msg_ring;
+mod op;
+mod registration;
+mod remote;
+mod user_data;
 
+pub use async_fd::{AsyncFd, ReadyGuard};
+pub use buf_ring::{BorrowedBuffer, BufRing};
+pub use builder::{Backend, UringBuilder};
+pub use capabilities::Capabilities;
+pub use chain::{Chain, Link};
 pub use completion::{Completion, CompletionStatus};
 pub use engine::UringDriver;
+pub use fixed_buf::{FixedBuf, FixedBufPool};
+pub use io_buf::{IoBuf, IoBufMut};
+pub use msg_ring::{Mailbox, Message, Messages, Post, SendStream};
+pub use op::{Op, Operation};
+
+pub(crate) use descriptor::{AsTarget, Descriptor, Target};
+pub(crate) use registration::Registration;
+pub(crate) use remote::Remote;
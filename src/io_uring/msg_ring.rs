@@ -0,0 +1,412 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, OwnedFd},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures::{Future, Stream};
+use io_uring::{
+    cqueue, opcode, squeue,
+    types::{DestinationSlot, Fd, Fixed},
+};
+
+use nix::libc;
+
+use crate::{context, net::TcpStream};
+
+use super::{
+    user_data::{Kind, UserData},
+    AsTarget, Descriptor, Op, Operation, Target,
+};
+
+/// A message posted to a ring by another ring via a [Mailbox], as received from [Messages].
+pub enum Message {
+    /// A value posted via [Mailbox::post].
+    Data(u64),
+    /// A connection sent via [Mailbox::send_stream], which lives in the fixed file table of the
+    /// receiving ring and is only usable from the worker that received it.
+    Stream(TcpStream),
+}
+
+/// A message as it is queued up by the [super::UringDriver] that received it, until it is handed
+/// out via [Messages].
+pub(crate) enum Received {
+    Data(u64),
+    File(u32),
+}
+
+impl Received {
+    /// Decode a message from the completion event the kernel posted for it, if it is one.
+    pub(crate) fn decode(value: &cqueue::Entry) -> Option<Received> {
+        let user_data = UserData::from(value.user_data());
+        match user_data.kind() {
+            Some(Kind::Message) => {
+                let upper = (user_data.index() as u64) << 32;
+                Some(Received::Data(upper | value.result() as u32 as u64))
+            }
+            Some(Kind::File) if value.result() >= 0 => Some(Received::File(value.result() as u32)),
+            _ => None,
+        }
+    }
+}
+
+/// A [Mailbox] is a handle to the ring of a [super::UringDriver] that any thread can use to post
+/// messages to it via `IORING_OP_MSG_RING`, retrieved via [super::UringDriver::mailbox]. Messages
+/// are submitted to the ring of the current thread, and the kernel posts them straight to the
+/// completion queue of the receiving ring, waking it up if it is waiting for events. The receiving
+/// worker picks them up via [Messages]. Note that messages aren't guaranteed to be received in
+/// the order they were posted in.
+///
+/// The [Mailbox] keeps the ring it points to alive, and posts to a ring whose driver has since been
+/// dropped fail with `EPIPE`. Should the driver be dropped while a post is in flight the message is
+/// lost, and a connection sent via [Mailbox::send_stream] stays open until every [Mailbox] for the
+/// ring has been dropped as well.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures::StreamExt;
+///
+/// use libuio::{
+///     context,
+///     executor::block_on,
+///     io_uring::{Message, Messages},
+/// };
+///
+/// let mailbox = context::uring()
+///     .mailbox()
+///     .expect("failed to create mailbox");
+///
+/// std::thread::spawn(move || {
+///     block_on(async {
+///         mailbox.post(42).await.expect("failed to post message");
+///     })
+/// });
+///
+/// block_on(async {
+///     if let Some(Message::Data(data)) = Messages::new().next().await {
+///         println!("received {data}");
+///     }
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Mailbox {
+    ring: Arc<OwnedFd>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Mailbox {
+    pub(crate) fn new(ring: Arc<OwnedFd>) -> Mailbox {
+        Mailbox {
+            ring,
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Mark the ring as no longer being driven, this is called when its driver is dropped.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    /// Returns the ring to submit to, or `None` if its driver has been dropped.
+    fn ring(&self) -> Option<Fd> {
+        match self.closed.load(Ordering::Acquire) {
+            true => None,
+            false => Some(Fd(self.ring.as_raw_fd())),
+        }
+    }
+
+    /// Post the given value to the ring, which is received as a [Message::Data].
+    pub fn post(&self, data: u64) -> Post {
+        let user_data = UserData::new((data >> 32) as usize, 0, Kind::Message);
+        Post::new(self.clone(), user_data, data as u32 as i32)
+    }
+
+    /// Wake up the ring if it is waiting for events, without posting a message to it.
+    pub fn wake(&self) -> Post {
+        Post::new(self.clone(), UserData::new(0, 0, Kind::Wake), 0)
+    }
+
+    /// Send the given connection to the ring, which is received as a [Message::Stream]. The
+    /// kernel installs the file into a free slot of the fixed file table of the receiving ring,
+    /// after which the [TcpStream] is handed back, and should be dropped to close this worker's
+    /// reference to the connection.
+    ///
+    /// The connection must be registered in the fixed file table of the ring of the current
+    /// worker, which is the case for connections accepted or created on this worker while there
+    /// was room in the table, otherwise this fails with `EBADF`. The send fails with `ENFILE` if
+    /// the fixed file table of the receiving ring is full or disabled.
+    pub fn send_stream(&self, stream: TcpStream) -> SendStream {
+        SendStream::new(self.clone(), stream)
+    }
+}
+
+/// Returns the result of a message submitted to a ring, or `EPIPE` if it never was since the
+/// driver of the ring had been dropped.
+fn result(closed: bool, result: io::Result<u32>) -> io::Result<()> {
+    match closed {
+        true => Err(io::Error::from_raw_os_error(libc::EPIPE)),
+        false => result.map(|_| ()),
+    }
+}
+
+struct PostOp {
+    mailbox: Mailbox,
+    closed: bool,
+    user_data: UserData,
+    data: i32,
+}
+
+// SAFETY: Posting a message doesn't reference any memory.
+unsafe impl Operation for PostOp {
+    type Output = io::Result<()>;
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        match self.mailbox.ring() {
+            Some(ring) => {
+                opcode::MsgRingData::new(ring, self.data, self.user_data.into(), None).build()
+            }
+            None => {
+                self.closed = true;
+                opcode::Nop::new().build()
+            }
+        }
+    }
+
+    fn complete(self, res: io::Result<u32>, _: u32) -> Self::Output {
+        result(self.closed, res)
+    }
+}
+
+/// This represents a single use asynchronous post of a message to another ring, see
+/// [Mailbox::post] and [Mailbox::wake].
+///
+/// The post is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct Post {
+    op: Op<PostOp>,
+}
+
+impl Post {
+    fn new(mailbox: Mailbox, user_data: UserData, data: i32) -> Post {
+        let op = PostOp {
+            mailbox,
+            closed: false,
+            user_data,
+            data,
+        };
+        Post { op: Op::new(op) }
+    }
+}
+
+impl Future for Post {
+    type Output = io::Result<()>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+struct SendStreamOp {
+    mailbox: Mailbox,
+    closed: bool,
+    target: Target,
+    stream: TcpStream,
+}
+
+// SAFETY: Sending a file doesn't reference any memory, and the stream is held on to until the
+// kernel is done with it.
+unsafe impl Operation for SendStreamOp {
+    type Output = (io::Result<()>, TcpStream);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let ring = match self.mailbox.ring() {
+            Some(ring) => ring,
+            None => {
+                self.closed = true;
+                return opcode::Nop::new().build();
+            }
+        };
+
+        // A connection that isn't in the fixed file table is given a slot that is out of range,
+        // which the kernel rejects with `EBADF`.
+        let slot = self.target.fixed_slot().unwrap_or(u32::MAX);
+        let user_data = UserData::new(0, 0, Kind::File);
+        opcode::MsgRingSendFd::new(
+            ring,
+            Fixed(slot),
+            DestinationSlot::auto_target(),
+            user_data.into(),
+        )
+        .build()
+    }
+
+    fn complete(self, res: io::Result<u32>, _: u32) -> Self::Output {
+        (result(self.closed, res), self.stream)
+    }
+}
+
+/// This represents a single use asynchronous send of a connection to another ring, ultimately
+/// handing the connection back, see [Mailbox::send_stream].
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct SendStream {
+    op: Op<SendStreamOp>,
+}
+
+impl SendStream {
+    fn new(mailbox: Mailbox, stream: TcpStream) -> SendStream {
+        let op = SendStreamOp {
+            mailbox,
+            closed: false,
+            target: stream.as_target(),
+            stream,
+        };
+        SendStream { op: Op::new(op) }
+    }
+}
+
+impl Future for SendStream {
+    type Output = (io::Result<()>, TcpStream);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+/// A stream of the [Message]s posted to the ring of the worker that polls it, see [Mailbox]. The
+/// stream never ends, and messages are queued up on the ring until they are picked up. Any number
+/// of streams may be polled on the same worker, in which case each message is picked up by only
+/// one of them.
+///
+/// Note that each worker has its own ring, so a task that migrates between workers picks up the
+/// messages of whichever worker it is polled on. Messages are best paired with
+/// [crate::executor::ThreadPoolBuilder::thread_per_core] where tasks never migrate.
+#[derive(Debug, Default)]
+pub struct Messages {
+    _private: (),
+}
+
+impl Messages {
+    /// Create a new stream of the messages posted to the ring of the current thread.
+    pub fn new() -> Messages {
+        Messages { _private: () }
+    }
+}
+
+impl Stream for Messages {
+    type Item = Message;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut uring = context::uring();
+        match uring.next_message(cx.waker()) {
+            Some(Received::Data(data)) => Poll::Ready(Some(Message::Data(data))),
+            Some(Received::File(slot)) => {
+                let descriptor = Descriptor::direct(uring.remote(), slot);
+                drop(uring);
+                Poll::Ready(Some(Message::Stream(TcpStream::from(descriptor))))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+
+    use futures::{stream::FuturesUnordered, StreamExt};
+
+    use super::*;
+    use crate::{executor::block_on, net::TcpListener, time};
+
+    #[test]
+    fn test_mailbox() {
+        let mut listener = TcpListener::new("127.0.0.1", 0).unwrap();
+        let addr = listener.local_addr();
+        let (tx, rx) = mpsc::channel();
+
+        let receiver = thread::spawn(move || {
+            tx.send(context::uring().mailbox().unwrap()).unwrap();
+            block_on(async {
+                // Data and files are posted to the ring along different paths, so they may be
+                // received in either order.
+                let mut messages = Messages::new().take(2);
+                let (mut data, mut stream) = (None, None);
+                while let Some(message) = messages.next().await {
+                    match message {
+                        Message::Data(value) => data = Some(value),
+                        Message::Stream(value) => stream = Some(value),
+                    }
+                }
+                assert_eq!(data, Some(u64::MAX - 1));
+                let mut stream = stream.unwrap();
+                assert!(stream.is_fixed());
+
+                let (read, buf) = stream.recv(vec![0u8; 4]).await;
+                assert_eq!(read.unwrap(), 4);
+                assert_eq!(&buf, b"ping");
+            });
+        });
+        let mailbox = rx.recv().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut conn = std::net::TcpStream::connect(addr).unwrap();
+            conn.write_all(b"ping").unwrap();
+            conn.read(&mut [0u8; 1]).unwrap()
+        });
+
+        block_on(async {
+            mailbox.post(u64::MAX - 1).await.unwrap();
+
+            let stream = listener.accept().await.unwrap();
+            assert!(stream.is_fixed());
+            let (sent, stream) = mailbox.send_stream(stream).await;
+            sent.unwrap();
+            drop(stream);
+        });
+        receiver.join().unwrap();
+        assert_eq!(client.join().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_mailbox_closed() {
+        let mut driver = crate::io_uring::UringDriver::new(8).unwrap();
+        let mailbox = driver.mailbox().unwrap();
+        drop(driver);
+
+        let err = block_on(mailbox.post(1)).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EPIPE));
+    }
+
+    #[test]
+    fn test_messages_streams() {
+        let (tx, rx) = mpsc::channel();
+        let receiver = thread::spawn(move || {
+            tx.send(context::uring().mailbox().unwrap()).unwrap();
+
+            // Each stream is its own task, and each has to be woken for its message.
+            let streams: FuturesUnordered<_> = (0..2)
+                .map(|_| async { Messages::new().next().await })
+                .collect();
+            let received = block_on(time::timeout(Duration::from_secs(5), streams.count()));
+            assert_eq!(received.unwrap(), 2);
+        });
+
+        let mailbox = rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        block_on(async {
+            mailbox.post(1).await.unwrap();
+            mailbox.post(2).await.unwrap();
+        });
+        receiver.join().unwrap();
+    }
+}
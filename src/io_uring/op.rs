@@ -0,0 +1,320 @@
+use std::{
+    cell::Cell,
+    io,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{cqueue, squeue};
+
+use crate::sync::OneShot;
+
+use super::{Completion, CompletionStatus, Registration};
+
+/// An [Operation] is a single use io_uring event that is executed via an [Op] future, this is the
+/// alternative to implementing [Completion] and tracking its registration by hand. The operation
+/// owns any resources the kernel uses while the event is in flight, such as buffers or addresses,
+/// and is only handed back via [Operation::complete] once the kernel is done with them. If the
+/// [Op] is dropped early the operation is canceled, and is kept alive until the kernel confirms
+/// the cancelation.
+///
+/// The completion events of the operation are collected by the [super::UringDriver], and only
+/// handed to the operation once the [Op] is polled, so [Operation::update] and
+/// [Operation::complete] run outside of the driver and are free to use [crate::context::uring].
+///
+/// # Safety
+///
+/// The kernel acts on the [squeue::Entry] returned by [Operation::as_entry] as is, so any memory
+/// it points to, such as buffers, addresses or message headers, must stay valid and must not move
+/// until the operation is either completed or dropped. The operation may be moved while in flight,
+/// so that memory has to live on the heap, owned by the operation or by something that is
+/// guaranteed to outlive it, such as an earlier step of the same [super::Chain].
+///
+/// # Examples
+///
+/// A no-op event, which is useful to round trip through the kernel:
+///
+/// ```no_run
+/// use std::io;
+///
+/// use io_uring::{opcode, squeue};
+///
+/// use libuio::{
+///     executor::block_on,
+///     io_uring::{Op, Operation},
+/// };
+///
+/// struct Nop;
+///
+/// // SAFETY: A no-op doesn't reference any memory.
+/// unsafe impl Operation for Nop {
+///     type Output = io::Result<()>;
+///
+///     fn as_entry(&mut self) -> squeue::Entry {
+///         opcode::Nop::new().build()
+///     }
+///
+///     fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+///         result.map(|_| ())
+///     }
+/// }
+///
+/// block_on(Op::new(Nop)).expect("failed to execute nop");
+/// ```
+pub unsafe trait Operation: Send + 'static {
+    /// The output of the [Op] future executing this operation.
+    type Output: Send;
+
+    /// Create the [squeue::Entry] for this operation, see [Completion::as_entry] and the safety
+    /// requirements of this trait.
+    fn as_entry(&mut self) -> squeue::Entry;
+
+    /// Handle a completion event flagged with [cqueue::more], meaning the kernel will post further
+    /// completion events for this operation, for instance the notification of a zero-copy send.
+    /// These are handed over in order right before [Operation::complete], and by default are
+    /// ignored.
+    fn update(&mut self, result: io::Result<u32>, flags: u32) {
+        let _ = (result, flags);
+    }
+
+    /// Handle the final completion event of this operation, consuming it and returning the output
+    /// of the [Op] future along with any resources it owns. The result is the result of the event
+    /// as an error if negative, and `flags` are the flags of the completion event.
+    fn complete(self, result: io::Result<u32>, flags: u32) -> Self::Output;
+}
+
+/// An [Operation] along with the completion events the driver has collected for it, which are
+/// handed to the operation via [OpState::finish] once the driver is done with it.
+pub(super) struct OpState<T> {
+    op: T,
+    updates: Vec<(io::Result<u32>, u32)>,
+    result: Option<(io::Result<u32>, u32)>,
+}
+
+impl<T> OpState<T>
+where
+    T: Operation,
+{
+    pub(super) fn new(op: T) -> OpState<T> {
+        OpState {
+            op,
+            updates: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Hand the collected completion events to the operation, returning its output.
+    pub(super) fn finish(mut self) -> T::Output {
+        for (result, flags) in self.updates {
+            self.op.update(result, flags);
+        }
+        let (result, flags) = self.result.expect("operation finished before completion");
+        self.op.complete(result, flags)
+    }
+}
+
+struct OpCompletion<T> {
+    op: Cell<Option<OpState<T>>>,
+    result: OneShot<OpState<T>>,
+}
+
+impl<T> Completion for OpCompletion<T>
+where
+    T: Operation,
+{
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        resolve(&self.op, value, |state| self.result.complete(state))
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        as_entry(&mut self.op)
+    }
+}
+
+/// Record the given completion event for the [Operation] held in `op`, passing the operation on
+/// to `complete` once this is its final completion event.
+pub(super) fn resolve<T>(
+    op: &Cell<Option<OpState<T>>>,
+    value: cqueue::Entry,
+    complete: impl FnOnce(OpState<T>),
+) -> CompletionStatus
+where
+    T: Operation,
+{
+    // An operation canceled by its timeout is reported by the driver as `-ETIMEDOUT`, which maps
+    // onto [io::ErrorKind::TimedOut].
+    let result = match value.result() {
+        result if result < 0 => Err(io::Error::from_raw_os_error(-result)),
+        result => Ok(result as u32),
+    };
+
+    let mut state = match op.take() {
+        Some(state) => state,
+        None => return CompletionStatus::Finalized,
+    };
+    if cqueue::more(value.flags()) {
+        state.updates.push((result, value.flags()));
+        op.set(Some(state));
+        return CompletionStatus::Armed;
+    }
+    state.result = Some((result, value.flags()));
+    complete(state);
+    CompletionStatus::Finalized
+}
+
+/// Create the [squeue::Entry] for the [Operation] held in `op`.
+pub(super) fn as_entry<T>(op: &mut Cell<Option<OpState<T>>>) -> squeue::Entry
+where
+    T: Operation,
+{
+    op.get_mut()
+        .as_mut()
+        .expect("operation submitted after completion")
+        .op
+        .as_entry()
+}
+
+/// This represents a single use asynchronous [Operation], which is submitted to the ring of the
+/// current thread the first time it is polled, and resolves to the output of the operation once
+/// the kernel is done with it. Dropping the future before then cancels the operation.
+///
+/// The output type `O` always is the [Operation::Output] of `T`, it is only spelled out so that
+/// futures wrapping an [Op] over a generic operation don't need to name the output via `T`.
+pub struct Op<T, O = <T as Operation>::Output> {
+    registration: Registration<OpCompletion<T>>,
+    result: OneShot<OpState<T>>,
+    output: PhantomData<fn() -> O>,
+}
+
+impl<T> Op<T>
+where
+    T: Operation,
+{
+    /// Create a new [Op] future executing the given [Operation].
+    pub fn new(op: T) -> Op<T> {
+        let result = OneShot::new();
+        let op = OpCompletion {
+            op: Cell::new(Some(OpState::new(op))),
+            result: result.clone(),
+        };
+
+        Op {
+            registration: Registration::new(op),
+            result,
+            output: PhantomData,
+        }
+    }
+
+    /// Set a deadline for this operation, enforced by the kernel via a linked timeout. If the
+    /// operation hasn't completed within `timeout` it is canceled, and its result is an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.registration.set_timeout(timeout);
+        self
+    }
+
+    /// Returns a mutable reference to the operation if it hasn't been submitted yet.
+    pub(crate) fn get_mut(&mut self) -> Option<&mut T> {
+        self.registration
+            .get_mut()
+            .and_then(|op| op.op.get_mut().as_mut())
+            .map(|state| &mut state.op)
+    }
+}
+
+impl<T> Future for Op<T>
+where
+    T: Operation,
+{
+    type Output = T::Output;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.registration.poll_register(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.result.set_waker(cx.waker().clone());
+        match self.result.take() {
+            Some(state) => Poll::Ready(state.finish()),
+            None => self.registration.poll_timeout(cx),
+        }
+    }
+}
+
+// The operation is moved onto the heap when the future is first polled, and never pinned in
+// place, so the future can be moved freely regardless of the operation.
+impl<T, O> Unpin for Op<T, O> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use io_uring::{opcode, types::Timespec};
+
+    use super::*;
+    use crate::{context, executor::block_on};
+
+    struct Nop;
+
+    // SAFETY: A no-op doesn't reference any memory.
+    unsafe impl Operation for Nop {
+        type Output = io::Result<u32>;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Nop::new().build()
+        }
+
+        fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+            result
+        }
+    }
+
+    /// A timeout operation that never completes on its own within the test.
+    struct Sleep(Pin<Box<Timespec>>);
+
+    // SAFETY: The timespec lives on the heap, owned by the operation.
+    unsafe impl Operation for Sleep {
+        type Output = io::Result<u32>;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Timeout::new(&*self.0).build()
+        }
+
+        fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+            result
+        }
+    }
+
+    /// A no-op that looks at the driver once complete.
+    struct Probe;
+
+    // SAFETY: A no-op doesn't reference any memory.
+    unsafe impl Operation for Probe {
+        type Output = bool;
+
+        fn as_entry(&mut self) -> squeue::Entry {
+            opcode::Nop::new().build()
+        }
+
+        fn complete(self, _: io::Result<u32>, _: u32) -> Self::Output {
+            context::uring().is_owner()
+        }
+    }
+
+    #[test]
+    fn test_op_complete_outside_driver() {
+        assert!(block_on(Op::new(Probe)));
+    }
+
+    #[test]
+    fn test_op() {
+        assert_eq!(block_on(Op::new(Nop)).unwrap(), 0);
+
+        let sleep = Sleep(Box::pin(Timespec::from(Duration::from_secs(60))));
+        let result = block_on(Op::new(sleep).timeout(Duration::from_millis(10)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+
+use crate::{
+    context,
+    time::{self, Deadline},
+};
+
+use super::{Completion, Remote};
+
+enum State<C> {
+    Idle(C),
+    Registered(Remote, u64),
+}
+
+/// A [Registration] tracks the lifecycle of a [Completion] on behalf of the future that created
+/// it. The completion is held back until the future is first polled, which allows for the
+/// submission to be customized, for instance by setting a linked timeout, after the future has
+/// been created. Once registered, the completion is automatically deregistered when the
+/// [Registration] is dropped.
+pub(crate) struct Registration<C> {
+    state: Option<State<C>>,
+    timeout: Option<Duration>,
+    timer: Option<Deadline>,
+}
+
+impl<C> Registration<C>
+where
+    C: Completion + 'static,
+{
+    /// Create a new [Registration] for the given [Completion], note that this does not register
+    /// the completion with the [super::UringDriver] that happens on the first call to
+    /// [Registration::poll_register].
+    pub(crate) fn new(op: C) -> Registration<C> {
+        Registration {
+            state: Some(State::Idle(op)),
+            timeout: None,
+            timer: None,
+        }
+    }
+
+    /// Set a timeout to link to the completion when it is registered, this has no effect if the
+    /// completion has already been registered.
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Returns a mutable reference to the completion if it hasn't been registered yet, allowing
+    /// for it to be customized before submission.
+    pub(crate) fn get_mut(&mut self) -> Option<&mut C> {
+        match self.state {
+            Some(State::Idle(ref mut op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Register the completion with the thread local [super::UringDriver] if it hasn't been
+    /// registered already. While the backlog of the driver is full the registration is held off,
+    /// and the task woken once there is room again, see [super::UringDriver::poll_ready].
+    pub(crate) fn poll_register(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if !matches!(self.state, Some(State::Idle(_))) {
+            return Poll::Ready(());
+        }
+        let mut uring = context::uring();
+        if uring.poll_ready(cx).is_pending() {
+            return Poll::Pending;
+        }
+        if let Some(State::Idle(op)) = self.state.take() {
+            // While the clock is paused the timeout is tracked by the paused clock rather than
+            // linked in the kernel, see [Registration::poll_timeout].
+            if let Some(timeout) = self.timeout.filter(|_| time::is_paused()) {
+                self.timer = Some(Deadline::new(timeout));
+            }
+
+            let id = match self.timeout {
+                Some(timeout) if self.timer.is_none() => uring.register_with_timeout(op, timeout),
+                _ => uring.register(op),
+            };
+            // The backlog was checked above while holding on to the driver, so there is room.
+            let id = id.expect("backlog full after poll_ready");
+            self.state = Some(State::Registered(uring.remote(), id));
+        }
+        Poll::Ready(())
+    }
+
+    /// Poll the timeout of a completion registered while the clock was paused, once the timeout
+    /// fires the completion is canceled with a result of `-ETIMEDOUT`, which maps onto
+    /// [std::io::ErrorKind::TimedOut]. This is always pending, since the result is handed back by
+    /// the completion either way.
+    pub(crate) fn poll_timeout<T>(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        let timer = match self.timer {
+            Some(ref mut timer) => timer,
+            None => return Poll::Pending,
+        };
+
+        if Pin::new(timer).poll(cx).is_ready() {
+            self.timer = None;
+            if let Some(State::Registered(ref ring, id)) = self.state {
+                ring.expire(id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<C> Drop for Registration<C> {
+    fn drop(&mut self) {
+        // The future may have been moved to another worker since it was registered, in which case
+        // the deregistration is handed off to the worker that owns the ring.
+        if let Some(State::Registered(ref ring, id)) = self.state {
+            ring.deregister(id);
+        }
+    }
+}
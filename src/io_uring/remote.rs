@@ -0,0 +1,116 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, ThreadId},
+};
+
+use crate::context;
+
+/// A [Remote] is a handle to a [super::UringDriver] that can be safely used from any thread. Each
+/// ring is only ever touched by the thread that owns it, so operations that need to reach a ring
+/// from another thread, such as a future that migrated to another worker dropping its
+/// registration, are queued up here and picked up by the owner on its next call to
+/// [super::UringDriver::run].
+#[derive(Clone)]
+pub(crate) struct Remote {
+    owner: ThreadId,
+    pending: Arc<Mutex<Vec<Pending>>>,
+}
+
+/// An operation handed off to the thread that owns a ring.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Pending {
+    /// Deregister the event with the given user_data.
+    Deregister(u64),
+    /// Cancel the event with the given user_data on behalf of its timeout.
+    Expire(u64),
+    /// Remove the file in the given slot of the fixed file table.
+    Unregister(u32),
+}
+
+impl Remote {
+    pub(crate) fn new() -> Remote {
+        Remote {
+            owner: thread::current().id(),
+            pending: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the [ThreadId] of the thread that owns the ring.
+    pub(crate) fn owner(&self) -> ThreadId {
+        self.owner
+    }
+
+    /// Returns whether or not the current thread owns the ring.
+    pub(crate) fn is_owner(&self) -> bool {
+        thread::current().id() == self.owner
+    }
+
+    /// Deregister the event with the given user_data from the ring, directly if the current
+    /// thread owns the ring, or by handing it off to the owner otherwise.
+    pub(crate) fn deregister(&self, user_data: u64) {
+        self.run(Pending::Deregister(user_data));
+    }
+
+    /// Cancel the event with the given user_data on behalf of its timeout, see
+    /// [super::UringDriver::expire], directly if the current thread owns the ring, or by handing it
+    /// off to the owner otherwise.
+    pub(crate) fn expire(&self, user_data: u64) {
+        self.run(Pending::Expire(user_data));
+    }
+
+    /// Remove the file in the given slot from the fixed file table of the ring, directly if the
+    /// current thread owns the ring, or by handing it off to the owner otherwise.
+    pub(crate) fn unregister_file(&self, slot: u32) {
+        self.run(Pending::Unregister(slot));
+    }
+
+    /// Run the given operation on the ring if the current thread owns it, otherwise queue it up for
+    /// the owner. The owner queues it up as well when the ring is already locked further up the
+    /// stack, such as when a completion being resolved drops a socket, and picks it up on its next
+    /// run instead.
+    fn run(&self, pending: Pending) {
+        let mut uring = match self.is_owner().then(context::try_uring).flatten() {
+            Some(uring) => uring,
+            None => return self.lock().push(pending),
+        };
+        match pending {
+            Pending::Deregister(user_data) => uring.deregister(user_data),
+            Pending::Expire(user_data) => uring.expire(user_data),
+            Pending::Unregister(slot) => uring.unregister_file(slot),
+        }
+    }
+
+    /// Take all operations queued up by other threads.
+    pub(crate) fn take(&self) -> Vec<Pending> {
+        mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Pending>> {
+        self.pending
+            .lock()
+            .expect("failed to lock remote queue: poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_handoff() {
+        let remote = context::uring().remote();
+        assert!(remote.is_owner());
+
+        let other = remote.clone();
+        thread::spawn(move || {
+            assert!(!other.is_owner());
+            other.deregister(42);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(remote.take(), vec![Pending::Deregister(42)]);
+        assert!(remote.take().is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+/// The number of bits of the user_data holding the state index of an event.
+const INDEX_BITS: u32 = 32;
+/// The number of bits of the user_data holding the generation of an event.
+const GENERATION_BITS: u32 = 24;
+const GENERATION_MASK: u32 = (1 << GENERATION_BITS) - 1;
+
+/// The kind of an event registered with a [super::UringDriver], encoded in its [UserData].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Kind {
+    /// An event registered by a future via [super::UringDriver::register].
+    Op = 0,
+    /// A timeout linked to an event, see [super::UringDriver::register_with_timeout].
+    LinkTimeout = 1,
+    /// An async cancel issued against another event.
+    Cancel = 2,
+    /// A timeout driving the [crate::time::wheel::TimerWheel].
+    Timer = 3,
+    /// A message posted by another ring via a [super::Mailbox], carrying the upper half of its
+    /// payload in place of the state index.
+    Message = 4,
+    /// A file sent by another ring via a [super::Mailbox].
+    File = 5,
+    /// A message posted by another ring via [super::Mailbox::wake] that only wakes up the ring.
+    Wake = 6,
+}
+
+/// The user_data attached to the entries submitted to the ring, packing the state index of the
+/// event in the low 32 bits, followed by a 24 bit generation and the 8 bit [Kind] of the event.
+/// Since state indexes are reused once an event is done, the generation is what tells a late
+/// completion apart from one for the event that now holds the index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct UserData(u64);
+
+impl UserData {
+    pub(crate) fn new(index: usize, generation: u32, kind: Kind) -> UserData {
+        debug_assert!(index < 1 << INDEX_BITS);
+        UserData(
+            index as u64
+                | ((generation & GENERATION_MASK) as u64) << INDEX_BITS
+                | (kind as u64) << (INDEX_BITS + GENERATION_BITS),
+        )
+    }
+
+    /// Returns the state index of the event.
+    pub(crate) fn index(self) -> usize {
+        (self.0 & u32::MAX as u64) as usize
+    }
+
+    /// Returns the [Kind] of the event, or `None` if the user_data wasn't generated by us.
+    pub(crate) fn kind(self) -> Option<Kind> {
+        match self.0 >> (INDEX_BITS + GENERATION_BITS) {
+            0 => Some(Kind::Op),
+            1 => Some(Kind::LinkTimeout),
+            2 => Some(Kind::Cancel),
+            3 => Some(Kind::Timer),
+            4 => Some(Kind::Message),
+            5 => Some(Kind::File),
+            6 => Some(Kind::Wake),
+            _ => None,
+        }
+    }
+
+    /// Returns the next generation after the given one, wrapping around within the bits available.
+    pub(crate) fn next_generation(generation: u32) -> u32 {
+        generation.wrapping_add(1) & GENERATION_MASK
+    }
+}
+
+impl From<u64> for UserData {
+    fn from(value: u64) -> Self {
+        UserData(value)
+    }
+}
+
+impl From<UserData> for u64 {
+    fn from(value: UserData) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_data() {
+        let data = UserData::new(42, GENERATION_MASK + 7, Kind::Cancel);
+        assert_eq!(data.index(), 42);
+        assert_eq!(data, UserData::new(42, 6, Kind::Cancel));
+        assert_eq!(data.kind(), Some(Kind::Cancel));
+        assert_eq!(UserData::from(u64::from(data)), data);
+        assert_ne!(data, UserData::new(42, 7, Kind::Cancel));
+        assert_eq!(UserData::next_generation(GENERATION_MASK), 0);
+        assert_eq!(UserData::from(u64::MAX).kind(), None);
+    }
+}
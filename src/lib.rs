@@ -23,7 +23,7 @@
 //!     // is set to listen on [::]:9091 and have a connection backlog of 1024.
 //!     let mut listener = TcpListener::with_outstanding("[::]", 9091, 1024)?;
 //!
-//!     let mut buf = vec![0u8; 1024];
+//!     let mut buf = Vec::with_capacity(1024);
 //!
 //!     println!("Listening on: {}", listener.local_addr());
 //!
@@ -46,7 +46,12 @@
 //!
 //!         println!("Got connection from: {}", conn.peer_addr());
 //!
-//!         let read = match conn.recv(buf.as_mut_slice()).await {
+//!         // The buffer is handed to the receive and returned once it completes, with its length set
+//!         // to the amount of data read.
+//!         buf.clear();
+//!         let (read, returned) = conn.recv(buf).await;
+//!         buf = returned;
+//!         let read = match read {
 //!             Ok(ret) => ret,
 //!             Err(e) => {
 //!                 println!("Failed to receive from client: {}", e);
@@ -58,9 +63,9 @@
 //!
 //!         println!("Client request: {}", s);
 //!
-//!         conn.send(&buf[..read])
-//!             .await
-//!             .expect("Failed to respond to client.");
+//!         let (sent, returned) = conn.send(buf).await;
+//!         buf = returned;
+//!         sent.expect("Failed to respond to client.");
 //!     }
 //!     Ok(())
 //! }
@@ -90,11 +95,12 @@
 //!     );
 //!
 //!     // Send some data to the remote host.
-//!     client.send("Hello from client!".as_bytes()).await?;
+//!     client.send("Hello from client!").await.0?;
 //!
-//!     // Now read back anything the server sent and then exit.
-//!     let mut buf = vec![0u8; 1024];
-//!     let read = client.recv(buf.as_mut_slice()).await?;
+//!     // Now read back anything the server sent and then exit. Buffers are handed to the event and
+//!     // returned once it completes.
+//!     let (read, buf) = client.recv(vec![0u8; 1024]).await;
+//!     let read = read?;
 //!
 //!     let str = String::from_utf8_lossy(&buf[..read]);
 //!     println!("Server response: {}", str);
@@ -107,10 +113,12 @@
 
 pub mod context;
 pub mod executor;
+pub mod fs;
 pub mod io_uring;
 pub mod net;
 pub(crate) mod ptr;
 pub mod sync;
+pub mod time;
 
 pub use executor::{spawn, ThreadPool, ThreadPoolBuilder};
 pub use libuio_macros::main;
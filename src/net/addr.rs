@@ -77,7 +77,7 @@ impl SocketAddrC {
             match self.v4.sin_family as i32 {
                 libc::AF_INET => {
                     let port = u16::from_be(self.v4.sin_port);
-                    let ip = Ipv4Addr::from(self.v4.sin_addr.s_addr);
+                    let ip = Ipv4Addr::from(self.v4.sin_addr.s_addr.to_ne_bytes());
 
                     SocketAddr::new(IpAddr::from(ip), port)
                 }
@@ -1,11 +1,14 @@
 use std::{
     io,
     net::SocketAddr,
-    os::fd::{AsRawFd, OwnedFd, RawFd},
+    os::fd::{AsRawFd, RawFd},
 };
 
+use crate::io_uring::{AsTarget, BufRing, Descriptor, FixedBuf, IoBuf, IoBufMut, Target};
+
 use super::{
-    getpeername, getsockname, socket, Connect, Recv, RecvFrom, RecvMsg, Send, SendMsg, SendTo,
+    getpeername, getsockname, socket, Connect, ReadFixed, Recv, RecvBuf, RecvFrom, RecvFromBuf,
+    RecvMsg, Send, SendMsg, SendTo, SendToZc, WriteFixed,
 };
 
 /// A [UdpSocket] represents a bi-directional UDP socket that can read and write data to any remote
@@ -13,7 +16,7 @@ use super::{
 /// host in order to not have to repeatedly specify the remote address in [UdpSocket::send_to] and
 /// [UdpSocket::send_msg] calls.
 pub struct UdpSocket {
-    fd: OwnedFd,
+    fd: Descriptor,
 }
 
 impl UdpSocket {
@@ -24,7 +27,9 @@ impl UdpSocket {
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
         let fd = socket::udp_socket(addr)?;
-        Ok(UdpSocket { fd })
+        Ok(UdpSocket {
+            fd: Descriptor::new(fd),
+        })
     }
 
     /// Retrieve this sockets local [SocketAddr], or panics if there is either no local address or
@@ -57,54 +62,101 @@ impl UdpSocket {
         getpeername(self.fd.as_raw_fd())
     }
 
+    /// Returns whether or not this socket is registered in the fixed file table of a ring, see
+    /// [crate::io_uring::UringBuilder::fixed_files].
+    pub fn is_fixed(&self) -> bool {
+        self.fd.is_fixed()
+    }
+
     /// Connect to the specified remote host.
     pub fn connect<'a>(&'a mut self, remote: &SocketAddr) -> Connect<'a, UdpSocket> {
         Connect::new(self, remote)
     }
 
-    /// Read data from the socket into the specified buffer, returning the number of bytes read.
-    /// Note that this method requires that [UdpSocket::connect] be called successfuly to set the
-    /// remote address.
-    pub fn recv<'a>(&'a mut self, buf: &mut [u8]) -> Recv<'a, UdpSocket> {
+    /// Read data from the socket into the specified buffer, returning the number of bytes read
+    /// along with the buffer. Note that this method requires that [UdpSocket::connect] be called
+    /// successfuly to set the remote address.
+    pub fn recv<'a, B: IoBufMut>(&'a mut self, buf: B) -> Recv<'a, UdpSocket, B> {
         Recv::new(self, buf)
     }
 
     /// Read data from the socket into the specified buffer, returning the number of bytes read and
-    /// the [SocketAddr] of the remote host that sent the data.
-    pub fn recv_from<'a>(&'a mut self, buf: &mut [u8]) -> RecvFrom<'a, UdpSocket> {
+    /// the [SocketAddr] of the remote host that sent the data along with the buffer.
+    pub fn recv_from<'a, B: IoBufMut>(&'a mut self, buf: B) -> RecvFrom<'a, UdpSocket, B> {
         RecvFrom::new(self, buf)
     }
 
+    /// Read data from the socket into a buffer picked by the kernel from the given [BufRing] once
+    /// data arrives, returning the data as a [crate::io_uring::BorrowedBuffer]. Note that this
+    /// method requires that [UdpSocket::connect] be called successfuly to set the remote address.
+    pub fn recv_buf<'a>(&'a mut self, ring: &BufRing) -> RecvBuf<'a, UdpSocket> {
+        RecvBuf::new(self, ring)
+    }
+
+    /// Read data from the socket into a buffer picked by the kernel from the given [BufRing] once
+    /// data arrives, returning the data as a [crate::io_uring::BorrowedBuffer] and the
+    /// [SocketAddr] of the remote host that sent the data.
+    pub fn recv_from_buf<'a>(&'a mut self, ring: &BufRing) -> RecvFromBuf<'a, UdpSocket> {
+        RecvFromBuf::new(self, ring)
+    }
+
     /// Read data from the socket into the specified buffers, returning the number of bytes read
-    /// and the [SocketAddr] of the remote host that sent the data.
-    pub fn recv_msg<'a>(&'a mut self, bufs: &mut [Vec<u8>]) -> RecvMsg<'a, UdpSocket> {
+    /// and the [SocketAddr] of the remote host that sent the data along with the buffers.
+    pub fn recv_msg<'a, B: IoBufMut>(&'a mut self, bufs: Vec<B>) -> RecvMsg<'a, UdpSocket, B> {
         RecvMsg::new(self, bufs)
     }
 
-    /// Send the specified data to the remote peer, returning the number of bytes read. Note that
+    /// Read a datagram from the socket into the specified [FixedBuf], returning the number of
+    /// bytes read which is also set as the length of the buffer along with the buffer. Note that
     /// this method requires that [UdpSocket::connect] be called successfuly to set the remote
     /// address.
-    pub fn send<'a>(&'a mut self, buf: &[u8]) -> Send<'a, UdpSocket> {
+    pub fn read_fixed<'a>(&'a mut self, buf: FixedBuf) -> ReadFixed<'a, UdpSocket> {
+        ReadFixed::new(self, buf, u64::MAX)
+    }
+
+    /// Send the data in the specified [FixedBuf] to the remote peer, returning the number of bytes
+    /// sent along with the buffer. Note that this method requires that [UdpSocket::connect] be
+    /// called successfuly to set the remote address.
+    pub fn write_fixed<'a>(&'a mut self, buf: FixedBuf) -> WriteFixed<'a, UdpSocket> {
+        WriteFixed::new(self, buf, u64::MAX)
+    }
+
+    /// Send the specified data to the remote peer, returning the number of bytes sent along with
+    /// the buffer. Note that this method requires that [UdpSocket::connect] be called successfuly
+    /// to set the remote address.
+    pub fn send<'a, B: IoBuf>(&'a mut self, buf: B) -> Send<'a, UdpSocket, B> {
         Send::new(self, buf)
     }
 
-    /// Send the specified data to the optionally specified host. Note that on unconnected sockets
-    /// the remote host is required.
-    pub fn send_to<'a>(
+    /// Send the specified data to the optionally specified host, returning the number of bytes sent
+    /// along with the buffer. Note that on unconnected sockets the remote host is required.
+    pub fn send_to<'a, B: IoBuf>(
         &'a mut self,
-        buf: &mut [u8],
+        buf: B,
         addr: Option<&SocketAddr>,
-    ) -> SendTo<'a, UdpSocket> {
+    ) -> SendTo<'a, UdpSocket, B> {
         SendTo::new(self, buf, addr)
     }
 
-    /// Send the data across all specified buffers to the optionally specified host. Note that on
-    /// unconnected sockets the remote host is required.
-    pub fn send_msg<'a>(
+    /// Send the specified data to the optionally specified host without copying it into the
+    /// socket, returning the number of bytes sent along with the buffer once the kernel has
+    /// released it. Note that on unconnected sockets the remote host is required.
+    pub fn send_to_zc<'a, B: IoBuf>(
         &'a mut self,
-        bufs: &mut [Vec<u8>],
+        buf: B,
         addr: Option<&SocketAddr>,
-    ) -> SendMsg<'a, UdpSocket> {
+    ) -> SendToZc<'a, UdpSocket, B> {
+        SendToZc::new(self, buf, addr)
+    }
+
+    /// Send the data across all specified buffers to the optionally specified host, returning the
+    /// number of bytes sent along with the buffers. Note that on unconnected sockets the remote
+    /// host is required.
+    pub fn send_msg<'a, B: IoBuf>(
+        &'a mut self,
+        bufs: Vec<B>,
+        addr: Option<&SocketAddr>,
+    ) -> SendMsg<'a, UdpSocket, B> {
         SendMsg::new(self, bufs, addr)
     }
 }
@@ -114,3 +166,9 @@ impl AsRawFd for UdpSocket {
         self.fd.as_raw_fd()
     }
 }
+
+impl AsTarget for UdpSocket {
+    fn as_target(&self) -> Target {
+        self.fd.target()
+    }
+}
@@ -1,84 +1,78 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, Op, Operation, Target},
     net::TcpStream,
-    sync::OneShot,
 };
 
-struct AcceptCompletion {
-    fd: RawFd,
-    conn: OneShot<io::Result<OwnedFd>>,
+struct AcceptOp {
+    target: Target,
 }
 
-impl AcceptCompletion {
-    pub fn new(fd: RawFd, result: OneShot<io::Result<OwnedFd>>) -> AcceptCompletion {
-        AcceptCompletion { fd, conn: result }
-    }
-}
-
-impl Completion for AcceptCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(unsafe { OwnedFd::from_raw_fd(result) }),
-        };
+// SAFETY: The peer address isn't asked for, so the entry doesn't reference any memory.
+unsafe impl Operation for AcceptOp {
+    type Output = io::Result<OwnedFd>;
 
-        self.conn.complete(result);
-        CompletionStatus::Finalized
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::Accept::new(fd, ptr::null_mut(), ptr::null_mut())
+            .build()
+            .flags(flags)
     }
 
-    fn as_entry(&mut self) -> squeue::Entry {
-        opcode::Accept::new(types::Fd(self.fd), ptr::null_mut(), ptr::null_mut()).build()
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        result.map(|fd| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
     }
 }
 
 /// This represents a single use future for accepting an active conntion from a live [TcpListener].
 /// When polled to completion the future will return a valid [TcpStream], or any [std::io::Error]
 /// encountered while awaiting the new connection.
+///
+/// The accept is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
 pub struct Accept<'a, T> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<OwnedFd>>,
-}
-
-impl<'a, T> Drop for Accept<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+    op: Op<AcceptOp>,
 }
 
 impl<'a, T> Accept<'a, T>
 where
     T: AsRawFd,
 {
-    pub(crate) fn new(listener: &'a mut T) -> Accept<'a, T> {
-        let result = OneShot::new();
-        let op = AcceptCompletion::new(listener.as_raw_fd(), result.clone());
-        let id = context::uring().register(op);
+    pub(crate) fn new(listener: &'a mut T) -> Accept<'a, T>
+    where
+        T: AsTarget,
+    {
+        let op = AcceptOp {
+            target: listener.as_target(),
+        };
 
         Accept {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this accept, enforced by the kernel via a linked timeout. If the accept
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
@@ -88,10 +82,10 @@ where
 {
     type Output = io::Result<TcpStream>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result.map(TcpStream::from)),
-            None => Poll::Pending,
-        }
+        // The connection is only wrapped here, outside of the driver, as that may register it in
+        // the fixed file table of the ring.
+        Pin::new(&mut self.op)
+            .poll(cx)
+            .map(|result| result.map(TcpStream::from))
     }
 }
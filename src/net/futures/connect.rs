@@ -1,89 +1,85 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     net::SocketAddr,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, types};
+use io_uring::{opcode, squeue};
 use nix::libc;
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, Op, Operation, Target},
     net::SocketAddrC,
-    sync::OneShot,
 };
 
-struct ConnectCompletion {
+struct ConnectOp {
     addr: Pin<Box<SocketAddrC>>,
     addr_len: libc::socklen_t,
-    fd: RawFd,
-    result: OneShot<io::Result<()>>,
+    target: Target,
 }
 
-impl Completion for ConnectCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(()),
-        };
+// SAFETY: The address lives on the heap, owned by the operation.
+unsafe impl Operation for ConnectOp {
+    type Output = io::Result<()>;
 
-        self.result.complete(result);
-        CompletionStatus::Finalized
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::Connect::new(fd, self.addr.as_ptr(), self.addr_len)
+            .build()
+            .flags(flags)
     }
 
-    fn as_entry(&mut self) -> io_uring::squeue::Entry {
-        opcode::Connect::new(types::Fd(self.fd), self.addr.as_ptr(), self.addr_len).build()
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        result.map(|_| ())
     }
 }
 
 /// This represents a single use asynchronous connect operation to create a new [TcpStream] object
 /// to interact with a remote host on. This will ultimately return the connected and ready to use
 /// [TcpStream].
+///
+/// The connect is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
 pub struct Connect<'a, T> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<()>>,
-}
-
-impl<'a, T> Drop for Connect<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+    op: Op<ConnectOp>,
 }
 
 impl<'a, T> Connect<'a, T>
 where
     T: AsRawFd,
 {
-    pub(crate) fn new(sock: &'a mut T, remote: &SocketAddr) -> Connect<'a, T> {
+    pub(crate) fn new(sock: &'a mut T, remote: &SocketAddr) -> Connect<'a, T>
+    where
+        T: AsTarget,
+    {
         let (addr, addr_len) = SocketAddrC::from_std(remote);
         let addr = Box::pin(addr);
 
-        let result = OneShot::new();
-        let op = ConnectCompletion {
+        let op = ConnectOp {
             addr,
             addr_len,
-            fd: sock.as_raw_fd(),
-            result: result.clone(),
+            target: sock.as_target(),
         };
-        let id = context::uring().register(op);
-
         Connect {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this connect, enforced by the kernel via a linked timeout. If the connect
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
@@ -93,11 +89,6 @@ where
 {
     type Output = io::Result<()>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -2,44 +2,72 @@ use std::{
     cmp::Ordering,
     io,
     marker::PhantomData,
-    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
     pin::Pin,
+    ptr,
     sync::mpsc::TryRecvError,
     task::{Context, Poll},
 };
 
 use futures::Stream;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{cqueue, opcode, squeue, types::DestinationSlot};
 
 use crate::{
     context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, Completion, CompletionStatus, Descriptor, Registration, Remote, Target},
     net::TcpStream,
     sync::{channel, Receiver, Sender},
 };
 
+/// A connection accepted by an [IncomingCompletion], either as a regular file descriptor or
+/// directly into a slot of the fixed file table of the ring. Either way the connection is closed
+/// should it be dropped without ever being handed out.
+enum Accepted {
+    Fd(OwnedFd),
+    Fixed(Descriptor),
+}
+
 struct IncomingCompletion {
-    fd: RawFd,
-    result: Sender<io::Result<OwnedFd>>,
+    target: Target,
+    direct: bool,
+    // The ring direct accepts are installed into, set when the stream is first polled.
+    ring: Option<Remote>,
+    multishot: bool,
+    result: Sender<io::Result<Accepted>>,
 }
 
 impl Completion for IncomingCompletion {
     fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
         let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(unsafe { OwnedFd::from_raw_fd(result) }),
+        let result = match (result.cmp(&0), &self.ring) {
+            (Ordering::Less, _) => Err(io::Error::from_raw_os_error(-result)),
+            (Ordering::Equal | Ordering::Greater, Some(ring)) if self.direct => Ok(
+                Accepted::Fixed(Descriptor::direct(ring.clone(), result as u32)),
+            ),
+            (Ordering::Equal | Ordering::Greater, _) => {
+                Ok(Accepted::Fd(unsafe { OwnedFd::from_raw_fd(result) }))
+            }
         };
 
         match self.result.push(result) {
             Err(_) => CompletionStatus::Finalized,
-            Ok(_) if cqueue::more(value.flags()) => CompletionStatus::Armed,
+            Ok(_) if self.multishot && cqueue::more(value.flags()) => CompletionStatus::Armed,
             Ok(_) => CompletionStatus::Rearm,
         }
     }
 
     fn as_entry(&mut self) -> squeue::Entry {
-        opcode::AcceptMulti::new(types::Fd(self.fd)).build()
+        let (fd, flags) = self.target.resolve();
+        if !self.multishot {
+            return opcode::Accept::new(fd, ptr::null_mut(), ptr::null_mut())
+                .file_index(self.direct.then(DestinationSlot::auto_target))
+                .build()
+                .flags(flags);
+        }
+        opcode::AcceptMulti::new(fd)
+            .allocate_file_index(self.direct)
+            .build()
+            .flags(flags)
     }
 }
 
@@ -49,40 +77,67 @@ impl Completion for IncomingCompletion {
 ///
 /// Note this future is meant to be reused, so ensure that when in use that its lifetime extends
 /// beyond any loops in use.
+///
+/// On kernels without [crate::io_uring::Capabilities::multishot_accept] this falls back to
+/// issuing a single-shot accept for each connection.
 pub struct Incoming<'a, T> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    stream: Receiver<io::Result<OwnedFd>>,
-}
-
-impl<'a, T> Drop for Incoming<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+    registration: Registration<IncomingCompletion>,
+    stream: Receiver<io::Result<Accepted>>,
 }
 
 impl<'a, T> Incoming<'a, T>
 where
     T: AsRawFd,
 {
-    pub(crate) fn new(listener: &'a mut T) -> Incoming<'a, T> {
+    pub(crate) fn new(listener: &'a mut T) -> Incoming<'a, T>
+    where
+        T: AsTarget,
+    {
         let (tx, rx) = channel();
         let op = IncomingCompletion {
-            fd: listener.as_raw_fd(),
+            target: listener.as_target(),
+            direct: false,
+            ring: None,
+            multishot: context::uring().capabilities().multishot_accept(),
             result: tx,
         };
-        let id = context::uring().register(op);
+        let registration = Registration::new(op);
 
         Incoming {
             inner: PhantomData,
-            id,
+            registration,
             stream: rx,
         }
     }
 
+    /// Accept connections directly into the fixed file table of the ring of the worker that
+    /// polls this stream, skipping the installation of a regular file descriptor altogether. This
+    /// saves a file table update per connection and per event on the connection, at the cost of
+    /// the resulting [TcpStream]s only being usable from the worker that accepted them, and having
+    /// no regular file descriptor, so calls such as [TcpStream::peer_addr] will fail.
+    ///
+    /// This is best paired with [crate::executor::ThreadPoolBuilder::thread_per_core] where tasks
+    /// never migrate between workers. Note that this has no effect once the stream has been
+    /// polled, and that the stream yields `ENFILE` errors once the table is full or if it is
+    /// disabled via [crate::io_uring::UringBuilder::fixed_files].
+    pub fn direct(mut self) -> Self {
+        if let Some(op) = self.registration.get_mut() {
+            op.direct = true;
+        }
+        self
+    }
+
     fn set_waker(&mut self, cx: &mut Context<'_>) {
         self.stream.set_waker(cx.waker().clone());
     }
+
+    fn accepted(accepted: Accepted) -> TcpStream {
+        match accepted {
+            Accepted::Fd(fd) => TcpStream::from(fd),
+            Accepted::Fixed(descriptor) => TcpStream::from(descriptor),
+        }
+    }
 }
 
 impl<'a, T> Stream for Incoming<'a, T>
@@ -91,11 +146,104 @@ where
 {
     type Item = io::Result<TcpStream>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Direct accepts land in the ring we are about to register with.
+        if let Some(op) = self.registration.get_mut().filter(|op| op.direct) {
+            op.ring = Some(context::uring().remote());
+        }
+        if self.registration.poll_register(cx).is_pending() {
+            return Poll::Pending;
+        }
         self.set_waker(cx);
         match self.stream.try_recv() {
-            Ok(val) => Poll::Ready(Some(val.map(TcpStream::from))),
+            Ok(val) => Poll::Ready(Some(val.map(Self::accepted))),
             Err(TryRecvError::Empty) => Poll::Pending,
             Err(TryRecvError::Disconnected) => Poll::Ready(None),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        thread,
+        time::Duration,
+    };
+
+    use futures::{poll, StreamExt};
+
+    use crate::{executor::block_on, net::TcpListener, time};
+
+    #[test]
+    fn test_incoming_direct() {
+        let mut listener = TcpListener::new("127.0.0.1", 0).unwrap();
+        assert!(listener.is_fixed());
+        let addr = listener.local_addr();
+
+        let client = thread::spawn(move || {
+            let mut conn = std::net::TcpStream::connect(addr).unwrap();
+            conn.write_all(b"ping").unwrap();
+            let mut buf = [0u8; 4];
+            conn.read_exact(&mut buf).unwrap();
+            buf
+        });
+
+        block_on(async {
+            let mut incoming = listener.incoming().direct();
+            let mut conn = incoming.next().await.unwrap().unwrap();
+            assert!(conn.is_fixed());
+            assert!(conn.try_peer_addr().is_err());
+
+            let (read, buf) = conn.recv(vec![0u8; 4]).await;
+            assert_eq!(read.unwrap(), 4);
+            assert_eq!(&buf, b"ping");
+            assert_eq!(conn.send(b"pong").await.0.unwrap(), 4);
+        });
+        assert_eq!(&client.join().unwrap(), b"pong");
+    }
+
+    #[test]
+    fn test_incoming_direct_dropped() {
+        let mut listener = TcpListener::new("127.0.0.1", 0).unwrap();
+        let addr = listener.local_addr();
+        let mut client = std::net::TcpStream::connect(addr).unwrap();
+
+        block_on(async {
+            let mut incoming = listener.incoming().direct();
+            assert!(poll!(incoming.next()).is_pending());
+
+            // Let the connection be accepted into the stream, and drop it without taking it.
+            time::sleep(Duration::from_millis(50)).await;
+            drop(incoming);
+            time::sleep(Duration::from_millis(50)).await;
+        });
+
+        // The slot holding the connection must have been released, closing it.
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(client.read(&mut [0u8; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_incoming_single_shot() {
+        let mut listener = TcpListener::new("127.0.0.1", 0).unwrap();
+        let addr = listener.local_addr();
+
+        let clients = thread::spawn(move || {
+            (0..3)
+                .map(|_| std::net::TcpStream::connect(addr).unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        block_on(async {
+            let mut incoming = listener.incoming();
+            incoming.registration.get_mut().unwrap().multishot = false;
+            for _ in 0..3 {
+                let conn = incoming.next().await.unwrap().unwrap();
+                assert_eq!(conn.peer_addr().ip(), addr.ip());
+            }
+        });
+        clients.join().unwrap();
+    }
+}
@@ -1,19 +1,33 @@
 mod accept;
 mod connect;
 mod incoming;
+mod readfixed;
 mod recv;
+mod recvbuf;
 mod recvfrom;
+mod recvfrombuf;
 mod recvmsg;
+mod recvstream;
 mod send;
 mod sendmsg;
 mod sendto;
+mod sendtozc;
+mod sendzc;
+mod writefixed;
 
 pub use accept::Accept;
 pub use connect::Connect;
 pub use incoming::Incoming;
+pub use readfixed::ReadFixed;
 pub use recv::Recv;
+pub use recvbuf::RecvBuf;
 pub use recvfrom::RecvFrom;
+pub use recvfrombuf::RecvFromBuf;
 pub use recvmsg::RecvMsg;
+pub use recvstream::RecvStream;
 pub use send::Send;
 pub use sendmsg::SendMsg;
 pub use sendto::SendTo;
+pub use sendtozc::SendToZc;
+pub use sendzc::SendZc;
+pub use writefixed::WriteFixed;
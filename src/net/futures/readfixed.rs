@@ -0,0 +1,158 @@
+use std::{
+    io,
+    marker::PhantomData,
+    os::fd::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{opcode, squeue};
+
+use crate::io_uring::{AsTarget, FixedBuf, FixedBufPool, Op, Operation, Target};
+
+struct ReadFixedOp {
+    target: Target,
+    buf: FixedBuf,
+    pool: FixedBufPool,
+    index: u16,
+    offset: u64,
+}
+
+// SAFETY: The fixed buffer is owned by the operation, and stays registered with the ring via the
+// pool the operation holds on to.
+unsafe impl Operation for ReadFixedOp {
+    type Output = (io::Result<usize>, FixedBuf);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        let buf = self.pool.buf_ptr(self.index);
+        let len = self.pool.buf_size() as u32;
+        match self.pool.fixed_index(self.index) {
+            Some(index) => opcode::ReadFixed::new(fd, buf, len, index)
+                .offset(self.offset)
+                .build()
+                .flags(flags),
+            None => opcode::Read::new(fd, buf, len)
+                .offset(self.offset)
+                .build()
+                .flags(flags),
+        }
+    }
+
+    fn complete(mut self, result: io::Result<u32>, _: u32) -> Self::Output {
+        let result = result.map(|len| len as usize);
+        if let Ok(len) = result {
+            self.buf.set_len(len);
+        }
+        (result, self.buf)
+    }
+}
+
+/// This represents a single use asynchronous read into a [FixedBuf], it takes ownership of the
+/// buffer and will read up to its capacity, ultimately returning the amount of data read, which is
+/// also set as the length of the buffer, along with the buffer.
+///
+/// The read is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct ReadFixed<'a, T> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<ReadFixedOp>,
+}
+
+impl<'a, T> ReadFixed<'a, T>
+where
+    T: AsRawFd,
+{
+    pub(crate) fn new(io: &'a mut T, buf: FixedBuf, offset: u64) -> ReadFixed<'a, T>
+    where
+        T: AsTarget,
+    {
+        let op = ReadFixedOp {
+            target: io.as_target(),
+            pool: buf.pool().clone(),
+            index: buf.index(),
+            offset,
+            buf,
+        };
+        ReadFixed {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this read, enforced by the kernel via a linked timeout. If the read
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T> Future for ReadFixed<'a, T>
+where
+    T: AsRawFd,
+{
+    type Output = (io::Result<usize>, FixedBuf);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::{
+        executor::block_on,
+        io_uring::{FixedBufPool, UringDriver},
+        net::TcpStream,
+    };
+
+    #[test]
+    fn test_fixed_buffers() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let pool = FixedBufPool::new(2, 16).unwrap();
+        assert!(FixedBufPool::current().is_some());
+        let buf = pool.checkout().unwrap();
+        let out = pool.checkout().unwrap();
+        assert!(pool.checkout().is_none());
+        drop(out);
+        assert_eq!(pool.available(), 1);
+
+        block_on(async {
+            peer.write_all(b"hello").unwrap();
+            let (read, mut buf) = stream.read_fixed(buf).await;
+            assert_eq!(read.unwrap(), 5);
+            assert_eq!(&buf[..], b"hello");
+
+            buf.extend_from_slice(b" world");
+            let (written, buf) = stream.write_fixed(buf).await;
+            assert_eq!(written.unwrap(), 11);
+            let (sent, _) = stream.send_fixed(buf).await;
+            assert_eq!(sent.unwrap(), 11);
+        });
+
+        let mut data = [0u8; 22];
+        peer.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello worldhello world");
+
+        // A ring only takes a single pool at a time.
+        let mut driver = UringDriver::new(8).unwrap();
+        let other = driver.register_buffers(1, 8).unwrap();
+        assert!(driver.register_buffers(1, 8).is_err());
+        driver.unregister_buffers().unwrap();
+        assert!(driver.fixed_buffers().is_none());
+        drop(other);
+    }
+}
@@ -1,100 +1,173 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
+use nix::libc;
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBufMut, Op, Operation, Target},
     ptr::SendMut,
-    sync::OneShot,
 };
 
-struct RecvCompletion {
-    fd: RawFd,
-    buf: SendMut<u8>,
+struct RecvOp<B> {
+    target: Target,
+    buf: B,
+    buf_ptr: SendMut<u8>,
     buf_len: u32,
-    result: OneShot<io::Result<usize>>,
 }
 
-impl Completion for RecvCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(result as usize),
-        };
+// SAFETY: The buffer is an [IoBufMut] owned by the operation, so its memory doesn't move.
+unsafe impl<B> Operation for RecvOp<B>
+where
+    B: IoBufMut,
+{
+    type Output = (io::Result<usize>, B);
 
-        self.result.complete(result);
-        CompletionStatus::Finalized
+    fn as_entry(&mut self) -> squeue::Entry {
+        // A receive into an empty buffer would complete straight away, indistinguishable from the
+        // remote host closing the connection, so it is rejected without reaching the socket.
+        if self.buf_len == 0 {
+            return opcode::Nop::new().build();
+        }
+        let (fd, flags) = self.target.resolve();
+        opcode::Recv::new(fd, self.buf_ptr.to_ptr(), self.buf_len)
+            .build()
+            .flags(flags)
     }
 
-    fn as_entry(&mut self) -> squeue::Entry {
-        opcode::Recv::new(types::Fd(self.fd), self.buf.to_ptr(), self.buf_len).build()
+    fn complete(mut self, result: io::Result<u32>, _: u32) -> Self::Output {
+        if self.buf_len == 0 {
+            return (Err(io::Error::from_raw_os_error(libc::EINVAL)), self.buf);
+        }
+        let result = result.map(|len| len as usize);
+        if let Ok(len) = result {
+            // SAFETY: The kernel initialized the first `len` bytes of the buffer.
+            unsafe { self.buf.set_init(len) };
+        }
+        (result, self.buf)
     }
 }
 
-/// This represents a single use asynchronous receive on a connected [TcpStream], it will use the
-/// given buffer to read data into, and ultimately return the amount of data read and whether or
-/// not ther was still data in the socket after the receive completed.
-pub struct Recv<'a, T> {
+/// This represents a single use asynchronous receive on a connected [crate::net::TcpStream], it
+/// takes ownership of the given buffer to read data into, and ultimately returns the amount of
+/// data read along with the buffer.
+///
+/// The data is read into the full capacity of the buffer, see [IoBufMut], and a buffer with no
+/// capacity at all such as [Vec::new] fails with `EINVAL`.
+///
+/// The receive is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct Recv<'a, T, B> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<usize>>,
-}
-
-impl<'a, T> Drop for Recv<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+    op: Op<RecvOp<B>, (io::Result<usize>, B)>,
 }
 
-impl<'a, T> Recv<'a, T>
+impl<'a, T, B> Recv<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    pub(crate) fn new<'buf>(stream: &'a mut T, buf: &'buf mut [u8]) -> Recv<'a, T> {
-        let result = OneShot::new();
-        let buf_len = buf.len() as u32;
-        let buf = unsafe { SendMut::new(buf.as_mut_ptr()) };
+    pub(crate) fn new(stream: &'a mut T, mut buf: B) -> Recv<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let buf_len = buf.bytes_total() as u32;
+        let buf_ptr = unsafe { SendMut::new(buf.stable_mut_ptr()) };
 
-        let op = RecvCompletion {
-            fd: stream.as_raw_fd(),
+        let op = RecvOp {
+            target: stream.as_target(),
             buf,
+            buf_ptr,
             buf_len,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         Recv {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this receive, enforced by the kernel via a linked timeout. If the receive
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for Recv<'a, T>
+impl<'a, T, B> Future for Recv<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    type Output = io::Result<usize>;
+    type Output = (io::Result<usize>, B);
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Write};
+
+    use futures::join;
+
+    use crate::{executor::block_on, net::TcpStream, time};
+
+    use super::*;
+
+    #[test]
+    fn test_recv_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (_peer, _) = listener.accept().unwrap();
+
+        let buf = vec![0u8; 16];
+        let (result, buf) = block_on(stream.recv(buf).timeout(Duration::from_millis(50)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(buf.len(), 16);
+
+        // The buffer is handed back once the kernel confirms the cancelation on the paused clock
+        // as well.
+        let (result, buf) = block_on(async {
+            time::pause();
+            let recv = stream.recv(buf).timeout(Duration::from_secs(60));
+            let (result, _) = join!(recv, time::advance(Duration::from_secs(120)));
+            time::resume();
+            result
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(buf.len(), 16);
+    }
+
+    #[test]
+    fn test_recv_capacity() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+        peer.write_all(b"hello").unwrap();
+
+        // An empty buffer can't be told apart from the connection closing, so it is rejected.
+        let (result, _) = block_on(stream.recv(Vec::new()));
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EINVAL));
+
+        let (result, buf) = block_on(stream.recv(Vec::with_capacity(16)));
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(&buf[..], b"hello");
     }
 }
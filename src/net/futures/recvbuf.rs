@@ -0,0 +1,150 @@
+use std::{
+    io,
+    marker::PhantomData,
+    os::fd::AsRawFd,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{cqueue, opcode, squeue, squeue::Flags};
+
+use crate::io_uring::{AsTarget, BorrowedBuffer, BufRing, Op, Operation, Target};
+
+struct RecvBufOp {
+    target: Target,
+    ring: BufRing,
+}
+
+// SAFETY: The buffer is picked by the kernel from the buffer ring the operation holds on to.
+unsafe impl Operation for RecvBufOp {
+    type Output = io::Result<BorrowedBuffer>;
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::Recv::new(fd, ptr::null_mut(), self.ring.buf_size() as u32)
+            .buf_group(self.ring.bgid())
+            .build()
+            .flags(flags | Flags::BUFFER_SELECT)
+    }
+
+    fn complete(self, result: io::Result<u32>, flags: u32) -> Self::Output {
+        result.map(|len| self.ring.borrow(cqueue::buffer_select(flags), len as usize))
+    }
+}
+
+/// This represents a single use asynchronous receive into a buffer picked by the kernel from a
+/// [BufRing] once data arrives, returning the received data as a [BorrowedBuffer]. An empty
+/// [BorrowedBuffer] signals that the remote host closed the connection.
+///
+/// Note that the receive fails with `ENOBUFS` if the [BufRing] has no buffers left when data
+/// arrives, and with an [std::io::ErrorKind::Other] error if polled on a thread other than the
+/// one that owns the [BufRing].
+///
+/// The receive is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct RecvBuf<'a, T> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<RecvBufOp>,
+}
+
+impl<'a, T> RecvBuf<'a, T>
+where
+    T: AsRawFd,
+{
+    pub(crate) fn new(stream: &'a mut T, ring: &BufRing) -> RecvBuf<'a, T>
+    where
+        T: AsTarget,
+    {
+        let op = RecvBufOp {
+            target: stream.as_target(),
+            ring: ring.clone(),
+        };
+        RecvBuf {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this receive, enforced by the kernel via a linked timeout. If the receive
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T> Future for RecvBuf<'a, T>
+where
+    T: AsRawFd,
+{
+    type Output = io::Result<BorrowedBuffer>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The buffer group only exists on the ring that owns it, so make sure that is the ring
+        // we are about to be registered with.
+        if let Some(op) = self.op.get_mut() {
+            if let Err(err) = op.ring.check_owner() {
+                return Poll::Ready(Err(err));
+            }
+        }
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, thread};
+
+    use crate::{executor::block_on, net::TcpStream};
+
+    use super::*;
+
+    #[test]
+    fn test_recv_buf() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let ring = BufRing::new(2, 8).unwrap();
+        block_on(async {
+            // Hold on to both buffers, so the ring runs dry.
+            peer.write_all(b"hello").unwrap();
+            let first = stream.recv_buf(&ring).await.unwrap();
+            assert_eq!(&first[..], b"hello");
+
+            peer.write_all(b"0123456789").unwrap();
+            let second = stream.recv_buf(&ring).await.unwrap();
+            assert_eq!(&second[..], b"01234567");
+
+            let err = stream.recv_buf(&ring).await.unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(nix::libc::ENOBUFS));
+
+            // Returning a buffer makes it available to the kernel again.
+            drop(first);
+            let third = stream.recv_buf(&ring).await.unwrap();
+            assert_eq!(&third[..], b"89");
+
+            drop((second, third));
+            drop(peer);
+            assert!(stream.recv_buf(&ring).await.unwrap().is_empty());
+        });
+
+        // A ring can't be used from another thread.
+        let other = ring.clone();
+        thread::spawn(move || {
+            let mut stream = TcpStream::new(true).unwrap();
+            let err = block_on(stream.recv_buf(&other)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Other);
+        })
+        .join()
+        .unwrap();
+    }
+}
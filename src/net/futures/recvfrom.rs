@@ -1,81 +1,102 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     net::SocketAddr,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
+use nix::libc;
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBufMut, Op, Operation, Target},
     net::{IoVec, MsgHdr, SocketAddrC},
     ptr::SendMut,
-    sync::OneShot,
 };
 
-struct RecvFromCompletion {
-    fd: RawFd,
+/// The output of a [RecvFrom], the result of the receive along with the buffer read into.
+type RecvFromOutput<B> = (io::Result<(usize, SocketAddr)>, B);
+
+struct RecvFromOp<B> {
+    target: Target,
+    buf: B,
     addr: Pin<Box<SocketAddrC>>,
     iovecs: Pin<Vec<IoVec>>,
     hdr: Pin<Box<MsgHdr>>,
-    result: OneShot<io::Result<(usize, SocketAddr)>>,
 }
 
-impl Completion for RecvFromCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok((result as usize, self.addr.as_std())),
-        };
-
-        assert!(self.iovecs.len() > 0);
-        self.result.complete(result);
-        CompletionStatus::Finalized
+impl<B> RecvFromOp<B> {
+    fn is_empty(&self) -> bool {
+        self.iovecs.iter().all(|iovec| iovec.iov_len == 0)
     }
+}
+
+// SAFETY: The buffer is an [IoBufMut] owned by the operation, and the address, iovecs and header
+// live on the heap, owned by the operation.
+unsafe impl<B> Operation for RecvFromOp<B>
+where
+    B: IoBufMut,
+{
+    type Output = RecvFromOutput<B>;
 
     fn as_entry(&mut self) -> squeue::Entry {
-        opcode::RecvMsg::new(types::Fd(self.fd), self.hdr.as_mut_ptr()).build()
+        // A receive into empty buffers would silently discard a datagram, so it is rejected
+        // without reaching the socket.
+        if self.is_empty() {
+            return opcode::Nop::new().build();
+        }
+        let (fd, flags) = self.target.resolve();
+        opcode::RecvMsg::new(fd, self.hdr.as_mut_ptr())
+            .build()
+            .flags(flags)
     }
-}
 
-/// This represents a single use asynchronous receive from operation, this will return both the
-/// number of bytes read as well as the socket address that the data was received from.
-pub struct RecvFrom<'a, T> {
-    inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<(usize, SocketAddr)>>,
+    fn complete(mut self, result: io::Result<u32>, _: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
+        if self.is_empty() {
+            return (Err(io::Error::from_raw_os_error(libc::EINVAL)), self.buf);
+        }
+        let result = result.map(|len| (len as usize, self.addr.as_std()));
+        if let Ok((len, _)) = result {
+            // SAFETY: The kernel initialized the first `len` bytes of the buffer.
+            unsafe { self.buf.set_init(len) };
+        }
+        (result, self.buf)
+    }
 }
 
-impl<'a, T> Drop for RecvFrom<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+/// This represents a single use asynchronous receive from operation, it takes ownership of the
+/// given buffer to read data into, and will return both the number of bytes read as well as the
+/// socket address that the data was received from along with the buffer.
+///
+/// The receive is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct RecvFrom<'a, T, B> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<RecvFromOp<B>, RecvFromOutput<B>>,
 }
 
-impl<'a, T> RecvFrom<'a, T>
+impl<'a, T, B> RecvFrom<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    pub(crate) fn new<'b>(sock: &'a mut T, buf: &'b mut [u8]) -> RecvFrom<'a, T> {
-        let result = OneShot::new();
-        let buf_len = buf.len();
-        let buf = unsafe { SendMut::new(buf.as_mut_ptr() as _) };
+    pub(crate) fn new(sock: &'a mut T, mut buf: B) -> RecvFrom<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let iov_len = buf.bytes_total();
+        let iov_base = unsafe { SendMut::new(buf.stable_mut_ptr() as _) };
 
         let (addr, addr_len) = SocketAddrC::new();
         let mut addr = Box::pin(addr);
 
-        let iovecs = vec![IoVec {
-            iov_base: buf,
-            iov_len: buf_len,
-        }];
+        let iovecs = vec![IoVec { iov_base, iov_len }];
         let mut iovecs = Pin::new(iovecs);
 
         let hdr = MsgHdr {
@@ -89,37 +110,37 @@ where
         };
         let hdr = Box::pin(hdr);
 
-        let op = RecvFromCompletion {
-            fd: sock.as_raw_fd(),
+        let op = RecvFromOp {
+            target: sock.as_target(),
+            buf,
             addr,
             iovecs,
             hdr,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         RecvFrom {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this receive, enforced by the kernel via a linked timeout. If the receive
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for RecvFrom<'a, T>
+impl<'a, T, B> Future for RecvFrom<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    type Output = io::Result<(usize, SocketAddr)>;
+    type Output = RecvFromOutput<B>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -0,0 +1,153 @@
+use std::{
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    os::fd::AsRawFd,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{cqueue, opcode, squeue, squeue::Flags};
+
+use crate::{
+    io_uring::{AsTarget, BorrowedBuffer, BufRing, Op, Operation, Target},
+    net::{IoVec, MsgHdr, SocketAddrC},
+    ptr::SendMut,
+};
+
+struct RecvFromBufOp {
+    target: Target,
+    ring: BufRing,
+    addr: Pin<Box<SocketAddrC>>,
+    iovecs: Pin<Vec<IoVec>>,
+    hdr: Pin<Box<MsgHdr>>,
+}
+
+// SAFETY: The address, iovecs and header live on the heap, owned by the operation, and the buffer
+// is picked by the kernel from the buffer ring the operation holds on to.
+unsafe impl Operation for RecvFromBufOp {
+    type Output = io::Result<(BorrowedBuffer, SocketAddr)>;
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::RecvMsg::new(fd, self.hdr.as_mut_ptr())
+            .buf_group(self.ring.bgid())
+            .build()
+            .flags(flags | Flags::BUFFER_SELECT)
+    }
+
+    fn complete(self, result: io::Result<u32>, flags: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
+        result.map(|len| {
+            let buf = self.ring.borrow(cqueue::buffer_select(flags), len as usize);
+            (buf, self.addr.as_std())
+        })
+    }
+}
+
+/// This represents a single use asynchronous receive from operation into a buffer picked by the
+/// kernel from a [BufRing] once a datagram arrives, returning both the received data as a
+/// [BorrowedBuffer] and the socket address that the data was received from.
+///
+/// Note that datagrams larger than [BufRing::buf_size] are truncated, and the same caveats as
+/// [super::RecvBuf] apply.
+///
+/// The receive is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct RecvFromBuf<'a, T> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<RecvFromBufOp>,
+}
+
+impl<'a, T> RecvFromBuf<'a, T>
+where
+    T: AsRawFd,
+{
+    pub(crate) fn new(sock: &'a mut T, ring: &BufRing) -> RecvFromBuf<'a, T>
+    where
+        T: AsTarget,
+    {
+        let (addr, addr_len) = SocketAddrC::new();
+        let mut addr = Box::pin(addr);
+
+        // With buffer selection the kernel fills in the base of the single iovec, we only need to
+        // provide the maximum length to receive.
+        let iovecs = vec![IoVec {
+            iov_base: unsafe { SendMut::new(ptr::null_mut()) },
+            iov_len: ring.buf_size(),
+        }];
+        let mut iovecs = Pin::new(iovecs);
+
+        let hdr = MsgHdr {
+            msg_name: unsafe { SendMut::new(addr.as_mut_ptr() as _) },
+            msg_namelen: addr_len,
+            msg_iov: unsafe { SendMut::new(iovecs.as_mut_ptr()) },
+            msg_iovlen: iovecs.len(),
+            msg_control: unsafe { SendMut::new(ptr::null_mut()) },
+            msg_controllen: 0,
+            msg_flags: 0,
+        };
+        let hdr = Box::pin(hdr);
+
+        let op = RecvFromBufOp {
+            target: sock.as_target(),
+            ring: ring.clone(),
+            addr,
+            iovecs,
+            hdr,
+        };
+        RecvFromBuf {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this receive, enforced by the kernel via a linked timeout. If the receive
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T> Future for RecvFromBuf<'a, T>
+where
+    T: AsRawFd,
+{
+    type Output = io::Result<(BorrowedBuffer, SocketAddr)>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The buffer group only exists on the ring that owns it, so make sure that is the ring
+        // we are about to be registered with.
+        if let Some(op) = self.op.get_mut() {
+            if let Err(err) = op.ring.check_owner() {
+                return Poll::Ready(Err(err));
+            }
+        }
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{executor::block_on, net::UdpSocket};
+
+    use super::*;
+
+    #[test]
+    fn test_recv_from_buf() {
+        let mut sock = UdpSocket::new("127.0.0.1", 0).unwrap();
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.send_to(b"datagram", sock.local_addr()).unwrap();
+
+        let ring = BufRing::new(4, 4).unwrap();
+        let (buf, addr) = block_on(sock.recv_from_buf(&ring)).unwrap();
+        assert_eq!(&buf[..], b"data");
+        assert_eq!(addr, peer.local_addr().unwrap());
+    }
+}
@@ -1,82 +1,111 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     net::SocketAddr,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
+use nix::libc;
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBufMut, Op, Operation, Target},
     net::{IoVec, MsgHdr, SocketAddrC},
     ptr::SendMut,
-    sync::OneShot,
 };
 
-struct RecvMsgCompletion {
-    fd: RawFd,
+/// The output of a [RecvMsg], the result of the receive along with the buffers read into.
+type RecvMsgOutput<B> = (io::Result<(usize, SocketAddr)>, Vec<B>);
+
+struct RecvMsgOp<B> {
+    target: Target,
+    bufs: Vec<B>,
     addr: Pin<Box<SocketAddrC>>,
     iovecs: Pin<Vec<IoVec>>,
     hdr: Pin<Box<MsgHdr>>,
-    result: OneShot<io::Result<(usize, SocketAddr)>>,
 }
 
-impl Completion for RecvMsgCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok((result as usize, self.addr.as_std())),
-        };
-
-        assert!(self.iovecs.len() > 0);
-        self.result.complete(result);
-        CompletionStatus::Finalized
+impl<B> RecvMsgOp<B> {
+    fn is_empty(&self) -> bool {
+        self.iovecs.iter().all(|iovec| iovec.iov_len == 0)
     }
+}
+
+// SAFETY: The buffers are [IoBufMut]s owned by the operation, and the address, iovecs and header
+// live on the heap, owned by the operation.
+unsafe impl<B> Operation for RecvMsgOp<B>
+where
+    B: IoBufMut,
+{
+    type Output = RecvMsgOutput<B>;
 
     fn as_entry(&mut self) -> squeue::Entry {
-        opcode::RecvMsg::new(types::Fd(self.fd), self.hdr.as_mut_ptr()).build()
+        // A receive into empty buffers would silently discard a datagram, so it is rejected
+        // without reaching the socket.
+        if self.is_empty() {
+            return opcode::Nop::new().build();
+        }
+        let (fd, flags) = self.target.resolve();
+        opcode::RecvMsg::new(fd, self.hdr.as_mut_ptr())
+            .build()
+            .flags(flags)
     }
-}
 
-/// This represents a single use asynchronous receive message operation. This will return the total
-/// numbe of bytes read in across all supplied vectors, and the socket address the data was
-/// received from. Users should read data from the first supplied buffer and continue until all
-/// read data has been handled.
-pub struct RecvMsg<'a, T> {
-    inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<(usize, SocketAddr)>>,
+    fn complete(mut self, result: io::Result<u32>, _: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
+        if self.is_empty() {
+            return (Err(io::Error::from_raw_os_error(libc::EINVAL)), self.bufs);
+        }
+        let result = result.map(|len| (len as usize, self.addr.as_std()));
+        if let Ok((len, _)) = result {
+            // The kernel fills the buffers in order.
+            let mut remaining = len;
+            for buf in self.bufs.iter_mut() {
+                let filled = remaining.min(buf.bytes_total());
+                // SAFETY: The kernel initialized the first `filled` bytes of the buffer.
+                unsafe { buf.set_init(filled) };
+                remaining -= filled;
+            }
+        }
+        (result, self.bufs)
+    }
 }
 
-impl<'a, T> Drop for RecvMsg<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+/// This represents a single use asynchronous receive message operation, it takes ownership of the
+/// given buffers to read data into. This will return the total numbe of bytes read in across all
+/// supplied buffers, and the socket address the data was received from, along with the buffers.
+/// Users should read data from the first supplied buffer and continue until all read data has
+/// been handled.
+///
+/// The receive is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct RecvMsg<'a, T, B> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<RecvMsgOp<B>, RecvMsgOutput<B>>,
 }
 
-impl<'a, T> RecvMsg<'a, T>
+impl<'a, T, B> RecvMsg<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    pub(crate) fn new<'b>(sock: &'a mut T, bufs: &'b mut [Vec<u8>]) -> RecvMsg<'a, T> {
-        let result = OneShot::new();
-
+    pub(crate) fn new(sock: &'a mut T, mut bufs: Vec<B>) -> RecvMsg<'a, T, B>
+    where
+        T: AsTarget,
+    {
         let (addr, addr_len) = SocketAddrC::new();
         let mut addr = Box::pin(addr);
 
         let mut iovecs = Vec::with_capacity(bufs.len());
         for buf in bufs.iter_mut() {
             iovecs.push(IoVec {
-                iov_base: unsafe { SendMut::new(buf.as_mut_ptr() as _) },
-                iov_len: buf.len(),
+                iov_base: unsafe { SendMut::new(buf.stable_mut_ptr() as _) },
+                iov_len: buf.bytes_total(),
             })
         }
         let mut iovecs = Pin::new(iovecs);
@@ -92,37 +121,37 @@ where
         };
         let hdr = Box::pin(hdr);
 
-        let op = RecvMsgCompletion {
-            fd: sock.as_raw_fd(),
+        let op = RecvMsgOp {
+            target: sock.as_target(),
+            bufs,
             addr,
             iovecs,
             hdr,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         RecvMsg {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this receive, enforced by the kernel via a linked timeout. If the receive
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for RecvMsg<'a, T>
+impl<'a, T, B> Future for RecvMsg<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBufMut,
 {
-    type Output = io::Result<(usize, SocketAddr)>;
+    type Output = RecvMsgOutput<B>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -0,0 +1,227 @@
+use std::{
+    cmp::Ordering,
+    io,
+    marker::PhantomData,
+    os::fd::AsRawFd,
+    pin::Pin,
+    sync::mpsc::TryRecvError,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use io_uring::{cqueue, opcode, squeue};
+use nix::libc;
+
+use crate::{
+    io_uring::{
+        AsTarget, BorrowedBuffer, BufRing, Completion, CompletionStatus, Registration, Target,
+    },
+    sync::{channel, Receiver, Sender},
+};
+
+struct RecvStreamCompletion {
+    target: Target,
+    ring: BufRing,
+    result: Sender<io::Result<BorrowedBuffer>>,
+}
+
+impl Completion for RecvStreamCompletion {
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        let result = value.result();
+        let buf = self
+            .ring
+            .borrow(cqueue::buffer_select(value.flags()), result.max(0) as usize);
+
+        let result = match result.cmp(&0) {
+            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
+            // The remote host closed the connection, so we are done. The kernel may still have
+            // picked a buffer for the empty receive, which is returned to the ring on drop.
+            Ordering::Equal => return CompletionStatus::Finalized,
+            Ordering::Greater => Ok(buf),
+        };
+
+        // Errors stop the receive for good, with the exception of the ring running dry. Rearming
+        // then would fail straight away until some buffers are returned, so the stream takes care
+        // of rearming in that case instead.
+        let failed = result.is_err();
+        match self.result.push(result) {
+            Err(_) => CompletionStatus::Finalized,
+            Ok(_) if cqueue::more(value.flags()) => CompletionStatus::Armed,
+            Ok(_) if failed => CompletionStatus::Finalized,
+            Ok(_) => CompletionStatus::Rearm,
+        }
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::RecvMulti::new(fd, self.ring.bgid())
+            .build()
+            .flags(flags)
+    }
+}
+
+/// This represents a stream of data received from the remote host into buffers picked by the
+/// kernel from a [BufRing], built on a single multi-shot receive that is automatically re-armed
+/// whenever the kernel stops it. The stream ends once the remote host closes the connection, or
+/// after an unrecoverable error is returned.
+///
+/// If the [BufRing] runs dry while data is arriving the stream waits for a [BorrowedBuffer] to be
+/// dropped, and then picks back up where it left off. Holding on to every buffer of the ring while
+/// waiting on the stream will therefore never complete.
+///
+/// Note that this stream is meant to be reused, so ensure that when in use that its lifetime
+/// extends beyond any loops in use, and that the stream yields an [std::io::ErrorKind::Other]
+/// error if polled on a thread other than the one that owns the [BufRing].
+pub struct RecvStream<'a, T> {
+    inner: PhantomData<&'a mut T>,
+    target: Target,
+    ring: BufRing,
+    registration: Registration<RecvStreamCompletion>,
+    stream: Receiver<io::Result<BorrowedBuffer>>,
+    generation: u64,
+    starved: bool,
+    done: bool,
+}
+
+impl<'a, T> RecvStream<'a, T>
+where
+    T: AsRawFd,
+{
+    pub(crate) fn new(stream: &'a mut T, ring: &BufRing) -> RecvStream<'a, T>
+    where
+        T: AsTarget,
+    {
+        let target = stream.as_target();
+        let (registration, stream) = Self::arm(target, ring);
+
+        RecvStream {
+            inner: PhantomData,
+            target,
+            ring: ring.clone(),
+            registration,
+            stream,
+            generation: 0,
+            starved: false,
+            done: false,
+        }
+    }
+
+    fn arm(
+        target: Target,
+        ring: &BufRing,
+    ) -> (
+        Registration<RecvStreamCompletion>,
+        Receiver<io::Result<BorrowedBuffer>>,
+    ) {
+        let (tx, rx) = channel();
+        let op = RecvStreamCompletion {
+            target,
+            ring: ring.clone(),
+            result: tx,
+        };
+        (Registration::new(op), rx)
+    }
+
+    fn set_waker(&mut self, cx: &mut Context<'_>) {
+        self.stream.set_waker(cx.waker().clone());
+    }
+}
+
+impl<'a, T> Stream for RecvStream<'a, T>
+where
+    T: AsRawFd,
+{
+    type Item = io::Result<BorrowedBuffer>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        // Rearming while the ring is dry would just fail again, so wait for a buffer first.
+        if self.starved {
+            if !self.ring.poll_recycled(self.generation, cx.waker()) {
+                return Poll::Pending;
+            }
+            self.starved = false;
+        }
+
+        // The buffer group only exists on the ring that owns it, so make sure that is the ring
+        // we are about to be registered with.
+        if self.registration.get_mut().is_some() {
+            if let Err(err) = self.ring.check_owner() {
+                self.done = true;
+                return Poll::Ready(Some(Err(err)));
+            }
+            self.generation = self.ring.generation();
+        }
+
+        if self.registration.poll_register(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.set_waker(cx);
+        match self.stream.try_recv() {
+            Ok(Err(err)) if err.raw_os_error() == Some(libc::ENOBUFS) => {
+                // The kernel stopped the receive since the ring ran dry, so queue up a fresh one
+                // to be submitted once a buffer has been returned.
+                let (registration, stream) = Self::arm(self.target, &self.ring);
+                self.registration = registration;
+                self.stream = stream;
+                self.starved = true;
+                self.poll_next(cx)
+            }
+            Ok(val) => Poll::Ready(Some(val)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, time::Duration};
+
+    use futures::StreamExt;
+
+    use crate::{executor::block_on, net::TcpStream, time};
+
+    use super::*;
+
+    #[test]
+    fn test_recv_stream() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let ring = BufRing::new(2, 8).unwrap();
+        block_on(async {
+            let mut recv = stream.recv_stream(&ring);
+
+            peer.write_all(b"hello").unwrap();
+            let first = recv.next().await.unwrap().unwrap();
+            assert_eq!(&first[..], b"hello");
+
+            // Hold on to both buffers, so the ring runs dry and the kernel stops the receive.
+            peer.write_all(b"0123456789").unwrap();
+            let second = recv.next().await.unwrap().unwrap();
+            assert_eq!(&second[..], b"01234567");
+            let starved = time::timeout(Duration::from_millis(100), recv.next()).await;
+            assert!(starved.is_err());
+
+            // Returning the buffers lets the stream pick back up where it left off.
+            drop((first, second));
+            let third = recv.next().await.unwrap().unwrap();
+            assert_eq!(&third[..], b"89");
+            drop(third);
+
+            drop(peer);
+            assert!(recv.next().await.is_none());
+            assert!(recv.next().await.is_none());
+        });
+    }
+}
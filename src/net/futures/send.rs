@@ -1,99 +1,98 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBuf, Op, Operation, Target},
     ptr::SendConst,
-    sync::OneShot,
 };
 
-struct SendCompletion {
-    fd: RawFd,
-    buf: SendConst<u8>,
+struct SendOp<B> {
+    target: Target,
+    buf: B,
+    buf_ptr: SendConst<u8>,
     buf_len: u32,
-    result: OneShot<io::Result<usize>>,
 }
 
-impl Completion for SendCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(result as usize),
-        };
+// SAFETY: The buffer is an [IoBuf] owned by the operation, so its memory doesn't move.
+unsafe impl<B> Operation for SendOp<B>
+where
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
 
-        self.result.complete(result);
-        CompletionStatus::Finalized
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::Send::new(fd, self.buf_ptr.to_ptr(), self.buf_len)
+            .build()
+            .flags(flags)
     }
 
-    fn as_entry(&mut self) -> squeue::Entry {
-        opcode::Send::new(types::Fd(self.fd), self.buf.to_ptr(), self.buf_len).build()
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        (result.map(|len| len as usize), self.buf)
     }
 }
 
-/// This represents a single use asynchronous send operation on a connected [TcpStream], it will
-/// use the given buffer to write data from, and ultimately return the amount of data written to
-/// the remote server.
-pub struct Send<'a, T> {
+/// This represents a single use asynchronous send operation on a connected
+/// [crate::net::TcpStream], it takes ownership of the given buffer to write data from, and
+/// ultimately returns the amount of data written to the remote server along with the buffer.
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct Send<'a, T, B> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<usize>>,
+    op: Op<SendOp<B>, (io::Result<usize>, B)>,
 }
 
-impl<'a, T> Drop for Send<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
-}
-
-impl<'a, T> Send<'a, T>
+impl<'a, T, B> Send<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    pub(crate) fn new<'buf>(stream: &'a mut T, buf: &'buf [u8]) -> Send<'a, T> {
-        let result = OneShot::new();
-        let buf_len = buf.len() as u32;
-        let buf = unsafe { SendConst::new(buf.as_ptr()) };
-        let op = SendCompletion {
-            fd: stream.as_raw_fd(),
+    pub(crate) fn new(stream: &'a mut T, buf: B) -> Send<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let buf_len = buf.bytes_init() as u32;
+        let buf_ptr = unsafe { SendConst::new(buf.stable_ptr()) };
+        let op = SendOp {
+            target: stream.as_target(),
             buf,
+            buf_ptr,
             buf_len,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         Send {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this send, enforced by the kernel via a linked timeout. If the send
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for Send<'a, T>
+impl<'a, T, B> Future for Send<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    type Output = io::Result<usize>;
+    type Output = (io::Result<usize>, B);
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -1,80 +1,76 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     net::SocketAddr,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBuf, Op, Operation, Target},
     net::{IoVec, MsgHdr, SocketAddrC},
     ptr::SendMut,
-    sync::OneShot,
 };
 
-struct SendMsgCompletion {
-    fd: RawFd,
+struct SendMsgOp<B> {
+    target: Target,
+    bufs: Vec<B>,
     addr: Option<Pin<Box<SocketAddrC>>>,
     iovecs: Pin<Vec<IoVec>>,
     hdr: Pin<Box<MsgHdr>>,
-    result: OneShot<io::Result<usize>>,
 }
 
-impl Completion for SendMsgCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(result as usize),
-        };
+// SAFETY: The buffers are [IoBuf]s owned by the operation, and the address, iovecs and header live
+// on the heap, owned by the operation.
+unsafe impl<B> Operation for SendMsgOp<B>
+where
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, Vec<B>);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::SendMsg::new(fd, self.hdr.as_mut_ptr())
+            .build()
+            .flags(flags)
+    }
 
-        assert!(self.iovecs.len() > 0);
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
         if let Some(addr) = &self.addr {
             assert!(addr.is_valid());
         }
-        self.result.complete(result);
-        CompletionStatus::Finalized
-    }
-
-    fn as_entry(&mut self) -> squeue::Entry {
-        opcode::SendMsg::new(types::Fd(self.fd), self.hdr.as_mut_ptr()).build()
+        (result.map(|len| len as usize), self.bufs)
     }
 }
 
-/// This represents a single use asynchronous send message operation. This will return the number
-/// of bytes sent from the supplied buffers. It is optional to supply the send to address on
-/// connected sockets.
-pub struct SendMsg<'a, T> {
+/// This represents a single use asynchronous send message operation, it takes ownership of the
+/// given buffers to send data from. This will return the number of bytes sent from the supplied
+/// buffers along with the buffers. It is optional to supply the send to address on connected
+/// sockets.
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct SendMsg<'a, T, B> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<usize>>,
-}
-
-impl<'a, T> Drop for SendMsg<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
+    op: Op<SendMsgOp<B>, (io::Result<usize>, Vec<B>)>,
 }
 
-impl<'a, T> SendMsg<'a, T>
+impl<'a, T, B> SendMsg<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    pub(crate) fn new<'b>(
-        sock: &'a mut T,
-        bufs: &'b mut [Vec<u8>],
-        addr: Option<&SocketAddr>,
-    ) -> SendMsg<'a, T> {
-        let result = OneShot::new();
-
+    pub(crate) fn new(sock: &'a mut T, bufs: Vec<B>, addr: Option<&SocketAddr>) -> SendMsg<'a, T, B>
+    where
+        T: AsTarget,
+    {
         let (addr, addr_ptr, addr_len) = match addr {
             Some(addr) => {
                 let (addr, addr_len) = SocketAddrC::from_std(addr);
@@ -86,10 +82,10 @@ where
         };
 
         let mut iovecs = Vec::with_capacity(bufs.len());
-        for buf in bufs.iter_mut() {
+        for buf in bufs.iter() {
             iovecs.push(IoVec {
-                iov_base: unsafe { SendMut::new(buf.as_mut_ptr() as _) },
-                iov_len: buf.len(),
+                iov_base: unsafe { SendMut::new(buf.stable_ptr() as _) },
+                iov_len: buf.bytes_init(),
             })
         }
         let mut iovecs = Pin::new(iovecs);
@@ -105,37 +101,37 @@ where
         };
         let hdr = Box::pin(hdr);
 
-        let op = SendMsgCompletion {
-            fd: sock.as_raw_fd(),
+        let op = SendMsgOp {
+            target: sock.as_target(),
+            bufs,
             addr,
             iovecs,
             hdr,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         SendMsg {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this send, enforced by the kernel via a linked timeout. If the send
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for SendMsg<'a, T>
+impl<'a, T, B> Future for SendMsg<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    type Output = io::Result<usize>;
+    type Output = (io::Result<usize>, Vec<B>);
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -1,80 +1,77 @@
 use std::{
-    cmp::Ordering,
     io,
     marker::PhantomData,
     net::SocketAddr,
-    os::fd::{AsRawFd, RawFd},
+    os::fd::AsRawFd,
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::Future;
-use io_uring::{cqueue, opcode, squeue, types};
+use io_uring::{opcode, squeue};
 
 use crate::{
-    context,
-    io_uring::{Completion, CompletionStatus},
+    io_uring::{AsTarget, IoBuf, Op, Operation, Target},
     net::{IoVec, MsgHdr, SocketAddrC},
     ptr::SendMut,
-    sync::OneShot,
 };
 
-struct SendToCompletion {
-    fd: RawFd,
+struct SendToOp<B> {
+    target: Target,
+    buf: B,
     addr: Option<Pin<Box<SocketAddrC>>>,
     iovecs: Pin<Vec<IoVec>>,
     hdr: Pin<Box<MsgHdr>>,
-    result: OneShot<io::Result<usize>>,
 }
 
-impl Completion for SendToCompletion {
-    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
-        let result = value.result();
-        let result = match result.cmp(&0) {
-            Ordering::Less => Err(io::Error::from_raw_os_error(-result)),
-            Ordering::Equal | Ordering::Greater => Ok(result as usize),
-        };
+// SAFETY: The buffer is an [IoBuf] owned by the operation, and the address, iovecs and header live
+// on the heap, owned by the operation.
+unsafe impl<B> Operation for SendToOp<B>
+where
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
 
-        assert!(self.iovecs.len() > 0);
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::SendMsg::new(fd, self.hdr.as_mut_ptr())
+            .build()
+            .flags(flags)
+    }
+
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
         if let Some(addr) = &self.addr {
             assert!(addr.is_valid());
         }
-        self.result.complete(result);
-        CompletionStatus::Finalized
-    }
-
-    fn as_entry(&mut self) -> squeue::Entry {
-        opcode::SendMsg::new(types::Fd(self.fd), self.hdr.as_mut_ptr()).build()
+        (result.map(|len| len as usize), self.buf)
     }
 }
 
-/// This represents a single use send to operation. This will return the number of bytes sent
-/// across the supplied buffers. Specifying the send to address is optional on connected sockets.
-pub struct SendTo<'a, T> {
+/// This represents a single use send to operation, it takes ownership of the given buffer to send
+/// data from. This will return the number of bytes sent along with the buffer. Specifying the send
+/// to address is optional on connected sockets.
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct SendTo<'a, T, B> {
     inner: PhantomData<&'a mut T>,
-    id: usize,
-    result: OneShot<io::Result<usize>>,
+    op: Op<SendToOp<B>, (io::Result<usize>, B)>,
 }
 
-impl<'a, T> Drop for SendTo<'a, T> {
-    fn drop(&mut self) {
-        context::uring().deregister(self.id);
-    }
-}
-
-impl<'a, T> SendTo<'a, T>
+impl<'a, T, B> SendTo<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    pub(crate) fn new<'b>(
-        sock: &'a mut T,
-        buf: &'b mut [u8],
-        addr: Option<&SocketAddr>,
-    ) -> SendTo<'a, T> {
-        let result = OneShot::new();
-        let buf_len = buf.len();
-        let buf = unsafe { SendMut::new(buf.as_mut_ptr() as _) };
+    pub(crate) fn new(sock: &'a mut T, buf: B, addr: Option<&SocketAddr>) -> SendTo<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let iov_len = buf.bytes_init();
+        let iov_base = unsafe { SendMut::new(buf.stable_ptr() as _) };
 
         let (addr, addr_ptr, addr_len) = match addr {
             Some(addr) => {
@@ -86,10 +83,7 @@ where
             None => (None, ptr::null_mut(), 0),
         };
 
-        let iovecs = vec![IoVec {
-            iov_base: buf,
-            iov_len: buf_len,
-        }];
+        let iovecs = vec![IoVec { iov_base, iov_len }];
         let mut iovecs = Pin::new(iovecs);
 
         let hdr = MsgHdr {
@@ -103,37 +97,37 @@ where
         };
         let hdr = Box::pin(hdr);
 
-        let op = SendToCompletion {
-            fd: sock.as_raw_fd(),
+        let op = SendToOp {
+            target: sock.as_target(),
+            buf,
             addr,
             iovecs,
             hdr,
-            result: result.clone(),
         };
-        let id = context::uring().register(op);
-
         SendTo {
             inner: PhantomData,
-            id,
-            result,
+            op: Op::new(op),
         }
     }
 
-    fn set_waker(&mut self, cx: &mut Context<'_>) {
-        self.result.set_waker(cx.waker().clone());
+    /// Set a deadline for this send, enforced by the kernel via a linked timeout. If the send
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
     }
 }
 
-impl<'a, T> Future for SendTo<'a, T>
+impl<'a, T, B> Future for SendTo<'a, T, B>
 where
     T: AsRawFd,
+    B: IoBuf,
 {
-    type Output = io::Result<usize>;
+    type Output = (io::Result<usize>, B);
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.set_waker(cx);
-        match self.result.take() {
-            Some(result) => Poll::Ready(result),
-            None => Poll::Pending,
-        }
+        Pin::new(&mut self.op).poll(cx)
     }
 }
@@ -0,0 +1,142 @@
+use std::{
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    os::fd::AsRawFd,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{opcode, squeue};
+
+use crate::{
+    io_uring::{AsTarget, IoBuf, Op, Operation, Target},
+    net::{IoVec, MsgHdr, SocketAddrC},
+    ptr::SendMut,
+};
+
+use super::sendzc::ZeroCopy;
+
+struct SendToZcOp<B> {
+    target: Target,
+    buf: B,
+    addr: Option<Pin<Box<SocketAddrC>>>,
+    iovecs: Pin<Vec<IoVec>>,
+    hdr: Pin<Box<MsgHdr>>,
+    zc: ZeroCopy,
+}
+
+// SAFETY: The buffer is an [IoBuf] owned by the operation, and the address, iovecs and header live
+// on the heap, owned by the operation.
+unsafe impl<B> Operation for SendToZcOp<B>
+where
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        opcode::SendMsgZc::new(fd, self.hdr.as_mut_ptr())
+            .build()
+            .flags(flags)
+    }
+
+    fn update(&mut self, result: io::Result<u32>, flags: u32) {
+        self.zc.update(result, flags);
+    }
+
+    fn complete(mut self, result: io::Result<u32>, flags: u32) -> Self::Output {
+        assert!(!self.iovecs.is_empty());
+        if let Some(addr) = &self.addr {
+            assert!(addr.is_valid());
+        }
+        (self.zc.complete(result, flags), self.buf)
+    }
+}
+
+/// This represents a single use zero-copy send to operation, where the kernel transmits the data
+/// straight from the given buffer. The future takes ownership of the buffer, and will return the
+/// number of bytes sent along with the buffer once the kernel has released it. Specifying the send
+/// to address is optional on connected sockets.
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct SendToZc<'a, T, B> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<SendToZcOp<B>, (io::Result<usize>, B)>,
+}
+
+impl<'a, T, B> SendToZc<'a, T, B>
+where
+    T: AsRawFd,
+    B: IoBuf,
+{
+    pub(crate) fn new(sock: &'a mut T, buf: B, addr: Option<&SocketAddr>) -> SendToZc<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let iov_len = buf.bytes_init();
+        let iov_base = unsafe { SendMut::new(buf.stable_ptr() as _) };
+
+        let (addr, addr_ptr, addr_len) = match addr {
+            Some(addr) => {
+                let (addr, addr_len) = SocketAddrC::from_std(addr);
+                let mut addr = Box::pin(addr);
+                let addr_ptr = addr.as_mut_ptr();
+                (Some(addr), addr_ptr as _, addr_len)
+            }
+            None => (None, ptr::null_mut(), 0),
+        };
+
+        let iovecs = vec![IoVec { iov_base, iov_len }];
+        let mut iovecs = Pin::new(iovecs);
+
+        let hdr = MsgHdr {
+            msg_name: unsafe { SendMut::new(addr_ptr as _) },
+            msg_namelen: addr_len,
+            msg_iov: unsafe { SendMut::new(iovecs.as_mut_ptr()) },
+            msg_iovlen: iovecs.len(),
+            msg_control: unsafe { SendMut::new(ptr::null_mut()) },
+            msg_controllen: 0,
+            msg_flags: 0,
+        };
+        let hdr = Box::pin(hdr);
+
+        let op = SendToZcOp {
+            target: sock.as_target(),
+            buf,
+            addr,
+            iovecs,
+            hdr,
+            zc: ZeroCopy::new(),
+        };
+        SendToZc {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this send, enforced by the kernel via a linked timeout. If the send
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T, B> Future for SendToZc<'a, T, B>
+where
+    T: AsRawFd,
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
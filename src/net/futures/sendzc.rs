@@ -0,0 +1,215 @@
+use std::{
+    io,
+    marker::PhantomData,
+    os::fd::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{opcode, squeue};
+
+use crate::{
+    io_uring::{AsTarget, FixedBuf, FixedBufPool, IoBuf, Op, Operation, Target},
+    ptr::SendConst,
+};
+
+/// The `IORING_CQE_F_NOTIF` completion flag, which isn't exposed by the version of [io_uring] in
+/// use. This marks the notification that the kernel is done with the buffer of a zero-copy send.
+const IORING_CQE_F_NOTIF: u32 = 1 << 3;
+
+/// Tracks the two completions of a zero-copy send, the result of the send followed by a
+/// notification once the kernel no longer references the buffer. The notification is only posted
+/// if the result is flagged with [io_uring::cqueue::more].
+pub(super) struct ZeroCopy {
+    sent: Option<io::Result<u32>>,
+}
+
+impl ZeroCopy {
+    pub(super) fn new() -> ZeroCopy {
+        ZeroCopy { sent: None }
+    }
+
+    /// Record the result of the send, which is followed by the notification.
+    pub(super) fn update(&mut self, result: io::Result<u32>, flags: u32) {
+        if flags & IORING_CQE_F_NOTIF == 0 {
+            self.sent = Some(result);
+        }
+    }
+
+    /// Complete the send with its final completion, returning the result of the send.
+    pub(super) fn complete(&mut self, result: io::Result<u32>, flags: u32) -> io::Result<usize> {
+        let result = match self.sent.take() {
+            Some(sent) if flags & IORING_CQE_F_NOTIF != 0 => sent,
+            _ => result,
+        };
+        result.map(|len| len as usize)
+    }
+}
+
+struct SendZcOp<B> {
+    target: Target,
+    buf: B,
+    buf_ptr: SendConst<u8>,
+    buf_len: u32,
+    fixed: Option<(FixedBufPool, u16)>,
+    zc: ZeroCopy,
+}
+
+// SAFETY: The buffer is an [IoBuf] owned by the operation, which is only completed once the kernel
+// has released it.
+unsafe impl<B> Operation for SendZcOp<B>
+where
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        let buf_index = self
+            .fixed
+            .as_ref()
+            .and_then(|(pool, index)| pool.fixed_index(*index));
+        opcode::SendZc::new(fd, self.buf_ptr.to_ptr(), self.buf_len)
+            .buf_index(buf_index)
+            .build()
+            .flags(flags)
+    }
+
+    fn update(&mut self, result: io::Result<u32>, flags: u32) {
+        self.zc.update(result, flags);
+    }
+
+    fn complete(mut self, result: io::Result<u32>, flags: u32) -> Self::Output {
+        (self.zc.complete(result, flags), self.buf)
+    }
+}
+
+/// This represents a single use asynchronous zero-copy send operation on a connected
+/// [crate::net::TcpStream], where the kernel transmits the data straight from the given buffer
+/// rather than copying it into the socket. The future takes ownership of the buffer, and only
+/// resolves to the amount of data sent along with the buffer once the kernel has released it.
+///
+/// Zero-copy sends have a fixed setup cost, so they generally only pay off for larger buffers.
+///
+/// The send is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct SendZc<'a, T, B> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<SendZcOp<B>, (io::Result<usize>, B)>,
+}
+
+impl<'a, T, B> SendZc<'a, T, B>
+where
+    T: AsRawFd,
+    B: IoBuf,
+{
+    pub(crate) fn new(stream: &'a mut T, buf: B) -> SendZc<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        Self::with_fixed(stream, buf, None)
+    }
+
+    fn with_fixed(stream: &'a mut T, buf: B, fixed: Option<(FixedBufPool, u16)>) -> SendZc<'a, T, B>
+    where
+        T: AsTarget,
+    {
+        let buf_len = buf.bytes_init() as u32;
+        let buf_ptr = unsafe { SendConst::new(buf.stable_ptr()) };
+        let op = SendZcOp {
+            target: stream.as_target(),
+            buf,
+            buf_ptr,
+            buf_len,
+            fixed,
+            zc: ZeroCopy::new(),
+        };
+        SendZc {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this send, enforced by the kernel via a linked timeout. If the send
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T> SendZc<'a, T, FixedBuf>
+where
+    T: AsRawFd,
+{
+    /// Create a [SendZc] sending from the given [FixedBuf], which skips pinning the pages of the
+    /// buffer on each send when submitted to the ring the buffer is registered with.
+    pub(crate) fn fixed(stream: &'a mut T, buf: FixedBuf) -> SendZc<'a, T, FixedBuf>
+    where
+        T: AsTarget,
+    {
+        let fixed = (buf.pool().clone(), buf.index());
+        Self::with_fixed(stream, buf, Some(fixed))
+    }
+}
+
+impl<'a, T, B> Future for SendZc<'a, T, B>
+where
+    T: AsRawFd,
+    B: IoBuf,
+{
+    type Output = (io::Result<usize>, B);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{
+        executor::block_on,
+        net::{TcpStream, UdpSocket},
+    };
+
+    #[test]
+    fn test_send_zc() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::new(true).unwrap();
+        block_on(stream.connect(&addr)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let data = vec![7u8; 64 * 1024];
+        let (sent, data) = block_on(stream.send_zc(data));
+        let sent = sent.unwrap();
+        assert!(sent > 0);
+        assert_eq!(data.len(), 64 * 1024);
+
+        let mut buf = vec![0u8; sent];
+        peer.read_exact(&mut buf).unwrap();
+        assert!(buf.iter().all(|b| *b == 7));
+    }
+
+    #[test]
+    fn test_send_to_zc() {
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = peer.local_addr().unwrap();
+
+        let mut sock = UdpSocket::new("127.0.0.1", 0).unwrap();
+        let (sent, _) = block_on(sock.send_to_zc(b"hello".as_slice(), Some(&addr)));
+        assert_eq!(sent.unwrap(), 5);
+
+        let mut buf = [0u8; 8];
+        let (len, from) = peer.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"hello");
+        assert_eq!(from, sock.local_addr());
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    io,
+    marker::PhantomData,
+    os::fd::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Future;
+use io_uring::{opcode, squeue};
+
+use crate::io_uring::{AsTarget, FixedBuf, FixedBufPool, Op, Operation, Target};
+
+struct WriteFixedOp {
+    target: Target,
+    buf: FixedBuf,
+    pool: FixedBufPool,
+    index: u16,
+    len: u32,
+    offset: u64,
+}
+
+// SAFETY: The fixed buffer is owned by the operation, and stays registered with the ring via the
+// pool the operation holds on to.
+unsafe impl Operation for WriteFixedOp {
+    type Output = (io::Result<usize>, FixedBuf);
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        let (fd, flags) = self.target.resolve();
+        let buf = self.pool.buf_ptr(self.index);
+        match self.pool.fixed_index(self.index) {
+            Some(index) => opcode::WriteFixed::new(fd, buf, self.len, index)
+                .offset(self.offset)
+                .build()
+                .flags(flags),
+            None => opcode::Write::new(fd, buf, self.len)
+                .offset(self.offset)
+                .build()
+                .flags(flags),
+        }
+    }
+
+    fn complete(self, result: io::Result<u32>, _: u32) -> Self::Output {
+        (result.map(|len| len as usize), self.buf)
+    }
+}
+
+/// This represents a single use asynchronous write from a [FixedBuf], it takes ownership of the
+/// buffer and will write the data in it, ultimately returning the amount of data written along
+/// with the buffer.
+///
+/// The write is only submitted to the ring once the future is first polled, rather than when
+/// the future is created.
+pub struct WriteFixed<'a, T> {
+    inner: PhantomData<&'a mut T>,
+    op: Op<WriteFixedOp>,
+}
+
+impl<'a, T> WriteFixed<'a, T>
+where
+    T: AsRawFd,
+{
+    pub(crate) fn new(io: &'a mut T, buf: FixedBuf, offset: u64) -> WriteFixed<'a, T>
+    where
+        T: AsTarget,
+    {
+        let op = WriteFixedOp {
+            target: io.as_target(),
+            pool: buf.pool().clone(),
+            index: buf.index(),
+            len: buf.len() as u32,
+            offset,
+            buf,
+        };
+        WriteFixed {
+            inner: PhantomData,
+            op: Op::new(op),
+        }
+    }
+
+    /// Set a deadline for this write, enforced by the kernel via a linked timeout. If the write
+    /// hasn't completed within `timeout` it is canceled, and the future resolves to an
+    /// [std::io::Error] of kind [std::io::ErrorKind::TimedOut].
+    ///
+    /// Note that this has no effect once the future has been polled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.op = self.op.timeout(timeout);
+        self
+    }
+}
+
+impl<'a, T> Future for WriteFixed<'a, T>
+where
+    T: AsRawFd,
+{
+    type Output = (io::Result<usize>, FixedBuf);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op).poll(cx)
+    }
+}
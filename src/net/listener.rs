@@ -1,9 +1,11 @@
 use std::{
     io,
     net::SocketAddr,
-    os::fd::{AsRawFd, OwnedFd, RawFd},
+    os::fd::{AsRawFd, RawFd},
 };
 
+use crate::io_uring::{AsTarget, Descriptor, Target};
+
 use super::{getsockname, socket, Accept, Incoming};
 
 const DEFAULT_OUSTANDING: i32 = 1024;
@@ -42,7 +44,7 @@ const DEFAULT_OUSTANDING: i32 = 1024;
 /// # }
 /// ```
 pub struct TcpListener {
-    fd: OwnedFd,
+    fd: Descriptor,
 }
 
 impl TcpListener {
@@ -64,7 +66,9 @@ impl TcpListener {
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
         let fd = socket::listener_socket(addr, outstanding)?;
 
-        Ok(TcpListener { fd })
+        Ok(TcpListener {
+            fd: Descriptor::new(fd),
+        })
     }
 
     /// Retrieve this sockets local [SocketAddr], or panics if there is either no local address or
@@ -81,6 +85,12 @@ impl TcpListener {
         getsockname(self.fd.as_raw_fd())
     }
 
+    /// Returns whether or not this socket is registered in the fixed file table of a ring, see
+    /// [crate::io_uring::UringBuilder::fixed_files].
+    pub fn is_fixed(&self) -> bool {
+        self.fd.is_fixed()
+    }
+
     /// Accept a single connection asynchronously, this will return an [Accept] future that when
     /// polled to completion will either return a valid [TcpStream] that is ready to use or an
     /// [std::io::Error] describing any errors that might have occured.
@@ -104,3 +114,9 @@ impl AsRawFd for TcpListener {
         self.fd.as_raw_fd()
     }
 }
+
+impl AsTarget for TcpListener {
+    fn as_target(&self) -> Target {
+        self.fd.target()
+    }
+}
@@ -4,13 +4,18 @@ use std::{
     os::fd::{AsRawFd, OwnedFd, RawFd},
 };
 
-use super::{getpeername, getsockname, socket, Connect, Recv, Send};
+use crate::io_uring::{AsTarget, BufRing, Descriptor, FixedBuf, IoBuf, IoBufMut, Target};
+
+use super::{
+    getpeername, getsockname, socket, Connect, ReadFixed, Recv, RecvBuf, RecvStream, Send, SendZc,
+    WriteFixed,
+};
 
 /// A [TcpStream] represents a bidirectional TCP connection that can read and write data to a
 /// remote host. There are two main ways to create a [TcpStream], either via the [super::TcpListener::accept]
 /// and [super::TcpListener::incoming] calls, or via the [TcpStream::connect] call.
 pub struct TcpStream {
-    fd: OwnedFd,
+    fd: Descriptor,
 }
 
 impl TcpStream {
@@ -18,6 +23,12 @@ impl TcpStream {
         socket::client_socket(ipv4).map(TcpStream::from)
     }
 
+    /// Returns whether or not this socket is registered in the fixed file table of a ring, see
+    /// [crate::io_uring::UringBuilder::fixed_files].
+    pub fn is_fixed(&self) -> bool {
+        self.fd.is_fixed()
+    }
+
     /// Connect to a given remote host and return a [Connect] future to poll for completion.
     pub fn connect<'a>(&'a mut self, addr: &SocketAddr) -> Connect<'a, TcpStream> {
         Connect::new(self, addr)
@@ -54,21 +65,74 @@ impl TcpStream {
     }
 
     /// Receive data using the given buffer from the remote host. This will return a single use
-    /// [Recv] future that returns the amount of data read into the buffer, and whether or not the
-    /// socket had more data available for read.
-    pub fn recv<'a>(&'a mut self, buf: &'a mut [u8]) -> Recv<'a, TcpStream> {
+    /// [Recv] future that takes ownership of the buffer, and returns the amount of data read into
+    /// the buffer along with the buffer itself. Data is read into the capacity of the buffer rather
+    /// than its length, see [IoBufMut].
+    pub fn recv<'a, B: IoBufMut>(&'a mut self, buf: B) -> Recv<'a, TcpStream, B> {
         Recv::new(self, buf)
     }
 
+    /// Receive data from the remote host into a buffer picked by the kernel from the given
+    /// [BufRing] once data arrives. This will return a single use [RecvBuf] future that returns
+    /// the received data as a [crate::io_uring::BorrowedBuffer], which is empty once the remote
+    /// host closes the connection.
+    pub fn recv_buf<'a>(&'a mut self, ring: &BufRing) -> RecvBuf<'a, TcpStream> {
+        RecvBuf::new(self, ring)
+    }
+
+    /// Receive a stream of data from the remote host into buffers picked by the kernel from the
+    /// given [BufRing]. This will return a [RecvStream] backed by a single multi-shot receive,
+    /// which yields each chunk of received data as a [crate::io_uring::BorrowedBuffer] until the
+    /// remote host closes the connection.
+    pub fn recv_stream<'a>(&'a mut self, ring: &BufRing) -> RecvStream<'a, TcpStream> {
+        RecvStream::new(self, ring)
+    }
+
     /// Send the data in the given buffer to the remote host. This will return a single use [Send]
-    /// future that returns the amount of data sent from the buffer.
-    pub fn send<'a>(&'a mut self, buf: &'a [u8]) -> Send<'a, TcpStream> {
+    /// future that takes ownership of the buffer, and returns the amount of data sent from the
+    /// buffer along with the buffer itself.
+    pub fn send<'a, B: IoBuf>(&'a mut self, buf: B) -> Send<'a, TcpStream, B> {
         Send::new(self, buf)
     }
+
+    /// Send the data in the given buffer to the remote host without copying it into the socket.
+    /// This will return a single use [SendZc] future that takes ownership of the buffer, and
+    /// returns the amount of data sent from the buffer along with the buffer once the kernel has
+    /// released it.
+    pub fn send_zc<'a, B: IoBuf>(&'a mut self, buf: B) -> SendZc<'a, TcpStream, B> {
+        SendZc::new(self, buf)
+    }
+
+    /// Read data from the remote host into the given [FixedBuf]. This will return a single use
+    /// [ReadFixed] future that returns the amount of data read, which is also set as the length of
+    /// the buffer, along with the buffer.
+    pub fn read_fixed<'a>(&'a mut self, buf: FixedBuf) -> ReadFixed<'a, TcpStream> {
+        ReadFixed::new(self, buf, u64::MAX)
+    }
+
+    /// Write the data in the given [FixedBuf] to the remote host. This will return a single use
+    /// [WriteFixed] future that returns the amount of data written from the buffer along with the
+    /// buffer.
+    pub fn write_fixed<'a>(&'a mut self, buf: FixedBuf) -> WriteFixed<'a, TcpStream> {
+        WriteFixed::new(self, buf, u64::MAX)
+    }
+
+    /// Send the data in the given [FixedBuf] to the remote host without copying it into the
+    /// socket, see [TcpStream::send_zc]. Since the buffer is registered with the ring the kernel
+    /// also skips pinning its pages for the send.
+    pub fn send_fixed<'a>(&'a mut self, buf: FixedBuf) -> SendZc<'a, TcpStream, FixedBuf> {
+        SendZc::fixed(self, buf)
+    }
 }
 
 impl From<OwnedFd> for TcpStream {
     fn from(fd: OwnedFd) -> Self {
+        TcpStream::from(Descriptor::new(fd))
+    }
+}
+
+impl From<Descriptor> for TcpStream {
+    fn from(fd: Descriptor) -> Self {
         TcpStream { fd }
     }
 }
@@ -78,3 +142,9 @@ impl AsRawFd for TcpStream {
         self.fd.as_raw_fd()
     }
 }
+
+impl AsTarget for TcpStream {
+    fn as_target(&self) -> Target {
+        self.fd.target()
+    }
+}
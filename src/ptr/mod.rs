@@ -14,9 +14,9 @@ impl<T> SendConst<T> {
     ///
     /// SAFETY:
     /// - You must be passing in a pinned heap allocated reference, really this means you need a
-    /// [std::pin::Pin]'ed box or arc.
+    ///   [std::pin::Pin]'ed box or arc.
     /// - As with all pointers the pointee must remain valid for the lifetime of this structure, it
-    /// is on the user to ensure this.
+    ///   is on the user to ensure this.
     pub unsafe fn new(val: *const T) -> SendConst<T> {
         SendConst(val)
     }
@@ -47,9 +47,9 @@ impl<T> SendMut<T> {
     ///
     /// SAFETY:
     /// - You must be passing in a pinned heap allocated reference, really this means you need a
-    /// [std::pin::Pin]'ed box or arc.
+    ///   [std::pin::Pin]'ed box or arc.
     /// - As with all pointers the pointee must remain valid for the lifetime of this structure, it
-    /// is on the user to ensure this.
+    ///   is on the user to ensure this.
     pub unsafe fn new(val: *mut T) -> SendMut<T> {
         SendMut(val)
     }
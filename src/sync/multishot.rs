@@ -49,6 +49,15 @@ impl<T> Sender<T> {
     }
 }
 
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // Wake the receiver so that it notices the channel was closed.
+        if let Some(waker) = self.lock_waker().take() {
+            waker.wake()
+        }
+    }
+}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     let (tx, rx) = mpsc::channel();
     let waker = Arc::new(Mutex::new(None));
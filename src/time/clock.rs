@@ -0,0 +1,245 @@
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::Future;
+
+use super::wheel::TimerWheel;
+
+struct Paused {
+    now: Instant,
+    wheel: Arc<Mutex<TimerWheel>>,
+}
+
+/// The [Clock] of a runtime, shared by all of the workers of a [crate::executor::ThreadPool] so
+/// that pausing and advancing it from one worker affects the timers on all of them.
+#[derive(Default)]
+pub(crate) struct Clock {
+    paused: Mutex<Option<Paused>>,
+}
+
+impl Clock {
+    fn lock(&self) -> MutexGuard<'_, Option<Paused>> {
+        self.paused
+            .lock()
+            .expect("failed to lock paused clock: poisoned")
+    }
+}
+
+thread_local! {
+    /// The clock of the runtime the current thread belongs to, threads that are not part of a
+    /// [crate::executor::ThreadPool] lazily get a clock of their own.
+    static CLOCK: RefCell<Option<Arc<Clock>>> = const { RefCell::new(None) };
+}
+
+/// Set the [Clock] used by the current thread, this is called by the workers of a
+/// [crate::executor::ThreadPool] on startup to share the clock of the pool.
+pub(crate) fn set_clock(clock: Arc<Clock>) {
+    CLOCK.with(|current| *current.borrow_mut() = Some(clock));
+}
+
+fn clock() -> Arc<Clock> {
+    CLOCK.with(|clock| {
+        clock
+            .borrow_mut()
+            .get_or_insert_with(Default::default)
+            .clone()
+    })
+}
+
+/// Returns the current [Instant] as seen by the timers in this module, this is the wall clock
+/// unless the clock has been paused via [pause], in which case this is the paused time as moved
+/// forward by [advance].
+pub fn now() -> Instant {
+    match *clock().lock() {
+        Some(ref paused) => paused.now,
+        None => Instant::now(),
+    }
+}
+
+/// Returns whether or not the clock of the current runtime is paused.
+pub fn is_paused() -> bool {
+    clock().lock().is_some()
+}
+
+/// Pause the clock of the current runtime, this is meant for testing time dependent logic such as
+/// retries, backoff, and idle timeouts deterministically and without actually waiting.
+///
+/// While paused, [now] stops moving and any timer created by the runtime, be it a [super::Sleep],
+/// [super::Timeout], [super::Interval], [super::Deadline], or an operation timeout, is tracked by
+/// the paused clock rather than the kernel. These timers only fire as time is moved forward with
+/// [advance], and always fire in deadline order.
+///
+/// The clock is shared by all workers of a [crate::executor::ThreadPool], so pausing it on one
+/// worker pauses it for tasks on every worker. Threads outside of a pool, such as one running
+/// [crate::executor::block_on], each have a clock of their own.
+///
+/// Timers that are still pending when the clock is resumed carry over the time they had left on
+/// the paused clock, and fire that much later on the wall clock.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use futures::join;
+/// use libuio::{executor::block_on, time};
+///
+/// block_on(async {
+///     time::pause();
+///
+///     let start = time::now();
+///     let sleep = time::sleep(Duration::from_secs(60));
+///     join!(sleep, time::advance(Duration::from_secs(60)));
+///
+///     assert_eq!(time::now() - start, Duration::from_secs(60));
+///     time::resume();
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics if the clock is already paused.
+pub fn pause() {
+    let clock = clock();
+    let mut paused = clock.lock();
+    assert!(paused.is_none(), "clock is already paused");
+
+    let wheel = TimerWheel::new();
+    *paused = Some(Paused {
+        now: wheel.tick_instant(0),
+        wheel: Arc::new(Mutex::new(wheel)),
+    });
+}
+
+/// Resume the clock of the current runtime, after which [now] follows the wall clock again and new
+/// timers are once again handled by the kernel. Timers created while paused that have yet to fire
+/// are moved over to the wall clock, keeping the time they had left.
+///
+/// # Panics
+///
+/// Panics if the clock is not paused.
+pub fn resume() {
+    let paused = clock().lock().take().expect("clock is not paused");
+    let wakers = paused
+        .wheel
+        .lock()
+        .expect("failed to lock timer wheel: poisoned")
+        .resume(paused.now);
+
+    // Let the pending timers move themselves over to the wall clock.
+    for waker in wakers {
+        waker.wake();
+    }
+}
+
+/// Move the paused clock forward by the given [Duration], firing any timers that expire along the
+/// way in deadline order. This returns an [Advance] future, which yields after each batch of
+/// timers fire so that the tasks waiting on them run before time moves on.
+///
+/// # Panics
+///
+/// Panics if the clock is not paused, when polled.
+pub fn advance(duration: Duration) -> Advance {
+    Advance {
+        target: now() + duration,
+    }
+}
+
+/// Returns the [TimerWheel] of the paused clock of the current runtime, if any.
+pub(crate) fn paused_wheel() -> Option<Arc<Mutex<TimerWheel>>> {
+    clock().lock().as_ref().map(|paused| paused.wheel.clone())
+}
+
+/// This represents a future that moves the paused clock forward to its target, this is returned by
+/// the [advance] function.
+pub struct Advance {
+    target: Instant,
+}
+
+impl Future for Advance {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let clock = clock();
+        let wakers = {
+            let mut paused = clock.lock();
+            let paused = paused.as_mut().expect("clock is not paused");
+            let mut wheel = paused
+                .wheel
+                .lock()
+                .expect("failed to lock timer wheel: poisoned");
+
+            loop {
+                // Step to the next slot that needs processing, or our target if that is sooner.
+                let next = wheel
+                    .next_deadline()
+                    .map(|tick| wheel.tick_instant(tick))
+                    .filter(|next| *next <= self.target);
+                paused.now = paused.now.max(next.unwrap_or(self.target));
+
+                let tick = wheel.now_tick(paused.now);
+                let wakers = wheel.advance(tick);
+                if !wakers.is_empty() {
+                    break wakers;
+                }
+
+                if next.is_none() {
+                    return Poll::Ready(());
+                }
+            }
+        };
+
+        // Let the tasks waiting on these timers run, before moving time any further.
+        for waker in wakers {
+            waker.wake();
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use futures::{future::join_all, join, StreamExt};
+
+    use super::*;
+    use crate::{executor::block_on, time};
+
+    #[test]
+    fn test_paused_ordering() {
+        block_on(async {
+            pause();
+            let start = Instant::now();
+            let order = Rc::new(RefCell::new(Vec::new()));
+
+            let sleeps = join_all([300u64, 100, 200].into_iter().map(|millis| {
+                let order = order.clone();
+                async move {
+                    time::sleep(Duration::from_millis(millis)).await;
+                    order.borrow_mut().push(millis);
+                }
+            }));
+            let ticks = time::interval(Duration::from_millis(120)).take(2).count();
+            let timeout = time::timeout(
+                Duration::from_millis(50),
+                time::sleep(Duration::from_secs(86400)),
+            );
+
+            let (_, ticks, timeout, _) =
+                join!(sleeps, ticks, timeout, advance(Duration::from_secs(3600)));
+            assert_eq!(*order.borrow(), vec![100, 200, 300]);
+            assert_eq!(ticks, 2);
+            assert!(timeout.is_err());
+
+            // An hour passed on the paused clock without actually waiting for it.
+            assert!(start.elapsed() < Duration::from_secs(5));
+            resume();
+        });
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::Future;
+
+use crate::context;
+
+use super::{clock, wheel::TimerWheel};
+
+/// A [Deadline] is a cheap, resettable timer backed by the timer wheel of the worker it was
+/// created on, rather than a dedicated kernel timeout. This makes it suitable for tracking large
+/// numbers of deadlines that are constantly being pushed back, such as idle or keepalive timeouts
+/// on every connection of a busy server.
+///
+/// Deadlines have a resolution of one millisecond, and will never fire early. Deadlines created
+/// while the clock is paused via [super::pause] are tracked by the paused clock instead, and move
+/// over to the timer wheel of the worker they are polled on once the clock is resumed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{io, time::Duration};
+///
+/// use futures::{future::select, future::Either, pin_mut};
+/// use libuio::{net::TcpStream, time::Deadline};
+///
+/// async fn serve(mut conn: TcpStream) -> io::Result<()> {
+///     let idle = Duration::from_secs(30);
+///     let mut deadline = Deadline::new(idle);
+///     let mut buf = vec![0u8; 1024];
+///     loop {
+///         let recv = conn.recv(buf);
+///         pin_mut!(recv);
+///         match select(recv, &mut deadline).await {
+///             Either::Left(((read, returned), _)) => {
+///                 if read? == 0 {
+///                     return Ok(());
+///                 }
+///                 buf = returned;
+///                 // Got data, so push the idle timeout back.
+///                 deadline.reset(idle);
+///             }
+///             Either::Right(_) => return Ok(()), // Idle for too long.
+///         }
+///     }
+/// }
+/// ```
+pub struct Deadline {
+    wheel: Arc<Mutex<TimerWheel>>,
+    key: usize,
+    deadline: Instant,
+}
+
+impl Deadline {
+    /// Create a new [Deadline] that expires after the given [Duration].
+    pub fn new(timeout: Duration) -> Deadline {
+        Deadline::new_at(clock::now() + timeout)
+    }
+
+    /// Create a new [Deadline] that expires at the given [Instant].
+    pub fn new_at(deadline: Instant) -> Deadline {
+        let wheel = match clock::paused_wheel() {
+            Some(wheel) => wheel,
+            None => context::uring().timer_wheel(),
+        };
+        let key = {
+            let mut guard = wheel.lock().expect("failed to lock timer wheel: poisoned");
+            let when = guard.deadline_tick(deadline);
+            guard.insert(when)
+        };
+
+        Deadline {
+            wheel,
+            key,
+            deadline,
+        }
+    }
+
+    /// Returns the [Instant] at which this [Deadline] expires.
+    pub fn deadline(&self) -> Instant {
+        self.lock_wheel().rebase(self.deadline)
+    }
+
+    /// Returns whether or not this [Deadline] has expired.
+    pub fn is_elapsed(&self) -> bool {
+        clock::now() >= self.deadline()
+    }
+
+    /// Reset this [Deadline] to expire after the given [Duration], this works regardless of
+    /// whether or not the deadline has already expired.
+    pub fn reset(&mut self, timeout: Duration) {
+        self.reset_at(clock::now() + timeout)
+    }
+
+    /// Reset this [Deadline] to expire at the given [Instant], this works regardless of whether or
+    /// not the deadline has already expired.
+    pub fn reset_at(&mut self, deadline: Instant) {
+        if self.lock_wheel().is_resumed() {
+            *self = Deadline::new_at(deadline);
+            return;
+        }
+
+        self.deadline = deadline;
+        let mut wheel = self.lock_wheel();
+        let when = wheel.deadline_tick(deadline);
+        wheel.reset(self.key, when);
+    }
+
+    fn lock_wheel(&self) -> MutexGuard<'_, TimerWheel> {
+        self.wheel
+            .lock()
+            .expect("failed to lock timer wheel: poisoned")
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        self.lock_wheel().remove(self.key);
+    }
+}
+
+impl Future for Deadline {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let resumed = {
+            let mut wheel = self.lock_wheel();
+            if wheel.poll(self.key, cx.waker()) {
+                return Poll::Ready(());
+            }
+            wheel.is_resumed()
+        };
+
+        // The paused clock we were created on has been resumed, so move over to the wall clock.
+        if resumed {
+            let deadline = self.deadline();
+            *self = Deadline::new_at(deadline);
+            return self.poll(cx);
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::block_on;
+
+    #[test]
+    fn test_deadline_reset() {
+        let start = Instant::now();
+        let mut deadline = Deadline::new(Duration::from_millis(10));
+        deadline.reset(Duration::from_millis(60));
+        block_on(&mut deadline);
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert!(deadline.is_elapsed());
+    }
+
+    #[test]
+    fn test_deadline_resume() {
+        block_on(async {
+            clock::pause();
+            let deadline = Deadline::new(Duration::from_millis(50));
+            clock::advance(Duration::from_millis(20)).await;
+            clock::resume();
+
+            // The remaining time carries over to the wall clock.
+            let start = Instant::now();
+            deadline.await;
+            assert!(start.elapsed() >= Duration::from_millis(30));
+        });
+    }
+}
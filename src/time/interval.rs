@@ -0,0 +1,239 @@
+use std::{
+    cell::Cell,
+    pin::Pin,
+    sync::mpsc::TryRecvError,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{future::poll_fn, Future, Stream};
+use io_uring::{
+    cqueue, opcode, squeue,
+    types::{TimeoutFlags, Timespec},
+};
+use nix::libc;
+
+use crate::{
+    io_uring::{Completion, CompletionStatus, Registration},
+    sync::{channel, Receiver, Sender},
+};
+
+use super::{clock, Deadline};
+
+/// The `IORING_TIMEOUT_MULTISHOT` flag, available since 6.4, which isn't exposed by the version of
+/// [io_uring] in use.
+const TIMEOUT_MULTISHOT: u32 = 1 << 6;
+
+struct IntervalCompletion {
+    timespec: Pin<Box<Timespec>>,
+    period: Duration,
+    // The deadline of the first tick, which is armed as a single-shot timeout so that the first
+    // tick is one period after the interval was created, rather than after it was first polled.
+    first: Cell<Option<Instant>>,
+    multishot: Cell<bool>,
+    result: Sender<Instant>,
+}
+
+impl Completion for IntervalCompletion {
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        match value.result() {
+            // Kernels before 6.4 reject the multishot flag, so fall back to re-arming a single-shot
+            // timeout on every tick instead.
+            result
+                if result == -libc::EINVAL
+                    && self.multishot.get()
+                    && self.first.get().is_none() =>
+            {
+                self.multishot.set(false);
+                return CompletionStatus::Rearm;
+            }
+            result if result == -libc::ETIME => {}
+            // Anything else, including being canceled, ends the interval, which the [Interval]
+            // notices once the sender is dropped.
+            _ => return CompletionStatus::Finalized,
+        }
+
+        let first = self.first.take().is_some();
+        match self.result.push(Instant::now()) {
+            Err(_) => CompletionStatus::Finalized,
+            // The first tick was a single-shot timeout, the period is armed from here on.
+            Ok(_) if first => CompletionStatus::Rearm,
+            Ok(_) if cqueue::more(value.flags()) => CompletionStatus::Armed,
+            Ok(_) => CompletionStatus::Rearm,
+        }
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        *self.timespec = match self.first.get() {
+            Some(first) => Timespec::from(first.saturating_duration_since(Instant::now())),
+            None => Timespec::from(self.period),
+        };
+        let entry = opcode::Timeout::new(&*self.timespec as *const _);
+        if self.first.get().is_some() || !self.multishot.get() {
+            return entry.build();
+        }
+
+        // SAFETY: The multishot flag is a valid timeout flag, it just isn't exposed by io_uring.
+        let flags = unsafe { TimeoutFlags::from_bits_unchecked(TIMEOUT_MULTISHOT) };
+        entry.flags(flags).build()
+    }
+}
+
+/// This represents a stream of ticks on a fixed period, backed by a single multi-shot timeout, or a
+/// single-shot timeout re-armed on every tick on kernels before 6.4. Each tick yields the [Instant]
+/// at which it was observed by the driver. This is returned by the [interval] function.
+///
+/// Note that ticks are queued up if the consumer falls behind, so a slow consumer will see a burst
+/// of ticks rather than missing any. Should the kernel fail the timeout, the interval carries on
+/// ticking via a [Deadline] instead.
+///
+/// The timeout is only submitted to the ring once the interval is first polled, rather than when
+/// the interval is created. The first tick is still one period after the interval was created.
+pub struct Interval {
+    period: Duration,
+    inner: Inner,
+}
+
+enum Inner {
+    Kernel {
+        // Held to keep the timeout armed until we are dropped.
+        registration: Registration<IntervalCompletion>,
+        stream: Receiver<Instant>,
+    },
+    Wheel(Deadline),
+}
+
+impl Interval {
+    pub(crate) fn new(period: Duration) -> Interval {
+        // While the clock is paused we simply reset a deadline on the paused clock on every tick.
+        if clock::is_paused() {
+            return Interval {
+                period,
+                inner: Inner::Wheel(Deadline::new(period)),
+            };
+        }
+
+        let (tx, rx) = channel();
+        let op = IntervalCompletion {
+            timespec: Box::pin(Timespec::new()),
+            period,
+            first: Cell::new(Some(Instant::now() + period)),
+            multishot: Cell::new(true),
+            result: tx,
+        };
+
+        Interval {
+            period,
+            inner: Inner::Kernel {
+                registration: Registration::new(op),
+                stream: rx,
+            },
+        }
+    }
+
+    /// Returns the period of this [Interval].
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Wait for the next tick of this [Interval], returning the [Instant] it fired at.
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Poll for the next tick of this [Interval], returning the [Instant] it fired at.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        match self.inner {
+            Inner::Kernel {
+                ref mut registration,
+                ref stream,
+            } => {
+                if registration.poll_register(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                stream.set_waker(cx.waker().clone());
+                match stream.try_recv() {
+                    Ok(instant) => Poll::Ready(instant),
+                    Err(TryRecvError::Empty) => Poll::Pending,
+                    Err(TryRecvError::Disconnected) => {
+                        // The kernel failed the timeout, so keep ticking on the timer wheel.
+                        self.inner = Inner::Wheel(Deadline::new(self.period));
+                        self.poll_tick(cx)
+                    }
+                }
+            }
+            Inner::Wheel(ref mut deadline) => match Pin::new(&mut *deadline).poll(cx) {
+                Poll::Ready(()) => {
+                    let instant = deadline.deadline();
+                    deadline.reset_at(instant + self.period);
+                    Poll::Ready(instant)
+                }
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_tick(cx).map(Some)
+    }
+}
+
+/// Create a new [Interval] that ticks every `period`, the first tick completes one `period` after
+/// the call to [interval].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use libuio::time;
+///
+/// #[libuio::main]
+/// async fn main() {
+///     let mut interval = time::interval(Duration::from_millis(100));
+///     for _ in 0..10 {
+///         interval.tick().await;
+///     }
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(!period.is_zero(), "interval period must be non-zero");
+    Interval::new(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::block_on;
+
+    #[test]
+    fn test_interval() {
+        let start = Instant::now();
+        let mut interval = interval(Duration::from_millis(10));
+        block_on(async {
+            for _ in 0..3 {
+                interval.tick().await;
+            }
+        });
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_interval_first_tick() {
+        let start = Instant::now();
+        let mut interval = interval(Duration::from_millis(300));
+        std::thread::sleep(Duration::from_millis(300));
+
+        // The first tick is due one period after creation, even though nothing was submitted until
+        // the interval was first polled.
+        block_on(interval.tick());
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,29 @@
+//! The [crate::time] module provides the utilities needed to track time within the runtime. All
+//! of the timers here are implemented as [crate::io_uring::Completion]s around the
+//! [io_uring::opcode::Timeout] operation and are driven by the same [crate::io_uring::UringDriver]
+//! that handles any other I/O on the worker they are created on.
+//!
+//! This module exposes the following:
+//! - [sleep] and [sleep_until] which return a [Sleep] future that completes once the requested
+//!   amount of time has passed.
+//! - [timeout] which returns a [Timeout] future that puts a deadline on any other future.
+//! - [interval] which returns an [Interval] stream that yields on a fixed period, using a single
+//!   multi-shot timeout under the hood.
+//! - [Deadline] which is a cheap resettable timer, backed by a per worker timer wheel rather than
+//!   a kernel timeout, meant for tracking idle and keepalive timeouts on large numbers of
+//!   connections.
+//! - [pause], [resume] and [advance] which allow for controlling the clock of the current runtime
+//!   manually, for writing fast and deterministic tests of time dependent logic.
+
+pub(crate) mod clock;
+mod deadline;
+mod interval;
+mod sleep;
+mod timeout;
+pub(crate) mod wheel;
+
+pub use clock::{advance, is_paused, now, pause, resume, Advance};
+pub use deadline::Deadline;
+pub use interval::{interval, Interval};
+pub use sleep::{sleep, sleep_until, Sleep};
+pub use timeout::{timeout, Timeout};
@@ -0,0 +1,169 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::Future;
+use io_uring::{cqueue, opcode, squeue, types::Timespec};
+use nix::libc;
+
+use crate::{
+    io_uring::{Completion, CompletionStatus, Registration},
+    sync::OneShot,
+};
+
+use super::{clock, Deadline};
+
+struct SleepCompletion {
+    deadline: Instant,
+    timespec: Pin<Box<Timespec>>,
+    result: OneShot<io::Result<()>>,
+}
+
+impl Completion for SleepCompletion {
+    fn resolve(&self, value: cqueue::Entry) -> CompletionStatus {
+        // The timer firing is reported as -ETIME, anything else means the kernel gave up on the
+        // timeout before the deadline was reached.
+        let result = match value.result() {
+            result if result == -libc::ETIME => Ok(()),
+            result => Err(io::Error::from_raw_os_error(-result)),
+        };
+        self.result.complete(result);
+        CompletionStatus::Finalized
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        // Calculate the remaining time at submission, so that the deadline is honored regardless
+        // of how long this completion took to be submitted.
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        *self.timespec = Timespec::from(remaining);
+        opcode::Timeout::new(&*self.timespec as *const _).build()
+    }
+}
+
+enum Inner {
+    Kernel {
+        // Held to keep the timeout armed until we are dropped.
+        registration: Registration<SleepCompletion>,
+        result: OneShot<io::Result<()>>,
+    },
+    Wheel(Deadline),
+}
+
+/// This represents a single use future that completes once its deadline has been reached. This is
+/// returned by the [sleep] and [sleep_until] functions. Should the kernel fail the timeout before
+/// the deadline, the sleep falls back to waiting on a [Deadline] instead.
+///
+/// The timeout is only submitted to the ring once the future is first polled, rather than when
+/// the future is created. The deadline is fixed at creation regardless.
+pub struct Sleep {
+    deadline: Instant,
+    inner: Inner,
+}
+
+impl Sleep {
+    pub(crate) fn new(deadline: Instant) -> Sleep {
+        // While the clock is paused there is no point in involving the kernel, the paused clock
+        // tracks the deadline for us instead.
+        if clock::is_paused() {
+            return Sleep {
+                deadline,
+                inner: Inner::Wheel(Deadline::new_at(deadline)),
+            };
+        }
+
+        let result = OneShot::new();
+        let op = SleepCompletion {
+            deadline,
+            timespec: Box::pin(Timespec::new()),
+            result: result.clone(),
+        };
+        Sleep {
+            deadline,
+            inner: Inner::Kernel {
+                registration: Registration::new(op),
+                result,
+            },
+        }
+    }
+
+    /// Returns the [Instant] at which this [Sleep] will complete.
+    pub fn deadline(&self) -> Instant {
+        match self.inner {
+            Inner::Kernel { .. } => self.deadline,
+            Inner::Wheel(ref deadline) => deadline.deadline(),
+        }
+    }
+
+    /// Returns whether or not the deadline for this [Sleep] has been reached.
+    pub fn is_elapsed(&self) -> bool {
+        clock::now() >= self.deadline()
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.inner {
+            Inner::Kernel {
+                ref mut registration,
+                ref result,
+            } => {
+                if registration.poll_register(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                result.set_waker(cx.waker().clone());
+                match result.take() {
+                    Some(Ok(())) => Poll::Ready(()),
+                    Some(Err(_)) => {
+                        self.inner = Inner::Wheel(Deadline::new_at(self.deadline));
+                        self.poll(cx)
+                    }
+                    None => Poll::Pending,
+                }
+            }
+            Inner::Wheel(ref mut deadline) => Pin::new(deadline).poll(cx),
+        }
+    }
+}
+
+/// Wait until the specified [Duration] has elapsed, this returns a [Sleep] future which when
+/// polled to completion will have waited for at least `duration`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use libuio::time;
+///
+/// #[libuio::main]
+/// async fn main() {
+///     time::sleep(Duration::from_secs(1)).await;
+///     println!("One second later!");
+/// }
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(clock::now() + duration)
+}
+
+/// Wait until the specified deadline has been reached, this returns a [Sleep] future which when
+/// polled to completion will have waited until at least `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::block_on;
+
+    #[test]
+    fn test_sleep() {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
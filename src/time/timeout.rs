@@ -0,0 +1,113 @@
+use std::{
+    io::{self, ErrorKind},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::Future;
+
+use super::{clock, Sleep};
+
+/// This represents a future with a deadline attached, it will return the result of the inner
+/// future if it completes before the deadline and an [std::io::Error] of kind
+/// [ErrorKind::TimedOut] otherwise. This is returned by the [timeout] function.
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    pub(crate) fn new(future: F, deadline: Instant) -> Timeout<F> {
+        Timeout {
+            future,
+            sleep: Sleep::new(deadline),
+        }
+    }
+
+    /// Returns a reference to the inner future.
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    /// Consumes the [Timeout] returning the inner future.
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: Future,
+{
+    type Output = io::Result<F::Output>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: We never move the inner future out of the pinned reference, and [Sleep] is Unpin
+        // so it is safe to hand out a mutable reference to it.
+        let (future, sleep) = unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.future), &mut this.sleep)
+        };
+
+        // Always give the inner future a chance to complete first, so that we don't error out on a
+        // future that is ready just because the timer also happened to fire.
+        if let Poll::Ready(result) = future.poll(cx) {
+            return Poll::Ready(Ok(result));
+        }
+
+        match Pin::new(sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "deadline has elapsed",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Require the given future to complete within the specified [Duration], this returns a
+/// [Timeout] future that resolves to the output of `future` or an [std::io::Error] of kind
+/// [ErrorKind::TimedOut] if the deadline is reached first.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{io, net::SocketAddr, time::Duration};
+///
+/// use libuio::{net::TcpStream, time};
+///
+/// #[libuio::main]
+/// async fn main() -> io::Result<()> {
+///     let remote_addr: SocketAddr = "[::1]:9091".parse().unwrap();
+///     let mut client = TcpStream::new(false)?;
+///
+///     time::timeout(Duration::from_secs(5), client.connect(&remote_addr)).await??;
+///     Ok(())
+/// }
+/// ```
+pub fn timeout<F>(duration: Duration, future: F) -> Timeout<F>
+where
+    F: Future,
+{
+    Timeout::new(future, clock::now() + duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::pending;
+
+    use super::*;
+    use crate::{executor::block_on, time::sleep};
+
+    #[test]
+    fn test_timeout() {
+        let result = block_on(timeout(Duration::from_millis(20), pending::<()>()));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+
+        let result = block_on(timeout(
+            Duration::from_secs(5),
+            sleep(Duration::from_millis(10)),
+        ));
+        assert!(result.is_ok());
+    }
+}
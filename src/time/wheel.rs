@@ -0,0 +1,396 @@
+use std::{
+    mem,
+    pin::Pin,
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+use io_uring::{cqueue, opcode, squeue, types::Timespec};
+use slab::Slab;
+
+use crate::io_uring::{Completion, CompletionStatus};
+
+/// The number of bits of the tick used to index the slots of a level.
+const SLOT_BITS: u32 = 6;
+
+/// The number of slots per level.
+const SLOTS: usize = 1 << SLOT_BITS;
+
+/// The number of levels in the wheel, with a tick of one millisecond this covers a little over
+/// two years worth of time before deadlines are clamped.
+const LEVELS: usize = 6;
+
+/// The maximum tick the wheel can represent.
+const MAX_TICK: u64 = (1 << (SLOT_BITS as usize * LEVELS)) - 1;
+
+struct Entry {
+    when: u64,
+    placed: u64,
+    fired: bool,
+    waker: Option<Waker>,
+    /// The level, slot and position within that slot this entry is currently placed at, if any.
+    slot: Option<(usize, usize, usize)>,
+}
+
+struct Expiration {
+    level: usize,
+    slot: usize,
+    deadline: u64,
+}
+
+struct Level {
+    occupied: u64,
+    slots: [Vec<usize>; SLOTS],
+}
+
+impl Level {
+    fn new() -> Level {
+        Level {
+            occupied: 0,
+            slots: std::array::from_fn(|_| Vec::new()),
+        }
+    }
+}
+
+/// A [TimerWheel] is a hierarchical hashed timer wheel, that tracks deadlines with millisecond
+/// resolution in userspace. Each level has 64 slots, with each slot at level `n` covering `64^n`
+/// ticks, so inserting and resetting a deadline is O(1) regardless of how many deadlines are being
+/// tracked. Deadlines cascade down the levels as time advances until they land in the lowest level
+/// and fire. Each entry tracks its position within its slot, so removing a deadline is O(1) as
+/// well.
+///
+/// Resetting a deadline to a later time is done lazily, the entry is left where it is and simply
+/// re-placed once its current slot is processed. This keeps the common case of pushing an idle
+/// timeout back on every read as cheap as updating a single integer.
+pub(crate) struct TimerWheel {
+    start: Instant,
+    elapsed: u64,
+    entries: Slab<Entry>,
+    levels: Vec<Level>,
+    /// The paused and wall clock time at which the paused clock driving this wheel was resumed.
+    resumed: Option<(Instant, Instant)>,
+}
+
+impl TimerWheel {
+    pub(crate) fn new() -> TimerWheel {
+        TimerWheel {
+            start: Instant::now(),
+            elapsed: 0,
+            entries: Slab::new(),
+            levels: (0..LEVELS).map(|_| Level::new()).collect(),
+            resumed: None,
+        }
+    }
+
+    /// Convert the given [Instant] into a tick, rounding up so that deadlines never fire early.
+    pub(crate) fn deadline_tick(&self, deadline: Instant) -> u64 {
+        let since = deadline.saturating_duration_since(self.start);
+        since.as_nanos().div_ceil(1_000_000).min(MAX_TICK as u128) as u64
+    }
+
+    /// Convert the given [Instant] into a tick, rounding down.
+    pub(crate) fn now_tick(&self, now: Instant) -> u64 {
+        let since = now.saturating_duration_since(self.start);
+        since.as_millis().min(MAX_TICK as u128) as u64
+    }
+
+    /// Convert the given tick back into an [Instant].
+    pub(crate) fn tick_instant(&self, tick: u64) -> Instant {
+        self.start + Duration::from_millis(tick)
+    }
+
+    /// Insert a new deadline at the given tick, returning its key.
+    pub(crate) fn insert(&mut self, when: u64) -> usize {
+        let key = self.entries.insert(Entry {
+            when,
+            placed: when,
+            fired: false,
+            waker: None,
+            slot: None,
+        });
+        self.place(key);
+        key
+    }
+
+    /// Reset the deadline for the given key to a new tick. Moving a deadline later is lazy, while
+    /// moving it earlier re-places it in the wheel immediately.
+    pub(crate) fn reset(&mut self, key: usize, when: u64) {
+        let entry = &mut self.entries[key];
+        let replace = entry.fired || when < entry.placed;
+        entry.when = when;
+        entry.fired = false;
+        if replace {
+            entry.placed = when;
+            self.place(key);
+        }
+    }
+
+    /// Remove the deadline for the given key, clearing it out of the slot it was placed in.
+    pub(crate) fn remove(&mut self, key: usize) {
+        if self.entries.contains(key) {
+            self.unlink(key);
+            self.entries.remove(key);
+        }
+    }
+
+    /// Check whether the deadline for the given key has fired, storing the [Waker] to notify once
+    /// it does if not.
+    pub(crate) fn poll(&mut self, key: usize, waker: &Waker) -> bool {
+        let entry = &mut self.entries[key];
+        if entry.fired {
+            return true;
+        }
+        match entry.waker {
+            Some(ref current) if current.will_wake(waker) => {}
+            _ => entry.waker = Some(waker.clone()),
+        }
+        false
+    }
+
+    /// Mark this wheel as no longer being driven by the paused clock, which was resumed at the
+    /// given paused time, returning the [Waker]s of all pending deadlines so that they can move
+    /// over to the wall clock.
+    pub(crate) fn resume(&mut self, paused: Instant) -> Vec<Waker> {
+        self.resumed = Some((paused, Instant::now()));
+        self.entries
+            .iter_mut()
+            .filter(|(_, entry)| !entry.fired)
+            .filter_map(|(_, entry)| entry.waker.take())
+            .collect()
+    }
+
+    /// Returns whether or not the paused clock driving this wheel has been resumed.
+    pub(crate) fn is_resumed(&self) -> bool {
+        self.resumed.is_some()
+    }
+
+    /// Translate the given deadline onto the wall clock, if the paused clock driving this wheel has
+    /// been resumed, keeping the time it had left as of the resume.
+    pub(crate) fn rebase(&self, deadline: Instant) -> Instant {
+        match self.resumed {
+            Some((paused, resumed)) => resumed + deadline.saturating_duration_since(paused),
+            None => deadline,
+        }
+    }
+
+    /// Returns the tick at which the next slot in the wheel needs to be processed if any, this
+    /// may be earlier than the actual next deadline when the earliest slot is on a higher level.
+    pub(crate) fn next_deadline(&self) -> Option<u64> {
+        self.next_expiration().map(|expiration| expiration.deadline)
+    }
+
+    /// Advance the wheel to the given tick, firing any expired deadlines and returning the
+    /// [Waker]s that need to be notified.
+    pub(crate) fn advance(&mut self, now: u64) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        while let Some(expiration) = self.next_expiration() {
+            if expiration.deadline > now {
+                break;
+            }
+            self.elapsed = expiration.deadline;
+
+            let level = &mut self.levels[expiration.level];
+            let bucket = mem::take(&mut level.slots[expiration.slot]);
+            level.occupied &= !(1 << expiration.slot);
+
+            // Removed and re-placed deadlines are unlinked from their slot, so every key left in
+            // the bucket is a pending deadline.
+            for key in bucket {
+                let entry = &mut self.entries[key];
+                entry.slot = None;
+
+                // Either the deadline has passed and we fire it, or it has been pushed back or
+                // needs to cascade down to a lower level so we place it again.
+                if entry.when <= now {
+                    entry.fired = true;
+                    if let Some(waker) = entry.waker.take() {
+                        wakers.push(waker);
+                    }
+                } else {
+                    entry.placed = entry.when;
+                    self.place(key);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+        wakers
+    }
+
+    /// Find the next slot that needs processing, the lowest occupied level always holds the
+    /// earliest slot as each level only holds deadlines beyond the range of the levels below it.
+    fn next_expiration(&self) -> Option<Expiration> {
+        let (level, slots) = self
+            .levels
+            .iter()
+            .enumerate()
+            .find(|(_, slots)| slots.occupied != 0)?;
+
+        let shift = level as u32 * SLOT_BITS;
+        let range = 1u64 << shift;
+        let current = ((self.elapsed >> shift) as usize) & (SLOTS - 1);
+
+        let offset = slots.occupied.rotate_right(current as u32).trailing_zeros() as usize;
+        let slot = (current + offset) % SLOTS;
+
+        let level_start = self.elapsed & !((range << SLOT_BITS) - 1);
+        let mut deadline = level_start + slot as u64 * range;
+        if slot < current {
+            deadline += range << SLOT_BITS;
+        }
+
+        Some(Expiration {
+            level,
+            slot,
+            deadline: deadline.max(self.elapsed),
+        })
+    }
+
+    /// Take the given key out of the slot it is currently placed in, if any, so that neither
+    /// removed nor re-placed deadlines keep slots occupied. The last entry of the slot is swapped
+    /// into its position, so this doesn't depend on how many deadlines share the slot.
+    fn unlink(&mut self, key: usize) {
+        let (level, slot, index) = match self.entries[key].slot.take() {
+            Some(location) => location,
+            None => return,
+        };
+        let bucket = &mut self.levels[level].slots[slot];
+        bucket.swap_remove(index);
+        if let Some(&moved) = bucket.get(index) {
+            self.entries[moved].slot = Some((level, slot, index));
+        }
+        if bucket.is_empty() {
+            self.levels[level].occupied &= !(1 << slot);
+        }
+    }
+
+    fn place(&mut self, key: usize) {
+        self.unlink(key);
+
+        let entry = &mut self.entries[key];
+        let when = entry.when.max(self.elapsed);
+
+        // The level is picked by the most significant bit that differs between the deadline and
+        // the current tick, and the slot by the bits of the deadline at that level.
+        let masked = (self.elapsed ^ when) | (SLOTS as u64 - 1);
+        let significant = 63 - masked.leading_zeros();
+        let level = ((significant / SLOT_BITS) as usize).min(LEVELS - 1);
+        let slot = ((when >> (level as u32 * SLOT_BITS)) as usize) & (SLOTS - 1);
+
+        let bucket = &mut self.levels[level].slots[slot];
+        entry.slot = Some((level, slot, bucket.len()));
+        bucket.push(key);
+        self.levels[level].occupied |= 1 << slot;
+    }
+}
+
+/// The single kernel timeout used to wake the [crate::io_uring::UringDriver] when the next slot in
+/// its [TimerWheel] is due, the wheel itself is advanced by the driver on every iteration of the
+/// event loop so this simply needs to finish.
+pub(crate) struct WheelTimeout {
+    timespec: Pin<Box<Timespec>>,
+}
+
+impl WheelTimeout {
+    pub(crate) fn new(timeout: Duration) -> WheelTimeout {
+        WheelTimeout {
+            timespec: Box::pin(Timespec::from(timeout)),
+        }
+    }
+}
+
+impl Completion for WheelTimeout {
+    fn resolve(&self, _: cqueue::Entry) -> CompletionStatus {
+        CompletionStatus::Finalized
+    }
+
+    fn as_entry(&mut self) -> squeue::Entry {
+        opcode::Timeout::new(&*self.timespec as *const _).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::task::noop_waker;
+
+    use super::*;
+
+    fn fired(wheel: &mut TimerWheel, key: usize) -> bool {
+        wheel.poll(key, &noop_waker())
+    }
+
+    #[test]
+    fn test_wheel_cascade() {
+        let mut wheel = TimerWheel::new();
+        let ticks = [0, 5, 63, 64, 70, 4095, 5000, 300_000];
+        let keys: Vec<_> = ticks.iter().map(|&tick| wheel.insert(tick)).collect();
+
+        let mut now = 0;
+        while now <= 300_000 {
+            wheel.advance(now);
+            for (key, &tick) in keys.iter().zip(ticks.iter()) {
+                assert_eq!(
+                    fired(&mut wheel, *key),
+                    tick <= now,
+                    "tick {} at {}",
+                    tick,
+                    now
+                );
+            }
+            now += 1 + now / 7;
+        }
+
+        wheel.advance(300_000);
+        assert!(keys.iter().all(|key| fired(&mut wheel, *key)));
+        assert!(wheel.next_deadline().is_none());
+    }
+
+    #[test]
+    fn test_wheel_reset() {
+        let mut wheel = TimerWheel::new();
+        let later = wheel.insert(10);
+        let earlier = wheel.insert(10_000);
+
+        // Push one back lazily and pull the other forward.
+        wheel.reset(later, 20_000);
+        wheel.reset(earlier, 15);
+
+        wheel.advance(15);
+        assert!(!fired(&mut wheel, later));
+        assert!(fired(&mut wheel, earlier));
+
+        wheel.advance(19_999);
+        assert!(!fired(&mut wheel, later));
+        wheel.advance(20_000);
+        assert!(fired(&mut wheel, later));
+
+        // Resetting a fired deadline re-arms it, and removed deadlines are ignored.
+        wheel.reset(later, 20_050);
+        assert!(!fired(&mut wheel, later));
+        wheel.remove(earlier);
+        wheel.advance(20_050);
+        assert!(fired(&mut wheel, later));
+    }
+
+    #[test]
+    fn test_wheel_remove() {
+        let mut wheel = TimerWheel::new();
+        let key = wheel.insert(100);
+        let moved = wheel.insert(5_000);
+        wheel.reset(moved, 256);
+
+        // Neither a removed deadline nor the old slot of one pulled forward keeps the wheel busy.
+        wheel.remove(key);
+        assert_eq!(wheel.next_deadline(), Some(256));
+        wheel.remove(moved);
+        assert!(wheel.next_deadline().is_none());
+
+        // Removing from the middle of a shared slot keeps the others in it intact.
+        let keys: Vec<_> = (0..4).map(|_| wheel.insert(5_000)).collect();
+        wheel.remove(keys[1]);
+        wheel.remove(keys[0]);
+        wheel.advance(5_000);
+        assert!(fired(&mut wheel, keys[2]));
+        assert!(fired(&mut wheel, keys[3]));
+        assert!(wheel.next_deadline().is_none());
+    }
+}